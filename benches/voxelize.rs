@@ -0,0 +1,94 @@
+// Compares single threaded and parallel voxelization of dense synthetic scans and
+// checks that both produce the same octree. Run with `cargo bench --bench voxelize`.
+use glam::Vec3;
+use model_voxelization::mesh::{normalize_meshes, TriangleMesh};
+use model_voxelization::octree::SparseVoxelOctree;
+use model_voxelization::voxelization::{voxelize_mesh_with_threads, VoxelizationSettings};
+use std::f32::consts::PI;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Runs per measurement; the fastest one is reported
+const RUNS: usize = 3;
+
+// Bumpy closed sphere with about 4 * segments^2 triangles, standing in for a scan
+fn scan_mesh(segments: u32) -> TriangleMesh {
+    let rings = segments;
+    let sectors = 2 * segments;
+    let mut positions = vec![Vec3::Y, -Vec3::Y];
+    for ring in 1..rings {
+        let theta = PI * ring as f32 / rings as f32;
+        for sector in 0..sectors {
+            let phi = 2.0 * PI * sector as f32 / sectors as f32;
+            let radius = 1.0 + 0.05 * (7.0 * theta).sin() * (5.0 * phi).sin();
+            positions.push(
+                radius
+                    * Vec3::new(
+                        theta.sin() * phi.cos(),
+                        theta.cos(),
+                        theta.sin() * phi.sin(),
+                    ),
+            );
+        }
+    }
+
+    let vertex = |ring: u32, sector: u32| 2 + (ring - 1) * sectors + sector % sectors;
+    let mut indices = Vec::new();
+    for sector in 0..sectors {
+        indices.extend([0, vertex(1, sector + 1), vertex(1, sector)]);
+        indices.extend([1, vertex(rings - 1, sector), vertex(rings - 1, sector + 1)]);
+        for ring in 1..rings - 1 {
+            let (a, b) = (vertex(ring, sector), vertex(ring, sector + 1));
+            let (c, d) = (vertex(ring + 1, sector), vertex(ring + 1, sector + 1));
+            indices.extend([a, b, d, a, d, c]);
+        }
+    }
+    TriangleMesh::new("scan", positions, indices)
+}
+
+fn measure(
+    mesh: &TriangleMesh,
+    settings: &VoxelizationSettings,
+    threads: usize,
+) -> (Duration, SparseVoxelOctree) {
+    let mut fastest = Duration::MAX;
+    let mut octree = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        octree = Some(voxelize_mesh_with_threads(mesh, settings, threads));
+        fastest = fastest.min(start.elapsed());
+    }
+    (fastest, octree.unwrap())
+}
+
+fn main() {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut results = Vec::new();
+
+    for (segments, depth) in [(100, 8), (300, 9), (500, 10)] {
+        let mut meshes = [scan_mesh(segments)];
+        normalize_meshes(&mut meshes);
+        let settings = VoxelizationSettings {
+            octree_depth: depth,
+            ..Default::default()
+        };
+
+        let (single, expected) = measure(&meshes[0], &settings, 1);
+        let (parallel, octree) = measure(&meshes[0], &settings, threads);
+        assert!(octree == expected, "parallel voxelization differs");
+        results.push((meshes[0].triangle_count(), depth, single, parallel));
+    }
+
+    println!();
+    println!("triangles  depth  1 thread  {} threads  speedup", threads);
+    for (triangles, depth, single, parallel) in results {
+        println!(
+            "{:>9}  {:>5}  {:>8.0?}  {:>10.0?}  {:>6.1}x",
+            triangles,
+            depth,
+            single,
+            parallel,
+            single.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+}
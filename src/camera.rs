@@ -1,7 +1,6 @@
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
-    window::PrimaryWindow,
 };
 use bevy_egui::EguiContext;
 
@@ -65,7 +64,6 @@ pub fn setup_camera(mut commands: Commands) {
 }
 
 pub fn camera_controller_system(
-    window_q: Query<&Window, With<PrimaryWindow>>,
     mut mouse_motion: EventReader<MouseMotion>,
     mut mouse_wheel: EventReader<MouseWheel>,
     mouse_buttons: Res<ButtonInput<MouseButton>>,
@@ -86,8 +84,6 @@ pub fn camera_controller_system(
         }
     }
 
-    let _window = window_q.single();
-
     for (mut transform, mut controller) in query.iter_mut() {
         // Orbit rotation (left mouse button)
         if mouse_buttons.pressed(controller.orbit_button) {
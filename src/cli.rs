@@ -0,0 +1,717 @@
+use model_voxelization::{
+    export::{
+        export_model, export_sdf, BlockPalette, ExportFormat, ExportOptions, MeshUnits, SdfFormat,
+        SliceAxis, VdbGrid,
+    },
+    import::SUPPORTED_EXTENSIONS,
+    meshing::MeshingMode,
+    sdf::{sdf_file, SDF_MAX_DEPTH},
+    voxelization::{
+        voxelize_file, FillMode, IntersectionTest, VoxelizationSettings, MAX_OCTREE_DEPTH,
+    },
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Subcommand that runs the voxelizer headlessly instead of opening the window
+pub const SUBCOMMAND: &str = "voxelize";
+
+fn usage() -> String {
+    let formats: Vec<&str> = ExportFormat::ALL.iter().map(|f| f.extension()).collect();
+    let sdf_formats: Vec<&str> = SdfFormat::ALL.iter().map(|f| f.extension()).collect();
+    format!(
+        "\
+Usage: model_voxelization {SUBCOMMAND} [OPTIONS] <INPUT>...
+
+Voxelize model files without opening a window. Inputs can be files,
+directories (searched recursively for {extensions} files) or glob patterns.
+
+Options:
+  -d, --depth <N>        Octree depth, 1-{MAX_OCTREE_DEPTH} (default: 6)
+  -m, --mode <MODE>      Fill mode: surface, solid (default: surface)
+  -t, --test <TEST>      Surface test: exact, approximate (default: exact)
+  -o, --output <PATH>    Output file, or output directory when there are several
+                         inputs, keeping their paths below the input directories
+                         and glob patterns (default: next to each input file)
+  -f, --format <FORMAT>  Output format: {formats} (default: taken from the
+                         output file extension, otherwise {default_format})
+  -u, --units <UNITS>    Units of mesh, glTF, NRRD and SDF outputs: normalized, original
+                         (default: normalized)
+  -M, --mesher <MESHER>  Mesher of mesh and glTF formats: cubes, greedy,
+                         smooth (default: cubes)
+  -b, --blocks <BLOCKS>  Minecraft block for every voxel, or concrete to give each
+                         submodel the concrete block nearest its submodel color
+                         (default: minecraft:stone)
+  -g, --grid <GRID>      OpenVDB grid: occupancy, level-set (default: occupancy)
+  -a, --axis <AXIS>      Axis of PNG slices: x, y, z (default: z)
+  -s, --sdf              Write the signed distance field of the model instead of
+                         its voxels, as {sdf_formats} (default: {default_sdf_format});
+                         depth {SDF_MAX_DEPTH} at most
+  -h, --help             Print this help
+",
+        extensions = SUPPORTED_EXTENSIONS.join(", "),
+        formats = formats.join(", "),
+        sdf_formats = sdf_formats.join(", "),
+        default_sdf_format = SdfFormat::Nrrd.extension(),
+        default_format = ExportFormat::VoxelList.extension(),
+    )
+}
+
+// Whether the command line asks for batch mode rather than the GUI
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    matches!(
+        args.first().map(String::as_str),
+        Some(SUBCOMMAND) | Some("-h") | Some("--help")
+    )
+}
+
+// A model file to convert, with its path below the directory or glob pattern it
+// was found through, which is kept below the output directory
+struct Input {
+    path: PathBuf,
+    relative: PathBuf,
+}
+
+struct BatchOptions {
+    settings: VoxelizationSettings,
+    output: Option<PathBuf>,
+    format: Option<ExportFormat>,
+    export_options: ExportOptions,
+    // Write signed distance fields instead of voxels
+    sdf: bool,
+    inputs: Vec<String>,
+}
+
+// Run batch mode with the command line arguments (without the program name).
+// Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.first().map(String::as_str) != Some(SUBCOMMAND) {
+        print!("{}", usage());
+        return 0;
+    }
+
+    let options = match parse_options(&args[1..]) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", usage());
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, usage());
+            return 2;
+        }
+    };
+
+    let inputs = match collect_inputs(&options.inputs) {
+        Ok(inputs) => inputs,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+
+    let multiple = inputs.len() > 1;
+    let format = match resolve_format(&options, multiple) {
+        Ok(format) => format,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 2;
+        }
+    };
+    let sdf_format = match SdfFormat::from_extension(format.extension()) {
+        Some(sdf_format) if options.sdf => Some(sdf_format),
+        None if options.sdf => {
+            let formats: Vec<&str> = SdfFormat::ALL.iter().map(|f| f.extension()).collect();
+            eprintln!(
+                "error: signed distance fields can be written as {}",
+                formats.join(", ")
+            );
+            return 2;
+        }
+        _ => None,
+    };
+
+    if let Some(output) = &options.output {
+        if multiple {
+            if let Err(err) = std::fs::create_dir_all(output) {
+                eprintln!("error: cannot create {}: {}", output.display(), err);
+                return 1;
+            }
+        }
+    }
+
+    let outputs = match output_paths(&inputs, options.output.as_deref(), multiple, format) {
+        Ok(outputs) => outputs,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+
+    let mut failures = 0;
+    for (input, output) in inputs.iter().zip(outputs) {
+        let input = &input.path;
+        if output == *input {
+            eprintln!(
+                "error: {}: output would overwrite the input, use --output",
+                input.display()
+            );
+            failures += 1;
+            continue;
+        }
+        if let Some(parent) = output.parent().filter(|_| multiple) {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                eprintln!("error: cannot create {}: {}", parent.display(), err);
+                failures += 1;
+                continue;
+            }
+        }
+        println!("Voxelizing {} -> {}", input.display(), output.display());
+
+        let result = match sdf_format {
+            Some(sdf_format) => sdf_file(input, options.settings.octree_depth)
+                .map_err(|err| err.to_string())
+                .and_then(|sdf| {
+                    export_sdf(&sdf, &output, sdf_format, &options.export_options)
+                        .map_err(|err| err.to_string())
+                }),
+            None => voxelize_file(input, &options.settings)
+                .map_err(|err| err.to_string())
+                .and_then(|model| {
+                    export_model(&model, &output, format, &options.export_options)
+                        .map_err(|err| err.to_string())
+                }),
+        };
+
+        if let Err(message) = result {
+            eprintln!("error: {}: {}", input.display(), message);
+            failures += 1;
+        }
+    }
+
+    println!("Processed {} file(s), {} failed", inputs.len(), failures);
+    if failures > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+// Parse the options after the subcommand. Returns None when help was requested.
+fn parse_options(args: &[String]) -> Result<Option<BatchOptions>, String> {
+    let mut options = BatchOptions {
+        settings: VoxelizationSettings::default(),
+        output: None,
+        format: None,
+        export_options: ExportOptions::default(),
+        sdf: false,
+        inputs: Vec::new(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-d" | "--depth" => {
+                let depth = value(arg)?;
+                options.settings.octree_depth = match depth.parse() {
+                    Ok(depth) if (1..=MAX_OCTREE_DEPTH).contains(&depth) => depth,
+                    _ => {
+                        return Err(format!(
+                            "invalid depth {}, expected 1-{}",
+                            depth, MAX_OCTREE_DEPTH
+                        ))
+                    }
+                };
+            }
+            "-m" | "--mode" => {
+                options.settings.fill_mode = match value(arg)?.as_str() {
+                    "surface" => FillMode::Surface,
+                    "solid" => FillMode::Solid,
+                    mode => {
+                        return Err(format!("invalid mode {}, expected surface or solid", mode))
+                    }
+                };
+            }
+            "-t" | "--test" => {
+                options.settings.intersection_test = match value(arg)?.as_str() {
+                    "exact" => IntersectionTest::Exact,
+                    "approximate" => IntersectionTest::Approximate,
+                    test => {
+                        return Err(format!(
+                            "invalid surface test {}, expected exact or approximate",
+                            test
+                        ))
+                    }
+                };
+            }
+            "-o" | "--output" => options.output = Some(PathBuf::from(value(arg)?)),
+            "-f" | "--format" => {
+                let format = value(arg)?;
+                options.format = Some(
+                    ExportFormat::from_extension(&format)
+                        .ok_or_else(|| format!("unknown output format {}", format))?,
+                );
+            }
+            "-u" | "--units" => {
+                options.export_options.mesh_units = match value(arg)?.as_str() {
+                    "normalized" => MeshUnits::Normalized,
+                    "original" => MeshUnits::Original,
+                    units => {
+                        return Err(format!(
+                            "invalid units {}, expected normalized or original",
+                            units
+                        ))
+                    }
+                };
+            }
+            "-M" | "--mesher" => {
+                options.export_options.meshing = match value(arg)?.as_str() {
+                    "cubes" => MeshingMode::Cubes,
+                    "greedy" => MeshingMode::Greedy,
+                    "smooth" => MeshingMode::SurfaceNets,
+                    mesher => {
+                        return Err(format!(
+                            "invalid mesher {}, expected cubes, greedy or smooth",
+                            mesher
+                        ))
+                    }
+                };
+            }
+            "-b" | "--blocks" => {
+                options.export_options.block_palette = match value(arg)?.as_str() {
+                    "concrete" => BlockPalette::concrete(),
+                    block => BlockPalette::Single(block.to_string()),
+                };
+            }
+            "-g" | "--grid" => {
+                options.export_options.vdb_grid = match value(arg)?.as_str() {
+                    "occupancy" => VdbGrid::Occupancy,
+                    "level-set" => VdbGrid::LevelSet,
+                    grid => {
+                        return Err(format!(
+                            "invalid grid {}, expected occupancy or level-set",
+                            grid
+                        ))
+                    }
+                };
+            }
+            "-a" | "--axis" => {
+                options.export_options.slice_axis = match value(arg)?.as_str() {
+                    "x" => SliceAxis::X,
+                    "y" => SliceAxis::Y,
+                    "z" => SliceAxis::Z,
+                    axis => return Err(format!("invalid axis {}, expected x, y or z", axis)),
+                };
+            }
+            "-s" | "--sdf" => options.sdf = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            input => options.inputs.push(input.to_string()),
+        }
+    }
+
+    if options.inputs.is_empty() {
+        return Err("no input files given".to_string());
+    }
+    if options.sdf && options.settings.octree_depth > SDF_MAX_DEPTH {
+        return Err(format!(
+            "signed distance fields are limited to depth {}",
+            SDF_MAX_DEPTH
+        ));
+    }
+    Ok(Some(options))
+}
+
+fn is_supported_model(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+// Expand files, directories and glob patterns into the list of model files
+fn collect_inputs(patterns: &[String]) -> Result<Vec<Input>, String> {
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if path.is_dir() {
+            collect_directory(path, path, &mut files)
+                .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+        } else if path.is_file() {
+            files.push(Input {
+                path: path.to_path_buf(),
+                relative: path.file_name().map(PathBuf::from).unwrap_or_default(),
+            });
+        } else if pattern.contains(['*', '?', '[']) {
+            let matches = glob::glob(pattern)
+                .map_err(|err| format!("invalid glob pattern {}: {}", pattern, err))?;
+            let root = glob_root(path);
+            let found = files.len();
+            for entry in matches {
+                let entry = entry.map_err(|err| err.to_string())?;
+                if entry.is_dir() {
+                    collect_directory(&entry, &root, &mut files)
+                        .map_err(|err| format!("cannot read {}: {}", entry.display(), err))?;
+                } else if is_supported_model(&entry) {
+                    files.push(Input {
+                        relative: relative_path(&entry, &root),
+                        path: entry,
+                    });
+                }
+            }
+            if files.len() == found {
+                return Err(format!("no model files match {}", pattern));
+            }
+        } else {
+            return Err(format!("input not found: {}", pattern));
+        }
+    }
+
+    Ok(files)
+}
+
+// Leading components of a glob pattern without wildcards, which all matches share
+fn glob_root(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+// Path of a file below `root`, or its file name if it is not below it
+fn relative_path(path: &Path, root: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => path.file_name().map(PathBuf::from).unwrap_or_default(),
+    }
+}
+
+// Recursively add all supported model files below `dir`, in a stable order, with
+// their paths relative to `root`
+fn collect_directory(dir: &Path, root: &Path, files: &mut Vec<Input>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_directory(&entry, root, files)?;
+        } else if is_supported_model(&entry) {
+            files.push(Input {
+                relative: relative_path(&entry, root),
+                path: entry,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn resolve_format(options: &BatchOptions, multiple: bool) -> Result<ExportFormat, String> {
+    if let Some(format) = options.format {
+        return Ok(format);
+    }
+
+    // Single output file: use its extension
+    match &options.output {
+        Some(output) if !multiple && !output.is_dir() => {
+            let extension = output
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or_default();
+            ExportFormat::from_extension(extension).ok_or_else(|| {
+                format!(
+                    "cannot infer the output format from {}, use --format",
+                    output.display()
+                )
+            })
+        }
+        _ if options.sdf => Ok(ExportFormat::Nrrd),
+        _ => Ok(ExportFormat::VoxelList),
+    }
+}
+
+// The output of every input, or an error if two inputs would be written to the
+// same file, such as model.obj and model.stl
+fn output_paths(
+    inputs: &[Input],
+    output: Option<&Path>,
+    multiple: bool,
+    format: ExportFormat,
+) -> Result<Vec<PathBuf>, String> {
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| output_path(input, output, multiple, format))
+        .collect();
+    let mut written = HashMap::new();
+    for (input, output) in inputs.iter().zip(&outputs) {
+        if let Some(other) = written.insert(output, &input.path) {
+            return Err(format!(
+                "{} and {} would both be written to {}",
+                other.display(),
+                input.path.display(),
+                output.display()
+            ));
+        }
+    }
+    Ok(outputs)
+}
+
+// Where the output of an input is written. Inputs from directories and glob
+// patterns keep their subdirectories below an output directory, so files with the
+// same name in different directories do not overwrite each other.
+fn output_path(
+    input: &Input,
+    output: Option<&Path>,
+    multiple: bool,
+    format: ExportFormat,
+) -> PathBuf {
+    match output {
+        Some(output) if multiple || output.is_dir() => {
+            output.join(input.relative.with_extension(format.extension()))
+        }
+        Some(output) => output.to_path_buf(),
+        None => input.path.with_extension(format.extension()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn parse(line: &str) -> Result<Option<BatchOptions>, String> {
+        parse_options(&args(line))
+    }
+
+    fn input(path: &str, relative: &str) -> Input {
+        Input {
+            path: PathBuf::from(path),
+            relative: PathBuf::from(relative),
+        }
+    }
+
+    // Empty directory in the system temp directory, unique to this test process
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "model_voxelization_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn options_are_parsed_from_their_flags() {
+        let options = parse(
+            "-d 7 --mode solid -t approximate -o out -f glb -u original -M smooth \
+             -b concrete --grid level-set -a x a.obj b.stl",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.settings.octree_depth, 7);
+        assert_eq!(options.settings.fill_mode, FillMode::Solid);
+        assert_eq!(
+            options.settings.intersection_test,
+            IntersectionTest::Approximate
+        );
+        assert_eq!(options.output, Some(PathBuf::from("out")));
+        assert_eq!(options.format, Some(ExportFormat::Glb));
+        assert_eq!(options.export_options.mesh_units, MeshUnits::Original);
+        assert_eq!(options.export_options.meshing, MeshingMode::SurfaceNets);
+        assert_eq!(
+            options.export_options.block_palette,
+            BlockPalette::concrete()
+        );
+        assert_eq!(options.export_options.vdb_grid, VdbGrid::LevelSet);
+        assert_eq!(options.export_options.slice_axis, SliceAxis::X);
+        assert!(!options.sdf);
+        assert_eq!(options.inputs, ["a.obj", "b.stl"]);
+
+        let options = parse("model.obj --blocks minecraft:oak_planks -s")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            options.export_options.block_palette,
+            BlockPalette::Single("minecraft:oak_planks".to_string())
+        );
+        assert!(options.sdf);
+        assert_eq!(options.settings, VoxelizationSettings::default());
+
+        assert!(parse("model.obj --help").unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_options_are_reported() {
+        for (line, message) in [
+            ("model.obj -d", "missing value for -d"),
+            ("-d 0 model.obj", "invalid depth 0"),
+            ("-d 11 model.obj", "invalid depth 11"),
+            ("-m hollow model.obj", "invalid mode hollow"),
+            ("-f xyz model.obj", "unknown output format xyz"),
+            ("-a w model.obj", "invalid axis w"),
+            ("--verbose model.obj", "unknown option --verbose"),
+            ("-d 5", "no input files given"),
+            ("-s -d 10 model.obj", "limited to depth"),
+        ] {
+            let error = parse(line).err().unwrap();
+            assert!(error.contains(message), "{}: {}", line, error);
+        }
+    }
+
+    #[test]
+    fn format_comes_from_the_flag_or_the_output_file() {
+        let format =
+            |line: &str, multiple| resolve_format(&parse(line).unwrap().unwrap(), multiple);
+        assert_eq!(format("a.obj", false), Ok(ExportFormat::VoxelList));
+        assert_eq!(format("-s a.obj", false), Ok(ExportFormat::Nrrd));
+        assert_eq!(format("-o a.binvox a.obj", false), Ok(ExportFormat::Binvox));
+        assert_eq!(
+            format("-o a.binvox -f vox a.obj", false),
+            Ok(ExportFormat::Vox)
+        );
+        // Several inputs go to a directory, whatever its name
+        assert_eq!(
+            format("-o out.binvox a.obj b.obj", true),
+            Ok(ExportFormat::VoxelList)
+        );
+        assert!(format("-o out.model a.obj", false)
+            .unwrap_err()
+            .contains("use --format"));
+    }
+
+    #[test]
+    fn glob_root_is_the_path_before_the_first_wildcard() {
+        for (pattern, root) in [
+            ("models/**/*.obj", "models"),
+            ("assets/cars/car_?.stl", "assets/cars"),
+            ("scans/[ab]*/mesh.ply", "scans"),
+            ("*.glb", ""),
+            ("/data/models/*.fbx", "/data/models"),
+        ] {
+            assert_eq!(glob_root(Path::new(pattern)), PathBuf::from(root));
+        }
+    }
+
+    #[test]
+    fn directories_and_globs_expand_to_model_files_below_them() {
+        let dir = temp_dir("cli_inputs");
+        for file in [
+            "b.obj",
+            "a.STL",
+            "notes.txt",
+            "cars/red.ply",
+            "cars/blue/car.obj",
+            "trees/oak.glb",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let relative = |inputs: Vec<Input>| -> Vec<String> {
+            inputs
+                .iter()
+                .map(|input| {
+                    assert!(input.path.ends_with(&input.relative));
+                    input.relative.to_str().unwrap().replace('\\', "/")
+                })
+                .collect()
+        };
+        let pattern = |pattern: &str| dir.join(pattern).to_str().unwrap().to_string();
+
+        // Directories recursively, sorted and without unsupported files
+        let inputs = collect_inputs(&[dir.to_str().unwrap().to_string()]).unwrap();
+        assert_eq!(
+            relative(inputs),
+            [
+                "a.STL",
+                "b.obj",
+                "cars/blue/car.obj",
+                "cars/red.ply",
+                "trees/oak.glb"
+            ]
+        );
+
+        // Glob matches keep their path below the pattern's root, and matched
+        // directories are expanded
+        let inputs = collect_inputs(&[pattern("*/*.ply"), pattern("cars/b*")]).unwrap();
+        assert_eq!(relative(inputs), ["cars/red.ply", "blue/car.obj"]);
+
+        // A single file is kept by its name
+        let inputs = collect_inputs(&[pattern("cars/red.ply")]).unwrap();
+        assert_eq!(inputs[0].relative, PathBuf::from("red.ply"));
+
+        let error = collect_inputs(&[pattern("*.fbx")]).err().unwrap();
+        assert!(error.contains("no model files match"), "{}", error);
+        let error = collect_inputs(&[pattern("missing.obj")]).err().unwrap();
+        assert!(error.contains("input not found"), "{}", error);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn outputs_keep_the_input_paths_below_the_output_directory() {
+        let inputs = [
+            input("models/cars/red.ply", "cars/red.ply"),
+            input("models/trees/oak.glb", "trees/oak.glb"),
+        ];
+        let outputs = output_paths(&inputs, Some(Path::new("out")), true, ExportFormat::Vox);
+        assert_eq!(
+            outputs.unwrap(),
+            [
+                PathBuf::from("out/cars/red.vox"),
+                PathBuf::from("out/trees/oak.vox")
+            ]
+        );
+
+        // Next to the input, or at the given file, for a single input
+        let outputs = output_paths(&inputs[..1], None, false, ExportFormat::Binvox);
+        assert_eq!(outputs.unwrap(), [PathBuf::from("models/cars/red.binvox")]);
+        let outputs = output_paths(
+            &inputs[..1],
+            Some(Path::new("car.vox")),
+            false,
+            ExportFormat::Vox,
+        );
+        assert_eq!(outputs.unwrap(), [PathBuf::from("car.vox")]);
+    }
+
+    #[test]
+    fn inputs_written_to_the_same_output_are_rejected() {
+        let inputs = [
+            input("models/car.obj", "car.obj"),
+            input("models/tree.obj", "tree.obj"),
+            input("models/car.stl", "car.stl"),
+        ];
+        for output in [None, Some(Path::new("out"))] {
+            let error = output_paths(&inputs, output, true, ExportFormat::Vox).unwrap_err();
+            assert!(
+                error.starts_with("models/car.obj and models/car.stl would both be written to"),
+                "{}",
+                error
+            );
+        }
+        // The same file given twice
+        let inputs = [
+            input("models/car.obj", "car.obj"),
+            input("models/car.obj", "car.obj"),
+        ];
+        assert!(output_paths(&inputs, None, true, ExportFormat::Vox).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::meshing::MeshingMode;
+use crate::sdf::SignedDistanceField;
+use crate::voxelization::VoxelizedModel;
+
+mod binvox;
+mod gltf;
+mod mesh;
+mod minecraft;
+mod nbt;
+mod vdb;
+mod volume;
+mod vox;
+
+pub use binvox::write_binvox;
+pub use gltf::{write_glb, write_gltf};
+pub use mesh::{write_obj, write_ply, write_stl};
+pub use minecraft::{write_schem, write_structure, BlockColor, BlockPalette};
+pub use vdb::{write_sdf_vdb, write_vdb, VdbGrid};
+pub use volume::{
+    write_nrrd, write_png_slices, write_raw, write_sdf_nrrd, write_sdf_raw, SliceAxis,
+};
+pub use vox::write_vox;
+
+// Output formats for a voxelized model
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    // Plain text list of occupied voxel coordinates
+    VoxelList,
+    // MagicaVoxel .vox
+    Vox,
+    // Run-length encoded dense grid (.binvox)
+    Binvox,
+    // Face-culled cube mesh as Wavefront OBJ
+    ObjMesh,
+    // Face-culled cube mesh as binary STL
+    StlMesh,
+    // Face-culled cube mesh as binary PLY
+    PlyMesh,
+    // glTF 2.0 scene with one mesh per submodel and an embedded buffer, colored per
+    // submodel
+    Gltf,
+    // glTF 2.0 scene in a single binary file
+    Glb,
+    // Sponge schematic for WorldEdit
+    Schematic,
+    // Vanilla Minecraft structure
+    Structure,
+    // OpenVDB sparse volume
+    Vdb,
+    // Dense occupancy volume with NRRD header
+    Nrrd,
+    // Dense occupancy volume without header
+    Raw,
+    // Dense occupancy volume as one PNG per slice
+    PngSlices,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 14] = [
+        ExportFormat::VoxelList,
+        ExportFormat::Vox,
+        ExportFormat::Binvox,
+        ExportFormat::ObjMesh,
+        ExportFormat::StlMesh,
+        ExportFormat::PlyMesh,
+        ExportFormat::Gltf,
+        ExportFormat::Glb,
+        ExportFormat::Schematic,
+        ExportFormat::Structure,
+        ExportFormat::Vdb,
+        ExportFormat::Nrrd,
+        ExportFormat::Raw,
+        ExportFormat::PngSlices,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::VoxelList => "txt",
+            ExportFormat::Vox => "vox",
+            ExportFormat::Binvox => "binvox",
+            ExportFormat::ObjMesh => "obj",
+            ExportFormat::StlMesh => "stl",
+            ExportFormat::PlyMesh => "ply",
+            ExportFormat::Gltf => "gltf",
+            ExportFormat::Glb => "glb",
+            ExportFormat::Schematic => "schem",
+            ExportFormat::Structure => "nbt",
+            ExportFormat::Vdb => "vdb",
+            ExportFormat::Nrrd => "nrrd",
+            ExportFormat::Raw => "raw",
+            ExportFormat::PngSlices => "png",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::VoxelList => "Voxel list",
+            ExportFormat::Vox => "MagicaVoxel",
+            ExportFormat::Binvox => "binvox",
+            ExportFormat::ObjMesh => "OBJ mesh",
+            ExportFormat::StlMesh => "STL mesh",
+            ExportFormat::PlyMesh => "PLY mesh",
+            ExportFormat::Gltf => "glTF",
+            ExportFormat::Glb => "glTF binary",
+            ExportFormat::Schematic => "Sponge schematic",
+            ExportFormat::Structure => "Minecraft structure",
+            ExportFormat::Vdb => "OpenVDB",
+            ExportFormat::Nrrd => "NRRD volume",
+            ExportFormat::Raw => "Raw u8 volume",
+            ExportFormat::PngSlices => "PNG slices",
+        }
+    }
+
+    // Look up a format by its file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+// Output formats for a signed distance field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdfFormat {
+    // Dense float volume with spacing and origin
+    Nrrd,
+    // Dense little endian f32 values without a header
+    Raw,
+    // OpenVDB narrow band level set
+    Vdb,
+}
+
+impl SdfFormat {
+    pub const ALL: [SdfFormat; 3] = [SdfFormat::Nrrd, SdfFormat::Raw, SdfFormat::Vdb];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SdfFormat::Nrrd => "nrrd",
+            SdfFormat::Raw => "raw",
+            SdfFormat::Vdb => "vdb",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SdfFormat::Nrrd => "NRRD float volume",
+            SdfFormat::Raw => "Raw f32 volume",
+            SdfFormat::Vdb => "OpenVDB level set",
+        }
+    }
+
+    // Look up a format by its file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+// Coordinate space of exported meshes and volumes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshUnits {
+    // The voxelizer's -1~1 range
+    #[default]
+    Normalized,
+    // The source model's units, undoing the normalization
+    Original,
+}
+
+impl MeshUnits {
+    pub const ALL: [MeshUnits; 2] = [MeshUnits::Normalized, MeshUnits::Original];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MeshUnits::Normalized => "Normalized (-1~1)",
+            MeshUnits::Original => "Original model units",
+        }
+    }
+}
+
+// Options that only some formats use
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub mesh_units: MeshUnits,
+    // Mesher of the mesh and glTF formats
+    pub meshing: MeshingMode,
+    // Blocks used by the Minecraft formats
+    pub block_palette: BlockPalette,
+    // Contents of the OpenVDB grid
+    pub vdb_grid: VdbGrid,
+    // Axis of PNG slice stacks
+    pub slice_axis: SliceAxis,
+}
+
+// Progress of an export, shared with other threads to watch or cancel it
+#[derive(Debug, Default)]
+pub struct ExportProgress {
+    bytes_written: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl ExportProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Bytes written to the output files so far
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    // Ask the export to stop; its next write fails and the files it wrote are removed
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Writer counting the bytes passed on into an export's progress, failing once the
+// export is cancelled
+struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a ExportProgress,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    fn new(inner: W, progress: &'a ExportProgress) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(io::Error::other("export cancelled"));
+        }
+        let written = self.inner.write(buf)?;
+        self.progress
+            .bytes_written
+            .fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Write a voxelized model to `path` in the given format
+pub fn export_model(
+    model: &VoxelizedModel,
+    path: &Path,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> io::Result<()> {
+    export_model_with_progress(model, path, format, options, &ExportProgress::new())
+}
+
+// Write a voxelized model like `export_model`, counting the bytes written in
+// `progress`. A cancelled export fails and leaves no partial files behind.
+pub fn export_model_with_progress(
+    model: &VoxelizedModel,
+    path: &Path,
+    format: ExportFormat,
+    options: &ExportOptions,
+    progress: &ExportProgress,
+) -> io::Result<()> {
+    // Slice stacks are written as one file per slice next to `path`
+    if format == ExportFormat::PngSlices {
+        return volume::write_png_slices_with_progress(model, options, path, progress);
+    }
+
+    let result = write_model_file(model, path, format, options, progress);
+    if result.is_err() && progress.is_cancelled() {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+fn write_model_file(
+    model: &VoxelizedModel,
+    path: &Path,
+    format: ExportFormat,
+    options: &ExportOptions,
+    progress: &ExportProgress,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(ProgressWriter::new(File::create(path)?, progress));
+    match format {
+        ExportFormat::VoxelList => write_voxel_list(model, &mut writer)?,
+        ExportFormat::Vox => write_vox(model, &mut writer)?,
+        ExportFormat::Binvox => write_binvox(model, &mut writer)?,
+        ExportFormat::ObjMesh => write_obj(model, options, &mut writer)?,
+        ExportFormat::StlMesh => write_stl(model, options, &mut writer)?,
+        ExportFormat::PlyMesh => write_ply(model, options, &mut writer)?,
+        ExportFormat::Gltf => write_gltf(model, options, &mut writer)?,
+        ExportFormat::Glb => write_glb(model, options, &mut writer)?,
+        ExportFormat::Schematic => write_schem(model, options, &mut writer)?,
+        ExportFormat::Structure => write_structure(model, options, &mut writer)?,
+        ExportFormat::Vdb => write_vdb(model, options, &mut writer)?,
+        ExportFormat::Nrrd => write_nrrd(model, options, &mut writer)?,
+        ExportFormat::Raw => write_raw(model, &mut writer)?,
+        ExportFormat::PngSlices => unreachable!(),
+    }
+    writer.flush()
+}
+
+// Write a signed distance field to `path` in the given format
+pub fn export_sdf(
+    sdf: &SignedDistanceField,
+    path: &Path,
+    format: SdfFormat,
+    options: &ExportOptions,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        SdfFormat::Nrrd => write_sdf_nrrd(sdf, options, &mut writer)?,
+        SdfFormat::Raw => write_sdf_raw(sdf, options, &mut writer)?,
+        SdfFormat::Vdb => write_sdf_vdb(sdf, &mut writer)?,
+    }
+    writer.flush()
+}
+
+// One "x y z" line per occupied voxel, grouped by submodel
+pub fn write_voxel_list(model: &VoxelizedModel, writer: &mut impl Write) -> io::Result<()> {
+    writeln!(writer, "# model_voxelization voxel list")?;
+    writeln!(
+        writer,
+        "# depth {}, voxel size {:.6}",
+        model.settings.octree_depth,
+        model.settings.voxel_size()
+    )?;
+
+    for mesh in &model.meshes {
+        writeln!(writer, "# mesh {}", mesh.name)?;
+        for voxel in mesh.octree.iter_leaves(mesh.octree.depth()) {
+            writeln!(writer, "{} {} {}", voxel.0, voxel.1, voxel.2)?;
+        }
+    }
+    Ok(())
+}
+
+// sRGB color of submodel `index` in formats that store colors: the first matches
+// the app's model color, the rest cycle through distinct hues
+fn submodel_color(index: usize) -> [u8; 4] {
+    if index == 0 {
+        return [204, 178, 153, 255];
+    }
+    let hue = (index as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |c: f32| (64.0 + c * 160.0) as u8;
+    [channel(r), channel(g), channel(b), 255]
+}
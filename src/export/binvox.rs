@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+
+use crate::voxelization::VoxelizedModel;
+
+// Write a .binvox file: a run-length encoded dense grid of all submodels.
+// The header's translate/scale map the grid back to the original model units.
+pub fn write_binvox(model: &VoxelizedModel, writer: &mut impl Write) -> io::Result<()> {
+    let depth = model.settings.octree_depth;
+    let resolution = 1u64 << depth;
+    let half = (resolution / 2) as i32;
+
+    // binvox orders voxels with y running fastest, then z, then x
+    let mut occupied: Vec<u64> = model
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.octree.iter_leaves(depth))
+        .map(|voxel| {
+            let x = (voxel.0 + half) as u64;
+            let y = (voxel.1 + half) as u64;
+            let z = (voxel.2 + half) as u64;
+            (x * resolution + z) * resolution + y
+        })
+        .collect();
+    occupied.sort_unstable();
+    occupied.dedup();
+
+    // The grid spans -1~1 in normalized coordinates:
+    // original = (normalized / scale) + center
+    let normalization = &model.normalization;
+    let translate = normalization.center - 1.0 / normalization.scale;
+    let scale = 2.0 / normalization.scale;
+
+    writeln!(writer, "#binvox 1")?;
+    writeln!(writer, "dim {} {} {}", resolution, resolution, resolution)?;
+    writeln!(
+        writer,
+        "translate {} {} {}",
+        translate.x, translate.y, translate.z
+    )?;
+    writeln!(writer, "scale {}", scale)?;
+    writeln!(writer, "data")?;
+
+    let mut runs = RunEncoder::default();
+    let mut next = 0;
+    for &index in &occupied {
+        runs.push(0, index - next);
+        runs.push(1, 1);
+        next = index + 1;
+    }
+    runs.push(0, resolution.pow(3) - next);
+
+    writer.write_all(&runs.finish())
+}
+
+// Run-length encoder producing binvox (value, count) byte pairs
+#[derive(Default)]
+struct RunEncoder {
+    data: Vec<u8>,
+    value: u8,
+    count: u64,
+}
+
+impl RunEncoder {
+    fn push(&mut self, value: u8, count: u64) {
+        if count == 0 {
+            return;
+        }
+        if value != self.value {
+            self.flush();
+            self.value = value;
+        }
+        self.count += count;
+    }
+
+    // Write the pending run, split into counts of at most 255
+    fn flush(&mut self) {
+        while self.count > 0 {
+            let run = self.count.min(255);
+            self.data.push(self.value);
+            self.data.push(run as u8);
+            self.count -= run;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.data
+    }
+}
@@ -0,0 +1,260 @@
+use base64::Engine;
+use gltf::json::{
+    self, accessor, buffer, material, mesh,
+    validation::{Checked::Valid, USize64},
+};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::mesh::surface_meshes;
+use super::{submodel_color, ExportOptions};
+use crate::voxelization::VoxelizedModel;
+
+// A glTF document before it is written: the JSON root, whose buffer has no URI
+// yet, and the single binary buffer it refers to
+struct GltfDocument {
+    root: json::Root,
+    binary: Vec<u8>,
+}
+
+// glTF with the buffer embedded as a base64 data URI
+pub fn write_gltf(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let mut document = build_document(model, options);
+    if let Some(buffer) = document.root.buffers.first_mut() {
+        buffer.uri = Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&document.binary)
+        ));
+    }
+    document.root.to_writer(writer)?;
+    Ok(())
+}
+
+// Binary glTF: a 12 byte header followed by the JSON chunk and the BIN chunk
+pub fn write_glb(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let document = build_document(model, options);
+    let glb = gltf::Glb {
+        // The length is worked out while writing
+        header: gltf::binary::Header {
+            magic: *b"glTF",
+            version: 2,
+            length: 0,
+        },
+        json: Cow::Owned(document.root.to_vec()?),
+        bin: (!document.binary.is_empty()).then_some(Cow::Owned(document.binary)),
+    };
+    glb.to_writer(writer).map_err(|err| match err {
+        gltf::Error::Io(err) => err,
+        err => io::Error::other(err),
+    })
+}
+
+// One mesh and node per submodel, with positions, normals, vertex colors and
+// indices. Every vertex of a submodel gets its submodel color; the voxels have no
+// colors of their own.
+fn build_document(model: &VoxelizedModel, options: &ExportOptions) -> GltfDocument {
+    let mut root = json::Root {
+        asset: json::Asset {
+            generator: Some("model_voxelization".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut binary = Vec::new();
+    let mut nodes = Vec::new();
+    // Pushed once there is a mesh to use it
+    let material = json::Index::new(0);
+
+    for (index, (name, surface)) in surface_meshes(model, options).into_iter().enumerate() {
+        // glTF accessors cannot be empty
+        if surface.vertex_count() == 0 {
+            continue;
+        }
+        let count = surface.vertex_count();
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in &surface.positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+
+        // Vertex colors are linear, the submodel colors sRGB
+        let [r, g, b, a] = submodel_color(index).map(|c| c as f32 / 255.0);
+        let color = [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a];
+
+        let positions = push_view(
+            &mut root,
+            &mut binary,
+            buffer::Target::ArrayBuffer,
+            |data| {
+                for component in surface.positions.iter().flatten() {
+                    data.extend_from_slice(&component.to_le_bytes());
+                }
+            },
+        );
+        let normals = push_view(
+            &mut root,
+            &mut binary,
+            buffer::Target::ArrayBuffer,
+            |data| {
+                for component in surface.normals.iter().flatten() {
+                    data.extend_from_slice(&component.to_le_bytes());
+                }
+            },
+        );
+        let colors = push_view(
+            &mut root,
+            &mut binary,
+            buffer::Target::ArrayBuffer,
+            |data| {
+                for component in std::iter::repeat_n(color, count).flatten() {
+                    data.extend_from_slice(&component.to_le_bytes());
+                }
+            },
+        );
+        let indices = push_view(
+            &mut root,
+            &mut binary,
+            buffer::Target::ElementArrayBuffer,
+            |data| {
+                for index in &surface.indices {
+                    data.extend_from_slice(&index.to_le_bytes());
+                }
+            },
+        );
+
+        let mut positions = accessor(positions, accessor::Type::Vec3, count);
+        positions.min = Some(json::Value::from(min.to_vec()));
+        positions.max = Some(json::Value::from(max.to_vec()));
+        let attributes = BTreeMap::from([
+            (Valid(mesh::Semantic::Positions), root.push(positions)),
+            (
+                Valid(mesh::Semantic::Normals),
+                root.push(accessor(normals, accessor::Type::Vec3, count)),
+            ),
+            (
+                Valid(mesh::Semantic::Colors(0)),
+                root.push(accessor(colors, accessor::Type::Vec4, count)),
+            ),
+        ]);
+        let mut indices = accessor(indices, accessor::Type::Scalar, surface.indices.len());
+        indices.component_type =
+            Valid(accessor::GenericComponentType(accessor::ComponentType::U32));
+        let indices = root.push(indices);
+
+        let mesh = root.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(name.to_string()),
+            primitives: vec![mesh::Primitive {
+                attributes,
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(indices),
+                material: Some(material),
+                mode: Valid(mesh::Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+        nodes.push(root.push(json::Node {
+            name: Some(name.to_string()),
+            mesh: Some(mesh),
+            ..Default::default()
+        }));
+    }
+
+    // A document without meshes is just the asset; scenes must not be empty
+    if !nodes.is_empty() {
+        let scene = root.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes,
+        });
+        root.scene = Some(scene);
+        // White base color, so the vertex colors show as they are
+        root.push(json::Material {
+            name: Some("voxel".to_string()),
+            pbr_metallic_roughness: material::PbrMetallicRoughness {
+                base_color_factor: material::PbrBaseColorFactor([1.0; 4]),
+                metallic_factor: material::StrengthFactor(0.0),
+                roughness_factor: material::StrengthFactor(0.9),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        root.push(json::Buffer {
+            byte_length: USize64::from(binary.len()),
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+
+    GltfDocument { root, binary }
+}
+
+// Float accessor over a whole buffer view
+fn accessor(
+    view: json::Index<buffer::View>,
+    type_: accessor::Type,
+    count: usize,
+) -> json::Accessor {
+    json::Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        count: USize64::from(count),
+        component_type: Valid(accessor::GenericComponentType(accessor::ComponentType::F32)),
+        extensions: None,
+        extras: Default::default(),
+        type_: Valid(type_),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    }
+}
+
+// Append the bytes written by `fill` to the buffer as a new buffer view, returning its index
+fn push_view(
+    root: &mut json::Root,
+    binary: &mut Vec<u8>,
+    target: buffer::Target,
+    fill: impl FnOnce(&mut Vec<u8>),
+) -> json::Index<buffer::View> {
+    let offset = binary.len();
+    fill(binary);
+    root.push(buffer::View {
+        buffer: json::Index::new(0),
+        byte_length: USize64::from(binary.len() - offset),
+        byte_offset: Some(USize64::from(offset)),
+        byte_stride: None,
+        name: None,
+        target: Some(Valid(target)),
+        extensions: None,
+        extras: Default::default(),
+    })
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,139 @@
+use glam::Vec3;
+use std::io::{self, Write};
+
+use super::{ExportOptions, MeshUnits};
+use crate::meshing::{build_surface_mesh, SurfaceMesh};
+use crate::voxelization::VoxelizedModel;
+
+// Build the surface mesh of every submodel with the requested mesher and units
+pub(super) fn surface_meshes<'a>(
+    model: &'a VoxelizedModel,
+    options: &ExportOptions,
+) -> Vec<(&'a str, SurfaceMesh)> {
+    model
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let mut surface = build_surface_mesh(&mesh.octree, options.meshing);
+            if options.mesh_units == MeshUnits::Original {
+                // Scaling is uniform, so the normals stay valid
+                for position in &mut surface.positions {
+                    *position = model.normalization.invert((*position).into()).into();
+                }
+            }
+            (mesh.name.as_str(), surface)
+        })
+        .collect()
+}
+
+// Wavefront OBJ with one object per submodel
+pub fn write_obj(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(writer, "# model_voxelization voxel mesh")?;
+
+    // OBJ indices are 1-based and shared by all objects
+    let mut offset = 1;
+    for (name, surface) in surface_meshes(model, options) {
+        writeln!(writer, "o {}", name)?;
+        for [x, y, z] in &surface.positions {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for [x, y, z] in &surface.normals {
+            writeln!(writer, "vn {} {} {}", x, y, z)?;
+        }
+        for triangle in surface.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                triangle[0] + offset,
+                triangle[1] + offset,
+                triangle[2] + offset,
+            ];
+            writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+        offset += surface.vertex_count() as u32;
+    }
+    Ok(())
+}
+
+// Binary STL of all submodels
+pub fn write_stl(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let surfaces = surface_meshes(model, options);
+    let triangle_count: usize = surfaces.iter().map(|(_, s)| s.triangle_count()).sum();
+
+    let mut header = [0u8; 80];
+    let title = b"model_voxelization voxel mesh";
+    header[..title.len()].copy_from_slice(title);
+    writer.write_all(&header)?;
+    writer.write_all(&(triangle_count as u32).to_le_bytes())?;
+
+    for (_, surface) in &surfaces {
+        for triangle in surface.indices.chunks_exact(3) {
+            // Facet normal from the winding, since the vertex normals of smooth
+            // meshes differ from it
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(surface.positions[triangle[i] as usize]));
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            for vector in [normal, a, b, c] {
+                for component in vector.to_array() {
+                    writer.write_all(&component.to_le_bytes())?;
+                }
+            }
+            // Attribute byte count
+            writer.write_all(&[0, 0])?;
+        }
+    }
+    Ok(())
+}
+
+// Binary little endian PLY of all submodels, with vertex normals
+pub fn write_ply(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let surfaces = surface_meshes(model, options);
+    let vertex_count: usize = surfaces.iter().map(|(_, s)| s.vertex_count()).sum();
+    let face_count: usize = surfaces.iter().map(|(_, s)| s.triangle_count()).sum();
+
+    write!(
+        writer,
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         comment model_voxelization voxel mesh\n\
+         element vertex {vertex_count}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property float nx\n\
+         property float ny\n\
+         property float nz\n\
+         element face {face_count}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n"
+    )?;
+
+    for (_, surface) in &surfaces {
+        for (position, normal) in surface.positions.iter().zip(&surface.normals) {
+            for component in position.iter().chain(normal) {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+        }
+    }
+
+    let mut offset = 0;
+    for (_, surface) in &surfaces {
+        for triangle in surface.indices.chunks_exact(3) {
+            writer.write_all(&[3])?;
+            for index in triangle {
+                writer.write_all(&(index + offset).to_le_bytes())?;
+            }
+        }
+        offset += surface.vertex_count() as u32;
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use flate2::{write::GzEncoder, Compression};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::nbt::Tag;
+use super::{submodel_color, ExportOptions};
+use crate::voxelization::VoxelizedModel;
+
+// Minecraft 1.20.1, understood by current WorldEdit and structure block versions
+const DATA_VERSION: i32 = 3465;
+
+// Structure blocks save and load at most 48 blocks per axis
+const STRUCTURE_MAX_SIZE: u32 = 48;
+// A schematic has to fit the world's build height (-64 to 320)
+const SCHEMATIC_MAX_SIZE: u32 = 384;
+
+const AIR: &str = "minecraft:air";
+
+// A block and the color it is picked for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockColor {
+    pub block: String,
+    pub color: [u8; 3],
+}
+
+impl BlockColor {
+    fn new(block: &str, color: [u8; 3]) -> Self {
+        Self {
+            block: block.to_string(),
+            color,
+        }
+    }
+}
+
+// How voxels are turned into Minecraft blocks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockPalette {
+    // Every voxel becomes the same block state, e.g. "minecraft:stone"
+    Single(String),
+    // Every voxel becomes the block closest to the color of its submodel, the same
+    // color the VOX and glTF exports give it. Textures and vertex colors of the
+    // source model are not kept, so each submodel is a single block.
+    NearestColor(Vec<BlockColor>),
+}
+
+impl Default for BlockPalette {
+    fn default() -> Self {
+        BlockPalette::Single("minecraft:stone".to_string())
+    }
+}
+
+impl BlockPalette {
+    // The 16 concrete colors
+    pub fn concrete() -> Self {
+        BlockPalette::NearestColor(vec![
+            BlockColor::new("minecraft:white_concrete", [207, 213, 214]),
+            BlockColor::new("minecraft:orange_concrete", [224, 97, 1]),
+            BlockColor::new("minecraft:magenta_concrete", [169, 48, 159]),
+            BlockColor::new("minecraft:light_blue_concrete", [36, 137, 199]),
+            BlockColor::new("minecraft:yellow_concrete", [241, 175, 21]),
+            BlockColor::new("minecraft:lime_concrete", [94, 169, 24]),
+            BlockColor::new("minecraft:pink_concrete", [214, 101, 143]),
+            BlockColor::new("minecraft:gray_concrete", [55, 58, 62]),
+            BlockColor::new("minecraft:light_gray_concrete", [125, 125, 115]),
+            BlockColor::new("minecraft:cyan_concrete", [21, 119, 136]),
+            BlockColor::new("minecraft:purple_concrete", [100, 32, 156]),
+            BlockColor::new("minecraft:blue_concrete", [45, 47, 143]),
+            BlockColor::new("minecraft:brown_concrete", [96, 60, 32]),
+            BlockColor::new("minecraft:green_concrete", [73, 91, 36]),
+            BlockColor::new("minecraft:red_concrete", [142, 33, 33]),
+            BlockColor::new("minecraft:black_concrete", [8, 10, 15]),
+        ])
+    }
+
+    // The block state used for a voxel of the given color
+    fn block_for(&self, color: [u8; 3]) -> Option<&str> {
+        match self {
+            BlockPalette::Single(block) => Some(block),
+            BlockPalette::NearestColor(blocks) => blocks
+                .iter()
+                .min_by_key(|entry| {
+                    (0..3)
+                        .map(|i| (entry.color[i] as i32 - color[i] as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .map(|entry| entry.block.as_str()),
+        }
+    }
+}
+
+// The voxels of all submodels as block palette indices, with the palette itself.
+// Index 0 is air; where submodels overlap the first one wins.
+struct BlockGrid {
+    size: u32,
+    palette: Vec<String>,
+    blocks: BTreeMap<[u32; 3], usize>,
+}
+
+fn build_grid(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    max_size: u32,
+    format: &str,
+) -> io::Result<BlockGrid> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let depth = model.settings.octree_depth;
+    let size = 1u32 << depth;
+    if size > max_size {
+        return Err(invalid(format!(
+            "octree depth {} gives a {}³ grid, but {} files are limited to {} blocks per axis; lower the octree depth",
+            depth, size, format, max_size
+        )));
+    }
+
+    let mut palette = vec![AIR.to_string()];
+    let mut blocks = BTreeMap::new();
+    let half = (size / 2) as i32;
+    for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+        let [r, g, b, _] = submodel_color(mesh_index);
+        let block = options
+            .block_palette
+            .block_for([r, g, b])
+            .filter(|block| !block.is_empty())
+            .ok_or_else(|| invalid("the block palette is empty".to_string()))?;
+        let index = match palette.iter().position(|entry| entry == block) {
+            Some(index) => index,
+            None => {
+                palette.push(block.to_string());
+                palette.len() - 1
+            }
+        };
+
+        for voxel in mesh.octree.iter_leaves(mesh.octree.depth()) {
+            let position = [voxel.0, voxel.1, voxel.2].map(|c| (c + half) as u32);
+            blocks.entry(position).or_insert(index);
+        }
+    }
+
+    Ok(BlockGrid {
+        size,
+        palette,
+        blocks,
+    })
+}
+
+// Sponge schematic version 2 (.schem), as read by WorldEdit
+pub fn write_schem(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let grid = build_grid(model, options, SCHEMATIC_MAX_SIZE, "schematic")?;
+    let size = grid.size as usize;
+
+    // Blocks are ordered by y, then z, then x, each palette index stored as a varint
+    let mut block_data = Vec::with_capacity(size * size * size);
+    for y in 0..grid.size {
+        for z in 0..grid.size {
+            for x in 0..grid.size {
+                let index = grid.blocks.get(&[x, y, z]).copied().unwrap_or(0);
+                push_varint(&mut block_data, index);
+            }
+        }
+    }
+
+    let palette = grid
+        .palette
+        .iter()
+        .enumerate()
+        .map(|(index, block)| (block.as_str(), Tag::Int(index as i32)))
+        .collect::<Vec<_>>();
+
+    let schematic = Tag::Compound(vec![
+        ("Version", Tag::Int(2)),
+        ("DataVersion", Tag::Int(DATA_VERSION)),
+        ("Width", Tag::Short(grid.size as i16)),
+        ("Height", Tag::Short(grid.size as i16)),
+        ("Length", Tag::Short(grid.size as i16)),
+        ("Offset", Tag::IntArray(vec![0, 0, 0])),
+        ("PaletteMax", Tag::Int(grid.palette.len() as i32)),
+        ("Palette", Tag::Compound(palette)),
+        ("BlockData", Tag::ByteArray(block_data)),
+        ("BlockEntities", Tag::List(Vec::new())),
+    ]);
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    schematic.write_named(&mut encoder, "Schematic")?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// Seven bits at a time from the lowest, the high bit set on all but the last byte
+fn push_varint(data: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        data.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+// Vanilla structure file (.nbt), as loaded by structure blocks and /place template.
+// Only occupied voxels are stored, so air in the grid leaves existing blocks alone.
+pub fn write_structure(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let grid = build_grid(model, options, STRUCTURE_MAX_SIZE, "structure")?;
+    let size = grid.size as i32;
+
+    let palette = grid
+        .palette
+        .iter()
+        .map(|block| Tag::Compound(vec![("Name", Tag::String(block.clone()))]))
+        .collect();
+    let blocks = grid
+        .blocks
+        .iter()
+        .map(|(position, &state)| {
+            Tag::Compound(vec![
+                (
+                    "pos",
+                    Tag::List(position.iter().map(|&c| Tag::Int(c as i32)).collect()),
+                ),
+                ("state", Tag::Int(state as i32)),
+            ])
+        })
+        .collect();
+
+    let structure = Tag::Compound(vec![
+        ("DataVersion", Tag::Int(DATA_VERSION)),
+        (
+            "size",
+            Tag::List(vec![Tag::Int(size), Tag::Int(size), Tag::Int(size)]),
+        ),
+        ("palette", Tag::List(palette)),
+        ("blocks", Tag::List(blocks)),
+        ("entities", Tag::List(Vec::new())),
+    ]);
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    structure.write_named(&mut encoder, "")?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints_continue_past_seven_bits() {
+        let mut data = Vec::new();
+        for value in [0, 1, 127, 128, 300, 16384] {
+            push_varint(&mut data, value);
+        }
+        assert_eq!(data, [0, 1, 0x7f, 0x80, 0x01, 0xac, 0x02, 0x80, 0x80, 0x01]);
+    }
+}
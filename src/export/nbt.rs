@@ -0,0 +1,76 @@
+use std::io::{self, Write};
+
+// The subset of Minecraft's NBT tags used by the schematic writers
+pub(super) enum Tag<'a> {
+    Short(i16),
+    Int(i32),
+    String(String),
+    List(Vec<Tag<'a>>),
+    Compound(Vec<(&'a str, Tag<'a>)>),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+}
+
+impl Tag<'_> {
+    fn id(&self) -> u8 {
+        match self {
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+        }
+    }
+
+    fn write_payload(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Tag::Short(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Int(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::String(value) => write_string(writer, value),
+            Tag::List(items) => {
+                // Empty lists use the End tag as element type
+                writer.write_all(&[items.first().map_or(0, Tag::id)])?;
+                writer.write_all(&(items.len() as i32).to_be_bytes())?;
+                for item in items {
+                    item.write_payload(writer)?;
+                }
+                Ok(())
+            }
+            Tag::Compound(entries) => {
+                for (name, tag) in entries {
+                    tag.write_named(writer, name)?;
+                }
+                writer.write_all(&[0])
+            }
+            Tag::ByteArray(bytes) => {
+                writer.write_all(&(bytes.len() as i32).to_be_bytes())?;
+                writer.write_all(bytes)
+            }
+            Tag::IntArray(values) => {
+                writer.write_all(&(values.len() as i32).to_be_bytes())?;
+                for value in values {
+                    writer.write_all(&value.to_be_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Write the tag with its type and name, as in a compound or at the root of a file
+    pub(super) fn write_named(&self, writer: &mut impl Write, name: &str) -> io::Result<()> {
+        writer.write_all(&[self.id()])?;
+        write_string(writer, name)?;
+        self.write_payload(writer)
+    }
+}
+
+// NBT strings are prefixed with their u16 byte length
+fn write_string(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let bytes = value.as_bytes();
+    let length = u16::try_from(bytes.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "NBT string too long"))?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(bytes)
+}
@@ -0,0 +1,515 @@
+use flate2::{write::ZlibEncoder, Compression};
+use glam::Vec3;
+use std::cmp::Reverse;
+use std::collections::{hash_map::Entry, BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::ExportOptions;
+use crate::sdf::SignedDistanceField;
+use crate::voxelization::{VoxelCoord, VoxelizedModel, NEIGHBOR_OFFSETS};
+
+const MAGIC: i64 = 0x5644_4220;
+// File format of OpenVDB 6 and later
+const FILE_VERSION: u32 = 224;
+const LIBRARY_VERSION: [u32; 2] = [10, 0];
+const COMPRESS_ZIP: u32 = 0x1;
+// Value arrays are stored whole, without an inactive value mask
+const NO_MASK_AND_ALL_VALS: u8 = 6;
+
+// The standard float grid: 32³ upper nodes, 16³ lower nodes and 8³ leaves
+const GRID_TYPE: &str = "Tree_float_5_4_3";
+const LEAF_LOG2: u32 = 3;
+const LOWER_LOG2: u32 = 4;
+const UPPER_LOG2: u32 = 5;
+const LOWER_SPAN_LOG2: u32 = LEAF_LOG2 + LOWER_LOG2;
+const UPPER_SPAN_LOG2: u32 = LOWER_SPAN_LOG2 + UPPER_LOG2;
+
+// Narrow band half width of level sets in voxels, OpenVDB's default
+const LEVEL_SET_HALF_WIDTH: f32 = 3.0;
+
+// Contents of the exported float grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VdbGrid {
+    // Fog volume with 1 in occupied voxels
+    #[default]
+    Occupancy,
+    // Narrow band signed distance to the voxel surface, negative inside
+    LevelSet,
+}
+
+impl VdbGrid {
+    pub const ALL: [VdbGrid; 2] = [VdbGrid::Occupancy, VdbGrid::LevelSet];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VdbGrid::Occupancy => "Occupancy (fog volume)",
+            VdbGrid::LevelSet => "Level set (SDF)",
+        }
+    }
+}
+
+// An 8³ leaf: active voxel bits and all 512 values
+struct Leaf {
+    value_mask: [u64; 8],
+    values: [f32; 512],
+}
+
+// Sparse float grid in voxel index space. Voxels without a leaf read as
+// `inactive_value`, which is the background outside and -background inside level sets.
+struct FloatGrid<F: Fn([i32; 3]) -> f32> {
+    background: f32,
+    inactive_value: F,
+    leaves: BTreeMap<[i32; 3], Leaf>,
+}
+
+impl<F: Fn([i32; 3]) -> f32> FloatGrid<F> {
+    fn new(background: f32, inactive_value: F) -> Self {
+        Self {
+            background,
+            inactive_value,
+            leaves: BTreeMap::new(),
+        }
+    }
+
+    fn set_active(&mut self, coord: [i32; 3], value: f32) {
+        let origin = node_origin(coord, LEAF_LOG2);
+        let inactive_value = &self.inactive_value;
+        let leaf = self.leaves.entry(origin).or_insert_with(|| {
+            let mut values = [0.0; 512];
+            for (n, value) in values.iter_mut().enumerate() {
+                *value = inactive_value(child_origin(origin, n, LEAF_LOG2, 0));
+            }
+            Leaf {
+                value_mask: [0; 8],
+                values,
+            }
+        });
+        let n = node_index(coord, LEAF_LOG2, 0);
+        leaf.value_mask[n >> 6] |= 1 << (n & 63);
+        leaf.values[n] = value;
+    }
+}
+
+// Write an OpenVDB file with one float grid of the union of all submodels. Voxel
+// centers are placed in the original model units, using the voxel size and the
+// normalization applied when the model was loaded.
+pub fn write_vdb(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let occupied: HashSet<[i32; 3]> = model
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.octree.iter_leaves(mesh.octree.depth()))
+        .map(|voxel| [voxel.0, voxel.1, voxel.2])
+        .collect();
+
+    // OpenVDB places voxel values at the index coordinates, so index 0 maps to the
+    // center of the cell the voxelizer tested
+    let normalization = &model.normalization;
+    let voxel_size = model.settings.voxel_size() / normalization.scale;
+    let translation = normalization.invert(model.settings.grid().cell_center(VoxelCoord(0, 0, 0)));
+
+    let (name, class, tree) = match options.vdb_grid {
+        VdbGrid::Occupancy => {
+            let mut grid = FloatGrid::new(0.0, |_| 0.0);
+            for &coord in &occupied {
+                grid.set_active(coord, 1.0);
+            }
+            ("density", "fog volume", write_tree(&grid)?)
+        }
+        VdbGrid::LevelSet => {
+            let background = LEVEL_SET_HALF_WIDTH * voxel_size;
+            let mut grid = FloatGrid::new(background, |coord| {
+                if occupied.contains(&coord) {
+                    -background
+                } else {
+                    background
+                }
+            });
+            for (coord, distance) in narrow_band(&occupied, LEVEL_SET_HALF_WIDTH) {
+                grid.set_active(coord, distance * voxel_size);
+            }
+            ("surface", "level set", write_tree(&grid)?)
+        }
+    };
+
+    write_grid(
+        writer,
+        name,
+        class,
+        &tree,
+        voxel_size,
+        translation,
+        occupied.len(),
+    )
+}
+
+// Write an OpenVDB level set of a signed distance field, keeping its distances
+// within the narrow band. Like `write_vdb` it is placed in the original model units.
+pub fn write_sdf_vdb(sdf: &SignedDistanceField, writer: &mut impl Write) -> io::Result<()> {
+    // Samples sit at the same voxel centers the voxelizer tests
+    let normalization = &sdf.normalization;
+    let voxel_size = sdf.voxel_size() / normalization.scale;
+    let translation = normalization.invert(sdf.sample_position(VoxelCoord(0, 0, 0)));
+
+    let background = LEVEL_SET_HALF_WIDTH * voxel_size;
+    let mut grid = FloatGrid::new(background, |[x, y, z]| match sdf.get(VoxelCoord(x, y, z)) {
+        Some(distance) if distance < 0.0 => -background,
+        _ => background,
+    });
+    let mut active = 0;
+    for (voxel, distance) in sdf.iter() {
+        let distance = distance / normalization.scale;
+        if distance.abs() < background {
+            grid.set_active([voxel.0, voxel.1, voxel.2], distance);
+            active += 1;
+        }
+    }
+
+    let tree = write_tree(&grid)?;
+    write_grid(
+        writer,
+        "surface",
+        "level set",
+        &tree,
+        voxel_size,
+        translation,
+        active,
+    )
+}
+
+// Write a file holding one grid, mapped to world space by a uniform scale and a
+// translation. `salt` only makes the file UUID differ between files.
+fn write_grid(
+    writer: &mut impl Write,
+    name: &str,
+    class: &str,
+    tree: &TreeData,
+    voxel_size: f32,
+    translation: Vec3,
+    salt: usize,
+) -> io::Result<()> {
+    let mut out = Vec::new();
+
+    // Header
+    out.extend_from_slice(&MAGIC.to_le_bytes());
+    out.extend_from_slice(&FILE_VERSION.to_le_bytes());
+    for version in LIBRARY_VERSION {
+        out.extend_from_slice(&version.to_le_bytes());
+    }
+    // The grid descriptors below carry stream offsets
+    out.push(1);
+    out.extend_from_slice(file_uuid(salt).as_bytes());
+
+    write_metadata(&mut out, &[("creator", "model_voxelization")])?;
+
+    // Grid descriptor: name, type, instance parent and the offsets of the grid,
+    // its buffers and its end, filled in once they are known
+    out.extend_from_slice(&1i32.to_le_bytes());
+    write_string(&mut out, name)?;
+    write_string(&mut out, GRID_TYPE)?;
+    write_string(&mut out, "")?;
+    let offsets_position = out.len();
+    out.extend_from_slice(&[0; 24]);
+    let grid_position = out.len();
+
+    out.extend_from_slice(&COMPRESS_ZIP.to_le_bytes());
+    write_metadata(&mut out, &[("class", class), ("name", name)])?;
+
+    // Uniform scale and translation from index space to world space
+    write_string(&mut out, "UniformScaleTranslateMap")?;
+    let scale = Vec3::splat(voxel_size);
+    // Translation, scale and voxel size
+    for vector in [translation, scale, scale] {
+        for component in vector.to_array() {
+            out.extend_from_slice(&(component as f64).to_le_bytes());
+        }
+    }
+
+    out.extend_from_slice(&tree.topology);
+    let block_position = out.len();
+    out.extend_from_slice(&tree.buffers);
+    let end_position = out.len();
+
+    for (i, position) in [grid_position, block_position, end_position]
+        .into_iter()
+        .enumerate()
+    {
+        let offset = offsets_position + i * 8;
+        out[offset..offset + 8].copy_from_slice(&(position as i64).to_le_bytes());
+    }
+
+    writer.write_all(&out)
+}
+
+// Leaf origins of each lower internal node, by upper internal node
+type NodeTree = BTreeMap<[i32; 3], BTreeMap<[i32; 3], Vec<[i32; 3]>>>;
+
+// The serialized tree: node structure and active masks, then the leaf values
+struct TreeData {
+    topology: Vec<u8>,
+    buffers: Vec<u8>,
+}
+
+fn write_tree<F: Fn([i32; 3]) -> f32>(grid: &FloatGrid<F>) -> io::Result<TreeData> {
+    // Group leaves under their lower and upper internal nodes, in the order
+    // OpenVDB stores children (by x, then y, then z)
+    let mut nodes = NodeTree::new();
+    for &origin in grid.leaves.keys() {
+        nodes
+            .entry(node_origin(origin, UPPER_SPAN_LOG2))
+            .or_default()
+            .entry(node_origin(origin, LOWER_SPAN_LOG2))
+            .or_default()
+            .push(origin);
+    }
+
+    let mut topology = Vec::new();
+    // Buffer count
+    topology.extend_from_slice(&1i32.to_le_bytes());
+
+    // Root node: background, no tiles, upper node children
+    topology.extend_from_slice(&grid.background.to_le_bytes());
+    topology.extend_from_slice(&0u32.to_le_bytes());
+    topology.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+
+    for (&upper_origin, lower_nodes) in &nodes {
+        for component in upper_origin {
+            topology.extend_from_slice(&component.to_le_bytes());
+        }
+        write_internal_node(
+            &mut topology,
+            upper_origin,
+            UPPER_LOG2,
+            LOWER_SPAN_LOG2,
+            lower_nodes.keys().copied(),
+            &grid.inactive_value,
+        )?;
+        for (&lower_origin, leaves) in lower_nodes {
+            write_internal_node(
+                &mut topology,
+                lower_origin,
+                LOWER_LOG2,
+                LEAF_LOG2,
+                leaves.iter().copied(),
+                &grid.inactive_value,
+            )?;
+            for origin in leaves {
+                write_mask(&mut topology, &grid.leaves[origin].value_mask);
+            }
+        }
+    }
+
+    let mut buffers = Vec::new();
+    for leaves in nodes.values().flat_map(|lower_nodes| lower_nodes.values()) {
+        for origin in leaves {
+            let leaf = &grid.leaves[origin];
+            write_mask(&mut buffers, &leaf.value_mask);
+            write_values(&mut buffers, &leaf.values)?;
+        }
+    }
+
+    Ok(TreeData { topology, buffers })
+}
+
+// Child mask, value mask and tile values of an internal node, whose children
+// span 2^child_span_log2 voxels. Tiles are inactive.
+fn write_internal_node(
+    out: &mut Vec<u8>,
+    origin: [i32; 3],
+    log2: u32,
+    child_span_log2: u32,
+    children: impl Iterator<Item = [i32; 3]>,
+    inactive_value: &impl Fn([i32; 3]) -> f32,
+) -> io::Result<()> {
+    let count = 1usize << (3 * log2);
+    let mut child_mask = vec![0u64; count / 64];
+    for child in children {
+        let n = node_index(child, log2, child_span_log2);
+        child_mask[n >> 6] |= 1 << (n & 63);
+    }
+
+    let values: Vec<f32> = (0..count)
+        .map(|n| {
+            if child_mask[n >> 6] & (1 << (n & 63)) != 0 {
+                0.0
+            } else {
+                inactive_value(child_origin(origin, n, log2, child_span_log2))
+            }
+        })
+        .collect();
+
+    write_mask(out, &child_mask);
+    write_mask(out, &vec![0u64; count / 64]);
+    write_values(out, &values)
+}
+
+fn write_mask(out: &mut Vec<u8>, words: &[u64]) {
+    for word in words {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+}
+
+// A zip compressed value array. Data that does not shrink is stored raw, marked
+// by a negative size.
+fn write_values(out: &mut Vec<u8>, values: &[f32]) -> io::Result<()> {
+    out.push(NO_MASK_AND_ALL_VALS);
+
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&bytes)?;
+    let compressed = encoder.finish()?;
+
+    if compressed.len() < bytes.len() {
+        out.extend_from_slice(&(compressed.len() as i64).to_le_bytes());
+        out.extend_from_slice(&compressed);
+    } else {
+        out.extend_from_slice(&(-(bytes.len() as i64)).to_le_bytes());
+        out.extend_from_slice(&bytes);
+    }
+    Ok(())
+}
+
+// Metadata map of string values
+fn write_metadata(out: &mut Vec<u8>, entries: &[(&str, &str)]) -> io::Result<()> {
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (name, value) in entries {
+        write_string(out, name)?;
+        write_string(out, "string")?;
+        write_string(out, value)?;
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> io::Result<()> {
+    let length = u32::try_from(value.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "VDB string too long"))?;
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+// Origin of the node spanning 2^span_log2 voxels that contains `coord`
+fn node_origin(coord: [i32; 3], span_log2: u32) -> [i32; 3] {
+    coord.map(|c| c & !((1 << span_log2) - 1))
+}
+
+// Index of the child containing `coord` in a node of 2^log2 children per axis
+fn node_index(coord: [i32; 3], log2: u32, child_span_log2: u32) -> usize {
+    let local = |c: i32| ((c >> child_span_log2) & ((1 << log2) - 1)) as usize;
+    (local(coord[0]) << (2 * log2)) | (local(coord[1]) << log2) | local(coord[2])
+}
+
+// Origin of child `n` of the node at `origin`
+fn child_origin(origin: [i32; 3], n: usize, log2: u32, child_span_log2: u32) -> [i32; 3] {
+    let mask = (1 << log2) - 1;
+    let local = [n >> (2 * log2), (n >> log2) & mask, n & mask];
+    [0, 1, 2].map(|axis| origin[axis] + ((local[axis] as i32) << child_span_log2))
+}
+
+// Signed distance in voxels from voxel centers to the voxel surface, for all voxels
+// within `half_width` of it. Every voxel takes the distance to the nearest voxel of
+// the other state, less half a voxel, found by propagating nearest seeds outward
+// in order of distance.
+fn narrow_band(occupied: &HashSet<[i32; 3]>, half_width: f32) -> HashMap<[i32; 3], f32> {
+    // Squared distances between voxel centers are integers
+    let max_distance_squared = (half_width + 0.5).powi(2) as i32;
+    let mut band = HashMap::new();
+    // Equally near seeds are taken in a fixed order so files are reproducible
+    let mut voxels: Vec<[i32; 3]> = occupied.iter().copied().collect();
+    voxels.sort_unstable();
+
+    for inside in [false, true] {
+        // Seeds are the voxels of the other state next to the surface
+        let mut nearest: HashMap<[i32; 3], ([i32; 3], i32)> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for &voxel in &voxels {
+            for offset in FACE_OFFSETS {
+                let neighbor = add(voxel, offset);
+                if occupied.contains(&neighbor) {
+                    continue;
+                }
+                let (seed, target) = if inside {
+                    (neighbor, voxel)
+                } else {
+                    (voxel, neighbor)
+                };
+                if let Entry::Vacant(entry) = nearest.entry(target) {
+                    entry.insert((seed, 1));
+                    queue.push(Reverse((1, target)));
+                }
+            }
+        }
+
+        while let Some(Reverse((distance_squared, voxel))) = queue.pop() {
+            let (seed, best) = nearest[&voxel];
+            if distance_squared > best {
+                // Already reached through a closer seed
+                continue;
+            }
+            for offset in NEIGHBOR_OFFSETS {
+                let neighbor = add(voxel, offset);
+                if occupied.contains(&neighbor) != inside {
+                    continue;
+                }
+                let distance_squared = distance_squared_between(neighbor, seed);
+                if distance_squared > max_distance_squared {
+                    continue;
+                }
+                let best = nearest.get(&neighbor).map_or(i32::MAX, |entry| entry.1);
+                if distance_squared < best {
+                    nearest.insert(neighbor, (seed, distance_squared));
+                    queue.push(Reverse((distance_squared, neighbor)));
+                }
+            }
+        }
+
+        let sign = if inside { -1.0 } else { 1.0 };
+        band.extend(nearest.into_iter().map(|(voxel, (_, distance_squared))| {
+            (voxel, sign * ((distance_squared as f32).sqrt() - 0.5))
+        }));
+    }
+
+    band
+}
+
+const FACE_OFFSETS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+fn add(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn distance_squared_between(a: [i32; 3], b: [i32; 3]) -> i32 {
+    let d = [0, 1, 2].map(|axis| a[axis] - b[axis]);
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+// A version 4 style UUID string; only needs to differ between files
+fn file_uuid(salt: usize) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    let bits = nanos ^ ((salt as u128) << 64);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-4{}-8{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,326 @@
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression, Crc,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::{ExportOptions, ExportProgress, MeshUnits, ProgressWriter};
+use crate::mesh::Normalization;
+use crate::sdf::SignedDistanceField;
+use crate::voxelization::{VoxelCoord, VoxelGrid, VoxelizedModel};
+
+// Value of occupied voxels in dense volumes; empty voxels are 0
+const OCCUPIED: u8 = 255;
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+// Axis that PNG slices are taken along
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SliceAxis {
+    X,
+    Y,
+    #[default]
+    Z,
+}
+
+impl SliceAxis {
+    pub const ALL: [SliceAxis; 3] = [SliceAxis::X, SliceAxis::Y, SliceAxis::Z];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SliceAxis::X => "X",
+            SliceAxis::Y => "Y",
+            SliceAxis::Z => "Z",
+        }
+    }
+}
+
+// Occupancy of all submodels over the whole voxel range, one layer at a time
+// along an axis. Layers are filled from the full octree nodes crossing them, so
+// the volume is never held in memory at once. A layer has the other two axes in
+// order, the lower one running fastest: y by z for X, x by z for Y and x by y for Z.
+struct LayerSweep {
+    resolution: usize,
+    axis: usize,
+    // Full nodes as their lowest voxel counted from the grid's minimum corner and
+    // their size, by their first layer
+    nodes: Vec<([usize; 3], usize)>,
+    next_node: usize,
+    // Nodes crossing the current layer
+    active: Vec<([usize; 3], usize)>,
+    layer: usize,
+    values: Vec<u8>,
+}
+
+impl LayerSweep {
+    fn new(model: &VoxelizedModel, axis: usize) -> Self {
+        let resolution = 1usize << model.settings.octree_depth;
+        let half = (resolution / 2) as i32;
+        let mut nodes: Vec<([usize; 3], usize)> = model
+            .meshes
+            .iter()
+            .flat_map(|mesh| mesh.octree.iter_nodes())
+            .map(|(origin, size)| {
+                let corner = [origin.0, origin.1, origin.2].map(|c| (c + half) as usize);
+                (corner, size as usize)
+            })
+            .collect();
+        nodes.sort_unstable_by_key(|(corner, _)| corner[axis]);
+        Self {
+            resolution,
+            axis,
+            nodes,
+            next_node: 0,
+            active: Vec::new(),
+            layer: 0,
+            values: vec![0; resolution * resolution],
+        }
+    }
+
+    // The values of the next layer
+    fn next_layer(&mut self) -> &[u8] {
+        let (axis, layer, resolution) = (self.axis, self.layer, self.resolution);
+        self.active
+            .retain(|(corner, size)| corner[axis] + size > layer);
+        while let Some(&node) = self.nodes.get(self.next_node) {
+            if node.0[axis] != layer {
+                break;
+            }
+            self.active.push(node);
+            self.next_node += 1;
+        }
+
+        let (u, v) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        self.values.fill(0);
+        for (corner, size) in &self.active {
+            for row in corner[v]..corner[v] + size {
+                let start = row * resolution + corner[u];
+                self.values[start..start + size].fill(OCCUPIED);
+            }
+        }
+        self.layer += 1;
+        &self.values
+    }
+}
+
+// Write the volume layer by layer along z, so x runs fastest, then y, then z
+fn write_occupancy(model: &VoxelizedModel, writer: &mut impl Write) -> io::Result<()> {
+    let mut sweep = LayerSweep::new(model, 2);
+    for _ in 0..sweep.resolution {
+        writer.write_all(sweep.next_layer())?;
+    }
+    Ok(())
+}
+
+// Raw dense u8 volume of 2^depth voxels per axis, x running fastest, then y, then z
+pub fn write_raw(model: &VoxelizedModel, writer: &mut impl Write) -> io::Result<()> {
+    write_occupancy(model, writer)
+}
+
+// Gzip encoded NRRD occupancy volume
+pub fn write_nrrd(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_nrrd_header(
+        writer,
+        &format!("occupancy grid, {OCCUPIED} in occupied voxels"),
+        "uint8",
+        model.settings.octree_depth,
+        &model.normalization,
+        options,
+    )?;
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    write_occupancy(model, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// Gzip encoded NRRD volume of signed distances, placed like `write_nrrd`. In the
+// original units the distances are scaled back as well.
+pub fn write_sdf_nrrd(
+    sdf: &SignedDistanceField,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_nrrd_header(
+        writer,
+        "signed distance field, negative inside",
+        "float",
+        sdf.depth(),
+        &sdf.normalization,
+        options,
+    )?;
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    write_distances(sdf, options, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// Raw dense little endian f32 distances, in the layout of `write_raw`
+pub fn write_sdf_raw(
+    sdf: &SignedDistanceField,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_distances(sdf, options, writer)
+}
+
+fn write_distances(
+    sdf: &SignedDistanceField,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let scale = match options.mesh_units {
+        MeshUnits::Normalized => 1.0,
+        MeshUnits::Original => 1.0 / sdf.normalization.scale,
+    };
+    for value in sdf.values() {
+        writer.write_all(&(value * scale).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+// Header of a dense volume covering the whole voxel range. The space directions
+// and origin place the sample of every voxel at its cell center, in the normalized
+// range or the original units.
+fn write_nrrd_header(
+    writer: &mut impl Write,
+    comment: &str,
+    sample_type: &str,
+    depth: usize,
+    normalization: &Normalization,
+    options: &ExportOptions,
+) -> io::Result<()> {
+    let size = 1usize << depth;
+    let grid = VoxelGrid::new(depth);
+    let first = -(size as i32) / 2;
+    let mut origin = grid.cell_center(VoxelCoord(first, first, first));
+    let mut spacing = grid.cell_size;
+    if options.mesh_units == MeshUnits::Original {
+        origin = normalization.invert(origin);
+        spacing /= normalization.scale;
+    }
+
+    write!(
+        writer,
+        "NRRD0004\n\
+         # model_voxelization {comment}\n\
+         type: {sample_type}\n\
+         dimension: 3\n\
+         space dimension: 3\n\
+         sizes: {size} {size} {size}\n\
+         space directions: ({spacing},0,0) (0,{spacing},0) (0,0,{spacing})\n\
+         space origin: ({},{},{})\n\
+         endian: little\n\
+         encoding: gzip\n\
+         \n",
+        origin.x, origin.y, origin.z
+    )
+}
+
+// One grayscale PNG per voxel layer along the slice axis, named after `path` with
+// the layer number appended (model.png -> model_0000.png, model_0001.png, ...).
+// Images are seen from the positive end of the axis, with +Y up for X and Z slices.
+pub fn write_png_slices(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    path: &Path,
+) -> io::Result<()> {
+    write_png_slices_with_progress(model, options, path, &ExportProgress::new())
+}
+
+// Write PNG slices like `write_png_slices`, counting the bytes written in
+// `progress`. A cancelled stack is removed.
+pub(super) fn write_png_slices_with_progress(
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    path: &Path,
+    progress: &ExportProgress,
+) -> io::Result<()> {
+    let axis = match options.slice_axis {
+        SliceAxis::X => 0,
+        SliceAxis::Y => 1,
+        SliceAxis::Z => 2,
+    };
+    let mut sweep = LayerSweep::new(model, axis);
+    let size = sweep.resolution;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "slice".to_string());
+    let slice_path = |layer| path.with_file_name(format!("{}_{:04}.png", stem, layer));
+
+    let mut pixels = vec![0; size * size];
+    for layer in 0..size {
+        let values = sweep.next_layer();
+        for row in 0..size {
+            for column in 0..size {
+                let flipped_row = size - 1 - row;
+                pixels[row * size + column] = match options.slice_axis {
+                    // Layers are y by z, z decreasing to the right
+                    SliceAxis::X => values[(size - 1 - column) * size + flipped_row],
+                    SliceAxis::Y => values[row * size + column],
+                    SliceAxis::Z => values[flipped_row * size + column],
+                };
+            }
+        }
+
+        let result = File::create(slice_path(layer)).and_then(|file| {
+            let mut writer = BufWriter::new(ProgressWriter::new(file, progress));
+            write_png(&mut writer, size as u32, size as u32, &pixels)?;
+            writer.flush()
+        });
+        if let Err(err) = result {
+            if progress.is_cancelled() {
+                for layer in 0..=layer {
+                    let _ = std::fs::remove_file(slice_path(layer));
+                }
+            }
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+// 8 bit grayscale PNG without filtering
+fn write_png(writer: &mut impl Write, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    writer.write_all(PNG_SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, grayscale, deflate, no filter method, not interlaced
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_png_chunk(writer, b"IHDR", &header)?;
+
+    // Every row starts with its filter type, 0 for none
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in pixels.chunks_exact(width as usize) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    write_png_chunk(writer, b"IDAT", &encoder.finish()?)?;
+
+    write_png_chunk(writer, b"IEND", &[])
+}
+
+fn write_png_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.sum().to_be_bytes())
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::submodel_color;
+use crate::voxelization::VoxelizedModel;
+
+// MagicaVoxel limits every model to 256 voxels per axis
+const MAX_MODEL_SIZE: i32 = 256;
+
+// One MagicaVoxel model: a chunk of at most 256³ voxels of one submodel
+struct VoxModel {
+    size: [i32; 3],
+    // Translation of the model's center in the scene
+    translation: [i32; 3],
+    voxels: Vec<[u8; 4]>,
+}
+
+// Write a MagicaVoxel .vox file. Each submodel gets its own palette color and is
+// split into models of at most 256³ voxels, placed in the scene with transform nodes.
+pub fn write_vox(model: &VoxelizedModel, writer: &mut impl Write) -> io::Result<()> {
+    let resolution = 1i32 << model.settings.octree_depth;
+    let half = resolution / 2;
+
+    let mut models = Vec::new();
+    for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+        let color_index = (mesh_index % 255) as u8 + 1;
+
+        // Group voxels into 256³ chunks. MagicaVoxel is Z-up, so Y-up voxels map
+        // to (x, -z, y), shifted to be non-negative.
+        let mut chunks: BTreeMap<[i32; 3], Vec<[i32; 3]>> = BTreeMap::new();
+        for voxel in mesh.octree.iter_leaves(mesh.octree.depth()) {
+            let position = [voxel.0 + half, half - 1 - voxel.2, voxel.1 + half];
+            let chunk = position.map(|c| c.div_euclid(MAX_MODEL_SIZE));
+            chunks.entry(chunk).or_default().push(position);
+        }
+
+        for positions in chunks.into_values() {
+            let mut min = [i32::MAX; 3];
+            let mut max = [i32::MIN; 3];
+            for position in &positions {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+            let size = [0, 1, 2].map(|axis| max[axis] - min[axis] + 1);
+
+            let voxels = positions
+                .iter()
+                .map(|p| {
+                    [
+                        (p[0] - min[0]) as u8,
+                        (p[1] - min[1]) as u8,
+                        (p[2] - min[2]) as u8,
+                        color_index,
+                    ]
+                })
+                .collect();
+
+            // MagicaVoxel places a model's voxel (0, 0, 0) at translation - size / 2
+            let translation = [0, 1, 2].map(|axis| min[axis] + size[axis] / 2 - half);
+            models.push(VoxModel {
+                size,
+                translation,
+                voxels,
+            });
+        }
+    }
+
+    let mut children = Vec::new();
+    for vox_model in &models {
+        let mut size = Vec::new();
+        for value in vox_model.size {
+            write_i32(&mut size, value);
+        }
+        write_chunk(&mut children, b"SIZE", &size);
+
+        let mut xyzi = Vec::new();
+        write_i32(&mut xyzi, vox_model.voxels.len() as i32);
+        for voxel in &vox_model.voxels {
+            xyzi.extend_from_slice(voxel);
+        }
+        write_chunk(&mut children, b"XYZI", &xyzi);
+    }
+
+    write_scene_graph(&mut children, &models);
+
+    // Palette entry i holds color index i + 1, the color of submodel i
+    let mut palette = Vec::with_capacity(256 * 4);
+    for i in 0..256 {
+        palette.extend_from_slice(&submodel_color(i));
+    }
+    write_chunk(&mut children, b"RGBA", &palette);
+
+    writer.write_all(b"VOX ")?;
+    writer.write_all(&200i32.to_le_bytes())?;
+    writer.write_all(b"MAIN")?;
+    writer.write_all(&0i32.to_le_bytes())?;
+    writer.write_all(&(children.len() as i32).to_le_bytes())?;
+    writer.write_all(&children)
+}
+
+// Scene graph: root transform -> group -> one transform + shape per model
+fn write_scene_graph(out: &mut Vec<u8>, models: &[VoxModel]) {
+    // Node ids: 0 root transform, 1 group, then transform/shape pairs
+    let mut root = Vec::new();
+    write_i32(&mut root, 0);
+    write_dict(&mut root, &[]);
+    write_i32(&mut root, 1); // child node
+    write_i32(&mut root, -1); // reserved
+    write_i32(&mut root, -1); // layer
+    write_i32(&mut root, 1); // frames
+    write_dict(&mut root, &[]);
+    write_chunk(out, b"nTRN", &root);
+
+    let mut group = Vec::new();
+    write_i32(&mut group, 1);
+    write_dict(&mut group, &[]);
+    write_i32(&mut group, models.len() as i32);
+    for i in 0..models.len() {
+        write_i32(&mut group, 2 + 2 * i as i32);
+    }
+    write_chunk(out, b"nGRP", &group);
+
+    for (i, vox_model) in models.iter().enumerate() {
+        let transform_id = 2 + 2 * i as i32;
+        let [x, y, z] = vox_model.translation;
+        let translation = format!("{} {} {}", x, y, z);
+
+        let mut transform = Vec::new();
+        write_i32(&mut transform, transform_id);
+        write_dict(&mut transform, &[]);
+        write_i32(&mut transform, transform_id + 1); // shape node
+        write_i32(&mut transform, -1); // reserved
+        write_i32(&mut transform, 0); // layer
+        write_i32(&mut transform, 1); // frames
+        write_dict(&mut transform, &[("_t", &translation)]);
+        write_chunk(out, b"nTRN", &transform);
+
+        let mut shape = Vec::new();
+        write_i32(&mut shape, transform_id + 1);
+        write_dict(&mut shape, &[]);
+        write_i32(&mut shape, 1); // models
+        write_i32(&mut shape, i as i32);
+        write_dict(&mut shape, &[]);
+        write_chunk(out, b"nSHP", &shape);
+    }
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_i32(out, value.len() as i32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_dict(out: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    write_i32(out, entries.len() as i32);
+    for (key, value) in entries {
+        write_string(out, key);
+        write_string(out, value);
+    }
+}
+
+// Chunk without children
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend_from_slice(id);
+    write_i32(out, content.len() as i32);
+    write_i32(out, 0);
+    out.extend_from_slice(content);
+}
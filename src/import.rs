@@ -0,0 +1,121 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::mesh::TriangleMesh;
+use crate::sdf::SDF_MAX_DEPTH;
+use crate::voxelization::{VoxelizedModel, MAX_OCTREE_DEPTH};
+
+mod binvox;
+mod fbx;
+mod gltf;
+mod obj;
+mod off;
+mod ply;
+mod stl;
+
+// Error while reading a model file
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedFormat(String),
+    // The model has no usable triangles to voxelize
+    EmptyModel,
+    // Voxels per axis of a grid deeper than `MAX_OCTREE_DEPTH`
+    GridTooLarge(usize),
+    // Signed distance field depth outside 1..=`SDF_MAX_DEPTH`
+    InvalidSdfDepth(usize),
+    // Stopped through `VoxelizationProgress::cancel`
+    Cancelled,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "I/O error: {}", err),
+            ImportError::Parse(message) => write!(f, "parse error: {}", message),
+            ImportError::UnsupportedFormat(extension) => {
+                write!(f, "unsupported model format: {}", extension)
+            }
+            ImportError::EmptyModel => write!(f, "model has no triangles"),
+            ImportError::GridTooLarge(size) => write!(
+                f,
+                "grid of {} voxels per axis is larger than the {} supported",
+                size,
+                1 << MAX_OCTREE_DEPTH
+            ),
+            ImportError::InvalidSdfDepth(depth) => write!(
+                f,
+                "signed distance field depth {} is outside the supported 1 to {}",
+                depth, SDF_MAX_DEPTH
+            ),
+            ImportError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+// File extensions that can be loaded, including voxel files
+pub const SUPPORTED_EXTENSIONS: &[&str] =
+    &["obj", "stl", "fbx", "gltf", "glb", "ply", "off", "binvox"];
+
+// File extensions of already voxelized models
+pub const VOXEL_EXTENSIONS: &[&str] = &["binvox"];
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+// Whether the file contains voxels rather than triangles
+pub fn is_voxel_file(path: &Path) -> bool {
+    VOXEL_EXTENSIONS.contains(&extension(path).as_str())
+}
+
+// Load all submodels of a model file as triangle meshes, in the file's own units
+pub fn load_model(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let extension = extension(path);
+
+    match extension.as_str() {
+        "obj" => obj::load_obj(path),
+        "stl" => stl::load_stl(path),
+        "fbx" => fbx::load_fbx(path),
+        "gltf" | "glb" => gltf::load_gltf(path),
+        "ply" => ply::load_ply(path),
+        "off" => off::load_off(path),
+        _ => Err(ImportError::UnsupportedFormat(extension)),
+    }
+}
+
+// Load a voxel file as a voxelized model
+pub fn load_voxels(path: &Path) -> Result<VoxelizedModel, ImportError> {
+    let extension = extension(path);
+
+    match extension.as_str() {
+        "binvox" => binvox::load_binvox(path),
+        _ => Err(ImportError::UnsupportedFormat(extension)),
+    }
+}
+
+// Triangulate a convex polygon as a fan around its first vertex
+fn triangulate_polygon(polygon: &[u32], indices: &mut Vec<u32>) {
+    for i in 1..polygon.len().saturating_sub(1) {
+        indices.extend([polygon[0], polygon[i], polygon[i + 1]]);
+    }
+}
@@ -0,0 +1,135 @@
+use glam::Vec3;
+use std::path::Path;
+
+use super::ImportError;
+use crate::mesh::Normalization;
+use crate::octree::SparseVoxelOctree;
+use crate::voxelization::{
+    VoxelCoord, VoxelizationSettings, VoxelizedMesh, VoxelizedModel, MAX_OCTREE_DEPTH,
+};
+
+// Read a .binvox file into a voxelized model. The grid is placed in an octree
+// deep enough to hold it, and the header's translate/scale become the model's
+// normalization so voxels keep their original units.
+pub(super) fn load_binvox(path: &Path) -> Result<VoxelizedModel, ImportError> {
+    let data = std::fs::read(path)?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("binvox");
+    parse_binvox(&data, name)
+}
+
+fn parse_binvox(data: &[u8], name: &str) -> Result<VoxelizedModel, ImportError> {
+    let invalid = |message: &str| ImportError::Parse(format!("invalid binvox file: {}", message));
+
+    // Header lines up to and including "data"
+    let mut dims = None;
+    let mut translate = Vec3::ZERO;
+    let mut scale = 1.0;
+    let mut pos = 0;
+    let mut first = true;
+    loop {
+        let line_end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("missing data section"))?;
+        let line = std::str::from_utf8(&data[pos..pos + line_end])
+            .map_err(|_| invalid("header is not text"))?
+            .trim();
+        pos += line_end + 1;
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if first {
+            if tokens.first() != Some(&"#binvox") {
+                return Err(invalid("missing #binvox magic"));
+            }
+            first = false;
+            continue;
+        }
+
+        let numbers = |values: &[&str]| -> Result<Vec<f32>, ImportError> {
+            values
+                .iter()
+                .map(|v| {
+                    v.parse::<f32>()
+                        .map_err(|_| invalid("bad number in header"))
+                })
+                .collect()
+        };
+        match tokens.as_slice() {
+            ["dim", x, y, z] => {
+                let parse = |v: &str| v.parse::<usize>().map_err(|_| invalid("bad dimension"));
+                dims = Some([parse(x)?, parse(y)?, parse(z)?]);
+            }
+            ["translate", values @ ..] if values.len() == 3 => {
+                let values = numbers(values)?;
+                translate = Vec3::new(values[0], values[1], values[2]);
+            }
+            ["scale", value] => scale = numbers(&[value])?[0],
+            ["data"] => break,
+            _ => return Err(invalid(&format!("unknown header line: {}", line))),
+        }
+    }
+
+    let [dim_x, dim_y, dim_z] = dims.ok_or_else(|| invalid("missing dim"))?;
+    let max_dim = dim_x.max(dim_y).max(dim_z);
+    if max_dim == 0 {
+        return Err(invalid("empty grid"));
+    }
+    let depth = (max_dim.next_power_of_two().trailing_zeros() as usize).max(1);
+    if depth > MAX_OCTREE_DEPTH {
+        return Err(ImportError::GridTooLarge(max_dim));
+    }
+    let resolution = 1usize << depth;
+    let half = (resolution / 2) as i32;
+
+    // Decode the (value, count) runs; y runs fastest, then z, then x
+    let mut octree = SparseVoxelOctree::new(depth);
+    let total = dim_x * dim_y * dim_z;
+    let mut index = 0;
+    for pair in data[pos..].chunks_exact(2) {
+        let (value, count) = (pair[0], pair[1] as usize);
+        if index + count > total {
+            return Err(invalid("data longer than grid"));
+        }
+        if value != 0 {
+            for i in index..index + count {
+                let x = i / (dim_z * dim_y);
+                let z = (i / dim_y) % dim_z;
+                let y = i % dim_y;
+                octree.insert(VoxelCoord(
+                    x as i32 - half,
+                    y as i32 - half,
+                    z as i32 - half,
+                ));
+            }
+        }
+        index += count;
+    }
+    if index != total {
+        return Err(invalid("data shorter than grid"));
+    }
+
+    // One binvox cell is scale / max_dim original units and one octree voxel is
+    // 2 / resolution normalized units
+    let cell_size = scale / max_dim as f32;
+    let normalization = Normalization {
+        center: translate + Vec3::splat(half as f32 * cell_size),
+        scale: 2.0 / (resolution as f32 * cell_size),
+        size: Vec3::new(dim_x as f32, dim_y as f32, dim_z as f32) * cell_size,
+    };
+
+    Ok(VoxelizedModel {
+        meshes: vec![VoxelizedMesh {
+            name: name.to_string(),
+            octree,
+            diagnostics: None,
+        }],
+        normalization,
+        settings: VoxelizationSettings {
+            octree_depth: depth,
+            ..Default::default()
+        },
+    })
+}
@@ -0,0 +1,707 @@
+use flate2::read::ZlibDecoder;
+use glam::{Mat4, Quat, Vec3};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use super::{triangulate_polygon, ImportError};
+use crate::mesh::TriangleMesh;
+
+// FBX 7.x importer for binary and ASCII files. Both encodings are read into the
+// same node tree; geometry is then extracted from the "Objects" and
+// "Connections" sections, with every model node's transform applied.
+
+const BINARY_MAGIC: &[u8] = b"Kaydara FBX Binary  \x00";
+
+#[derive(Clone, Debug)]
+enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    // Raw binary data (embedded media); the payload is skipped
+    Raw,
+    IntArray(Vec<i64>),
+    FloatArray(Vec<f64>),
+}
+
+impl Property {
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Property::Int(value) => Some(*value),
+            Property::Bool(value) => Some(*value as i64),
+            Property::Float(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        match self {
+            Property::Float(value) => Some(*value),
+            Property::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_float_array(&self) -> Option<Vec<f64>> {
+        match self {
+            Property::FloatArray(values) => Some(values.clone()),
+            Property::IntArray(values) => Some(values.iter().map(|&v| v as f64).collect()),
+            _ => None,
+        }
+    }
+
+    fn to_int_array(&self) -> Option<Vec<i64>> {
+        match self {
+            Property::IntArray(values) => Some(values.clone()),
+            Property::FloatArray(values) => Some(values.iter().map(|&v| v as i64).collect()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+pub(super) fn load_fbx(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let data = std::fs::read(path)?;
+    let nodes = if data.starts_with(BINARY_MAGIC) {
+        parse_binary(&data)?
+    } else {
+        let text = std::str::from_utf8(&data)
+            .map_err(|_| ImportError::Parse("not a binary or ASCII FBX file".to_string()))?;
+        parse_ascii(text)?
+    };
+    extract_meshes(&nodes)
+}
+
+// Binary FBX
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // Files from version 7500 on use 64-bit offsets in node records
+    wide_offsets: bool,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ImportError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| ImportError::Parse("unexpected end of FBX file".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ImportError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ImportError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ImportError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn offset(&mut self) -> Result<u64, ImportError> {
+        if self.wide_offsets {
+            Ok(u64::from_le_bytes(self.array()?))
+        } else {
+            Ok(self.u32()? as u64)
+        }
+    }
+
+    // Read a node record; returns None for the null record that ends a node list
+    fn node(&mut self) -> Result<Option<Node>, ImportError> {
+        let end_offset = self.offset()? as usize;
+        let property_count = self.offset()?;
+        let _property_list_len = self.offset()?;
+        let name_len = self.u8()? as usize;
+        if end_offset == 0 {
+            return Ok(None);
+        }
+        if end_offset > self.data.len() {
+            return Err(ImportError::Parse(
+                "FBX node extends past end of file".to_string(),
+            ));
+        }
+
+        let name = String::from_utf8_lossy(self.take(name_len)?).into_owned();
+        let mut properties = Vec::new();
+        for _ in 0..property_count {
+            properties.push(self.property()?);
+        }
+
+        let mut children = Vec::new();
+        while self.pos < end_offset {
+            match self.node()? {
+                Some(child) => children.push(child),
+                None => break,
+            }
+        }
+        self.pos = end_offset;
+
+        Ok(Some(Node {
+            name,
+            properties,
+            children,
+        }))
+    }
+
+    fn property(&mut self) -> Result<Property, ImportError> {
+        let type_code = self.u8()?;
+        let property = match type_code {
+            b'C' => Property::Bool(self.u8()? != 0),
+            b'Y' => Property::Int(i16::from_le_bytes(self.array()?) as i64),
+            b'I' => Property::Int(i32::from_le_bytes(self.array()?) as i64),
+            b'L' => Property::Int(i64::from_le_bytes(self.array()?)),
+            b'F' => Property::Float(f32::from_le_bytes(self.array()?) as f64),
+            b'D' => Property::Float(f64::from_le_bytes(self.array()?)),
+            b'S' => {
+                let len = self.u32()? as usize;
+                Property::String(String::from_utf8_lossy(self.take(len)?).into_owned())
+            }
+            b'R' => {
+                let len = self.u32()? as usize;
+                self.take(len)?;
+                Property::Raw
+            }
+            b'f' => Property::FloatArray(
+                self.array_property(4)?
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+                    .collect(),
+            ),
+            b'd' => Property::FloatArray(
+                self.array_property(8)?
+                    .chunks_exact(8)
+                    .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'i' => Property::IntArray(
+                self.array_property(4)?
+                    .chunks_exact(4)
+                    .map(|b| i32::from_le_bytes(b.try_into().unwrap()) as i64)
+                    .collect(),
+            ),
+            b'l' => Property::IntArray(
+                self.array_property(8)?
+                    .chunks_exact(8)
+                    .map(|b| i64::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'b' => Property::IntArray(self.array_property(1)?.iter().map(|&b| b as i64).collect()),
+            other => {
+                return Err(ImportError::Parse(format!(
+                    "unknown FBX property type '{}'",
+                    other as char
+                )))
+            }
+        };
+        Ok(property)
+    }
+
+    // Raw bytes of an array property, inflated if zlib compressed
+    fn array_property(&mut self, element_size: usize) -> Result<Vec<u8>, ImportError> {
+        let len = self.u32()? as usize;
+        let encoding = self.u32()?;
+        let stored_len = self.u32()? as usize;
+        let stored = self.take(stored_len)?;
+        let expected_len = len * element_size;
+
+        let bytes = match encoding {
+            0 => stored.to_vec(),
+            1 => {
+                let mut bytes = Vec::with_capacity(expected_len);
+                ZlibDecoder::new(stored).read_to_end(&mut bytes)?;
+                bytes
+            }
+            other => {
+                return Err(ImportError::Parse(format!(
+                    "unknown FBX array encoding {}",
+                    other
+                )))
+            }
+        };
+
+        if bytes.len() != expected_len {
+            return Err(ImportError::Parse("FBX array has wrong length".to_string()));
+        }
+        Ok(bytes)
+    }
+}
+
+fn parse_binary(data: &[u8]) -> Result<Vec<Node>, ImportError> {
+    // Magic, two reserved bytes, then the u32 version
+    let mut reader = BinaryReader {
+        data,
+        pos: BINARY_MAGIC.len() + 2,
+        wide_offsets: false,
+    };
+    let version = reader.u32()?;
+    reader.wide_offsets = version >= 7500;
+    if version < 7000 {
+        return Err(ImportError::Parse(format!(
+            "FBX version {} is not supported, only 7.x",
+            version
+        )));
+    }
+
+    let mut nodes = Vec::new();
+    while let Some(node) = reader.node()? {
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+// ASCII FBX
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    // Identifier followed by ':', starting a node
+    Key(String),
+    // Bare identifier value such as `T` or `Y`
+    Word(String),
+    Number(String),
+    String(String),
+    // `*N` element count of an array node
+    Count,
+    Comma,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ImportError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ';' => {
+                // Comment until end of line
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            c if c.is_whitespace() => i += 1,
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '{' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            '}' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            '"' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(ImportError::Parse("unterminated FBX string".to_string()));
+                }
+                tokens.push(Token::String(chars[start..i].iter().collect()));
+                i += 1;
+            }
+            '*' => {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                tokens.push(Token::Count);
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || matches!(chars[i], '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Number(chars[start..i].iter().collect()));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '|'))
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if i < chars.len() && chars[i] == ':' {
+                    tokens.push(Token::Key(word));
+                    i += 1;
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+            other => {
+                return Err(ImportError::Parse(format!(
+                    "unexpected character '{}' in FBX file",
+                    other
+                )))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Node>, ImportError> {
+    let tokens = tokenize(text)?;
+    let mut pos = 0;
+    let nodes = parse_ascii_nodes(&tokens, &mut pos)?;
+    if pos < tokens.len() {
+        return Err(ImportError::Parse("unbalanced '}' in FBX file".to_string()));
+    }
+    Ok(nodes)
+}
+
+// Parse nodes until the end of input or a closing brace
+fn parse_ascii_nodes(tokens: &[Token], pos: &mut usize) -> Result<Vec<Node>, ImportError> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.get(*pos) {
+        let name = match token {
+            Token::Key(name) => name.clone(),
+            Token::Close => break,
+            other => {
+                return Err(ImportError::Parse(format!(
+                    "expected FBX node name, found {:?}",
+                    other
+                )))
+            }
+        };
+        *pos += 1;
+
+        let mut node = Node {
+            name,
+            ..Default::default()
+        };
+        let mut is_array = false;
+
+        // Comma separated values
+        loop {
+            match tokens.get(*pos) {
+                Some(Token::Number(number)) => node.properties.push(parse_number(number)?),
+                Some(Token::String(value)) | Some(Token::Word(value)) => {
+                    node.properties.push(Property::String(value.clone()))
+                }
+                Some(Token::Count) => is_array = true,
+                // Empty value, e.g. `Content: , "..."`
+                Some(Token::Comma) => {
+                    *pos += 1;
+                    continue;
+                }
+                _ => break,
+            }
+            *pos += 1;
+            if tokens.get(*pos) == Some(&Token::Comma) {
+                *pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        // Nested nodes
+        if tokens.get(*pos) == Some(&Token::Open) {
+            *pos += 1;
+            node.children = parse_ascii_nodes(tokens, pos)?;
+            if tokens.get(*pos) != Some(&Token::Close) {
+                return Err(ImportError::Parse("missing '}' in FBX file".to_string()));
+            }
+            *pos += 1;
+        }
+
+        // `Name: *N { a: values }` stores the array in the "a" child
+        if is_array {
+            let values = node
+                .child("a")
+                .map(|a| {
+                    a.properties
+                        .iter()
+                        .filter_map(Property::as_float)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            node.properties = vec![Property::FloatArray(values)];
+            node.children.clear();
+        }
+
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+fn parse_number(number: &str) -> Result<Property, ImportError> {
+    let invalid = || ImportError::Parse(format!("invalid number '{}' in FBX file", number));
+    if number.contains(['.', 'e', 'E']) {
+        number.parse().map(Property::Float).map_err(|_| invalid())
+    } else {
+        number.parse().map(Property::Int).map_err(|_| invalid())
+    }
+}
+
+// Geometry extraction
+
+// Object names are "Class::Name" in ASCII files and "Name\0\x01Class" in binary files
+fn object_name(node: &Node) -> String {
+    let raw = node
+        .properties
+        .get(1)
+        .and_then(Property::as_str)
+        .unwrap_or_default();
+    let name = match raw.split_once("\u{0}\u{1}") {
+        Some((name, _class)) => name,
+        None => raw.split_once("::").map_or(raw, |(_class, name)| name),
+    };
+    name.to_string()
+}
+
+fn object_id(node: &Node) -> Option<i64> {
+    node.properties.first().and_then(Property::as_int)
+}
+
+// Vector value of a "P" entry in a Properties70 block
+fn property_vec3(node: &Node, name: &str) -> Option<Vec3> {
+    node.child("Properties70")?
+        .children_named("P")
+        .find(|p| p.properties.first().and_then(Property::as_str) == Some(name))
+        .and_then(|p| {
+            let values: Vec<f64> = p
+                .properties
+                .get(4..)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Property::as_float)
+                .collect();
+            (values.len() >= 3)
+                .then(|| Vec3::new(values[0] as f32, values[1] as f32, values[2] as f32))
+        })
+}
+
+// Integer value of a "P" entry in a Properties70 block, such as an enum
+fn property_int(node: &Node, name: &str) -> Option<i64> {
+    node.child("Properties70")?
+        .children_named("P")
+        .find(|p| p.properties.first().and_then(Property::as_str) == Some(name))
+        .and_then(|p| p.properties.get(4))
+        .and_then(Property::as_int)
+}
+
+// FBX Euler angles are in degrees. `order` is the RotationOrder enum, naming the
+// axes in the order they are applied; 0 (XYZ) is the default, and the spheric
+// order 6 is treated like it.
+fn euler_rotation(degrees: Vec3, order: i64) -> Quat {
+    let radians = degrees * (std::f32::consts::PI / 180.0);
+    let (x, y, z) = (
+        Quat::from_rotation_x(radians.x),
+        Quat::from_rotation_y(radians.y),
+        Quat::from_rotation_z(radians.z),
+    );
+    match order {
+        1 => y * z * x,
+        2 => x * z * y,
+        3 => z * x * y,
+        4 => y * x * z,
+        5 => x * y * z,
+        _ => z * y * x,
+    }
+}
+
+// Local transform of a model node, as defined by the FBX SDK:
+// T * Roff * Rp * Rpre * R * Rpost^-1 * Rp^-1 * Soff * Sp * S * Sp^-1.
+// Pre- and post-rotations always use the XYZ order.
+fn local_transform(model: &Node) -> Mat4 {
+    let vector = |name: &str, default: Vec3| property_vec3(model, name).unwrap_or(default);
+    let translation = Mat4::from_translation(vector("Lcl Translation", Vec3::ZERO));
+    let rotation_offset = Mat4::from_translation(vector("RotationOffset", Vec3::ZERO));
+    let rotation_pivot = vector("RotationPivot", Vec3::ZERO);
+    let pre_rotation = euler_rotation(vector("PreRotation", Vec3::ZERO), 0);
+    let order = property_int(model, "RotationOrder").unwrap_or(0);
+    let rotation = euler_rotation(vector("Lcl Rotation", Vec3::ZERO), order);
+    let post_rotation = euler_rotation(vector("PostRotation", Vec3::ZERO), 0);
+    let scaling_offset = Mat4::from_translation(vector("ScalingOffset", Vec3::ZERO));
+    let scaling_pivot = vector("ScalingPivot", Vec3::ZERO);
+    let scaling = Mat4::from_scale(vector("Lcl Scaling", Vec3::ONE));
+
+    translation
+        * rotation_offset
+        * Mat4::from_translation(rotation_pivot)
+        * Mat4::from_quat(pre_rotation * rotation * post_rotation.inverse())
+        * Mat4::from_translation(-rotation_pivot)
+        * scaling_offset
+        * Mat4::from_translation(scaling_pivot)
+        * scaling
+        * Mat4::from_translation(-scaling_pivot)
+}
+
+// Geometric transform, applied to the model's geometry only and not inherited by children
+fn geometric_transform(model: &Node) -> Mat4 {
+    let translation = property_vec3(model, "GeometricTranslation").unwrap_or(Vec3::ZERO);
+    let rotation = property_vec3(model, "GeometricRotation").unwrap_or(Vec3::ZERO);
+    let scaling = property_vec3(model, "GeometricScaling").unwrap_or(Vec3::ONE);
+
+    Mat4::from_scale_rotation_translation(scaling, euler_rotation(rotation, 0), translation)
+}
+
+fn extract_meshes(nodes: &[Node]) -> Result<Vec<TriangleMesh>, ImportError> {
+    let objects = nodes
+        .iter()
+        .find(|node| node.name == "Objects")
+        .ok_or_else(|| ImportError::Parse("FBX file has no Objects section".to_string()))?;
+
+    let geometries: HashMap<i64, &Node> = objects
+        .children_named("Geometry")
+        .filter(|g| g.properties.get(2).and_then(Property::as_str) == Some("Mesh"))
+        .filter_map(|g| Some((object_id(g)?, g)))
+        .collect();
+    let models: HashMap<i64, &Node> = objects
+        .children_named("Model")
+        .filter_map(|m| Some((object_id(m)?, m)))
+        .collect();
+
+    // Object-object connections: "C: "OO", child, parent"
+    let mut parents: HashMap<i64, i64> = HashMap::new();
+    let mut geometry_models: Vec<(i64, i64)> = Vec::new();
+    if let Some(connections) = nodes.iter().find(|node| node.name == "Connections") {
+        for connection in connections.children_named("C") {
+            let props = &connection.properties;
+            if props.first().and_then(Property::as_str) != Some("OO") {
+                continue;
+            }
+            let (Some(child), Some(parent)) = (
+                props.get(1).and_then(Property::as_int),
+                props.get(2).and_then(Property::as_int),
+            ) else {
+                continue;
+            };
+
+            if geometries.contains_key(&child) && models.contains_key(&parent) {
+                geometry_models.push((child, parent));
+            } else if models.contains_key(&child) {
+                parents.insert(child, parent);
+            }
+        }
+    }
+
+    let world_transform = |model_id: i64| {
+        let mut transform = Mat4::IDENTITY;
+        let mut current = Some(model_id);
+        // Guard against connection cycles in malformed files
+        let mut steps = 0;
+        while let Some(id) = current {
+            let Some(model) = models.get(&id) else {
+                break;
+            };
+            transform = local_transform(model) * transform;
+            current = parents.get(&id).copied();
+            steps += 1;
+            if steps > models.len() {
+                break;
+            }
+        }
+        transform
+    };
+
+    let mut meshes = Vec::new();
+    for &(geometry_id, model_id) in &geometry_models {
+        let model = models[&model_id];
+        let transform = world_transform(model_id) * geometric_transform(model);
+        meshes.push(geometry_mesh(
+            geometries[&geometry_id],
+            object_name(model),
+            transform,
+        ));
+    }
+
+    // Geometry that is not attached to any model keeps its own coordinates
+    let mut unattached: Vec<_> = geometries
+        .iter()
+        .filter(|(id, _)| !geometry_models.iter().any(|(g, _)| g == *id))
+        .collect();
+    unattached.sort_by_key(|(id, _)| **id);
+    for (_, geometry) in unattached {
+        meshes.push(geometry_mesh(
+            geometry,
+            object_name(geometry),
+            Mat4::IDENTITY,
+        ));
+    }
+
+    Ok(meshes)
+}
+
+fn geometry_mesh(geometry: &Node, name: String, transform: Mat4) -> TriangleMesh {
+    let vertices = geometry
+        .child("Vertices")
+        .and_then(|v| v.properties.first())
+        .and_then(Property::to_float_array)
+        .unwrap_or_default();
+    let polygon_indices = geometry
+        .child("PolygonVertexIndex")
+        .and_then(|p| p.properties.first())
+        .and_then(Property::to_int_array)
+        .unwrap_or_default();
+
+    let positions: Vec<Vec3> = vertices
+        .chunks_exact(3)
+        .map(|v| transform.transform_point3(Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32)))
+        .collect();
+
+    // A negative index ends a polygon and encodes the last vertex as -(index + 1),
+    // which is the bitwise complement and cannot overflow
+    let mut indices = Vec::new();
+    let mut polygon = Vec::new();
+    for &index in &polygon_indices {
+        let (vertex, last) = if index < 0 {
+            (!index, true)
+        } else {
+            (index, false)
+        };
+        // Indices past the vertices are kept, as u32::MAX if they do not fit, and their
+        // triangles are skipped as out of range
+        polygon.push(u32::try_from(vertex).unwrap_or(u32::MAX));
+
+        if last {
+            triangulate_polygon(&polygon, &mut indices);
+            polygon.clear();
+        }
+    }
+
+    TriangleMesh::new(name, positions, indices)
+}
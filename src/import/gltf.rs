@@ -0,0 +1,119 @@
+use glam::{Mat4, Vec3};
+use gltf::mesh::Mode;
+use std::path::Path;
+
+use super::{triangulate_polygon, ImportError};
+use crate::mesh::TriangleMesh;
+
+// Load every triangle primitive of a .gltf/.glb file, with the node hierarchy's
+// transforms applied. Each primitive becomes its own mesh, like OBJ submodels.
+pub(super) fn load_gltf(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let gltf = gltf::Gltf::open(path).map_err(gltf_error)?;
+    let buffers = gltf::import_buffers(&gltf.document, path.parent(), gltf.blob.clone())
+        .map_err(gltf_error)?;
+
+    let mut meshes = Vec::new();
+    match gltf
+        .document
+        .default_scene()
+        .or_else(|| gltf.document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                add_node(&node, Mat4::IDENTITY, &buffers, &mut meshes)?;
+            }
+        }
+        // Files without scenes: load the meshes untransformed
+        None => {
+            for mesh in gltf.document.meshes() {
+                add_mesh(&mesh, mesh.name(), Mat4::IDENTITY, &buffers, &mut meshes)?;
+            }
+        }
+    }
+
+    Ok(meshes)
+}
+
+fn gltf_error(err: gltf::Error) -> ImportError {
+    match err {
+        gltf::Error::Io(err) => ImportError::Io(err),
+        err => ImportError::Parse(err.to_string()),
+    }
+}
+
+fn add_node(
+    node: &gltf::Node,
+    parent_transform: Mat4,
+    buffers: &[gltf::buffer::Data],
+    meshes: &mut Vec<TriangleMesh>,
+) -> Result<(), ImportError> {
+    let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        let name = node.name().or(mesh.name());
+        add_mesh(&mesh, name, transform, buffers, meshes)?;
+    }
+    for child in node.children() {
+        add_node(&child, transform, buffers, meshes)?;
+    }
+    Ok(())
+}
+
+fn add_mesh(
+    mesh: &gltf::Mesh,
+    name: Option<&str>,
+    transform: Mat4,
+    buffers: &[gltf::buffer::Data],
+    meshes: &mut Vec<TriangleMesh>,
+) -> Result<(), ImportError> {
+    let name = name
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("mesh{}", mesh.index()));
+
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+        let positions: Vec<Vec3> = positions
+            .map(|p| transform.transform_point3(Vec3::from(p)))
+            .collect();
+        let vertex_indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let indices = match primitive.mode() {
+            Mode::Triangles => vertex_indices,
+            Mode::TriangleStrip => strip_to_list(&vertex_indices),
+            Mode::TriangleFan => {
+                let mut indices = Vec::new();
+                triangulate_polygon(&vertex_indices, &mut indices);
+                indices
+            }
+            // Points and lines have no surface to voxelize
+            _ => continue,
+        };
+
+        let primitive_name = if mesh.primitives().len() > 1 {
+            format!("{}.{}", name, primitive.index())
+        } else {
+            name.clone()
+        };
+        meshes.push(TriangleMesh::new(primitive_name, positions, indices));
+    }
+    Ok(())
+}
+
+fn strip_to_list(strip: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for i in 0..strip.len().saturating_sub(2) {
+        // Every other triangle is flipped to keep a consistent winding
+        if i % 2 == 0 {
+            indices.extend([strip[i], strip[i + 1], strip[i + 2]]);
+        } else {
+            indices.extend([strip[i + 1], strip[i], strip[i + 2]]);
+        }
+    }
+    indices
+}
@@ -0,0 +1,159 @@
+use glam::Vec3;
+use std::path::Path;
+
+use super::ImportError;
+use crate::mesh::TriangleMesh;
+
+// Index given to face corners that referenced a missing vertex; it is past the end
+// of every mesh, so `TriangleMesh::triangles` skips their triangles and `diagnose`
+// counts them
+const OUT_OF_RANGE: u32 = u32::MAX;
+
+pub(super) fn load_obj(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let text = std::fs::read_to_string(path)?;
+
+    // tobj rejects the whole file if a face references a missing vertex, so such
+    // references are pointed at a placeholder vertex first
+    let redirected = redirect_missing_vertices(&text);
+    let source = redirected.as_ref().map_or(&text, |(text, _)| text);
+    let placeholder = redirected.as_ref().map(|&(_, placeholder)| placeholder);
+    let (models, _materials_maybe) = tobj::load_obj_buf(
+        &mut source.as_bytes(),
+        &tobj::LoadOptions {
+            triangulate: true,
+            ..Default::default()
+        },
+        |material_path| tobj::load_mtl(path.with_file_name(material_path)),
+    )
+    .map_err(|err| ImportError::Parse(err.to_string()))?;
+
+    Ok(models
+        .into_iter()
+        .map(|model| {
+            let mut positions: Vec<Vec3> = model
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Vec3::new(p[0], p[1], p[2]))
+                .collect();
+            let mut indices = model.mesh.indices;
+            if let Some(placeholder) = placeholder {
+                remove_placeholder(&mut positions, &mut indices, placeholder);
+            }
+            TriangleMesh::new(model.name, positions, indices)
+        })
+        .collect())
+}
+
+// The OBJ text with a placeholder vertex added before all others and every face
+// reference to a missing vertex pointed at it, together with the placeholder's
+// position, which is not the position of any vertex of the file. None if every
+// face references existing vertices.
+fn redirect_missing_vertices(text: &str) -> Option<(String, Vec3)> {
+    let mut positions = Vec::new();
+    let mut has_colors = false;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("v") {
+            let values: Vec<f32> = words.map_while(|word| word.parse().ok()).collect();
+            if values.len() >= 3 {
+                positions.push(Vec3::new(values[0], values[1], values[2]));
+            }
+            has_colors |= values.len() >= 6;
+        }
+    }
+
+    let total = positions.len();
+    let mut read = 0;
+    let mut any_missing = false;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => read += 1,
+            Some("f" | "l") => any_missing |= words.any(|word| is_missing(word, read, total)),
+            _ => {}
+        }
+    }
+    if !any_missing {
+        return None;
+    }
+
+    let mut placeholder = Vec3::splat(f32::MAX);
+    while positions.contains(&placeholder) {
+        placeholder.x = f32::from_bits(placeholder.x.to_bits() - 1);
+    }
+
+    // Vertex colors must stay aligned with the positions, so the placeholder gets
+    // one when the file has them
+    let mut redirected = String::with_capacity(text.len() + 100);
+    redirected.push_str(&format!(
+        "v {} {} {}",
+        placeholder.x, placeholder.y, placeholder.z
+    ));
+    redirected.push_str(if has_colors { " 0 0 0\n" } else { "\n" });
+
+    // Vertex numbers move up by one behind the placeholder; relative references
+    // are counted back from the current vertex and stay as they are
+    let mut read = 0;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                read += 1;
+                redirected.push_str(line);
+            }
+            Some(keyword @ ("f" | "l")) => {
+                redirected.push_str(keyword);
+                for word in words {
+                    let (vertex, rest) = word.split_at(word.find('/').unwrap_or(word.len()));
+                    redirected.push(' ');
+                    if is_missing(word, read, total) {
+                        redirected.push('1');
+                        redirected.push_str(rest);
+                    } else if let Ok(number @ 1..) = vertex.parse::<i64>() {
+                        redirected.push_str(&(number + 1).to_string());
+                        redirected.push_str(rest);
+                    } else {
+                        redirected.push_str(word);
+                    }
+                }
+            }
+            _ => redirected.push_str(line),
+        }
+        redirected.push('\n');
+    }
+    Some((redirected, placeholder))
+}
+
+// Whether a face word like `v`, `v/vt/vn` or `v//vn` references a vertex the file
+// does not have: vertex numbers count from 1 up to `total`, and negative ones count
+// back from the `read` vertices before the face. Words that are not references are
+// left for tobj to reject.
+fn is_missing(word: &str, read: usize, total: usize) -> bool {
+    let Some(Ok(number)) = word.split('/').next().map(str::parse::<i64>) else {
+        return false;
+    };
+    let index = if number < 0 {
+        read.checked_sub(number.unsigned_abs() as usize)
+    } else {
+        (number as usize).checked_sub(1)
+    };
+    index.is_none_or(|index| index >= total)
+}
+
+// Drop the placeholder vertex from a loaded mesh, turning its uses into out of
+// range indices
+fn remove_placeholder(positions: &mut Vec<Vec3>, indices: &mut [u32], placeholder: Vec3) {
+    let Some(removed) = positions.iter().position(|&p| p == placeholder) else {
+        return;
+    };
+    positions.remove(removed);
+    let removed = removed as u32;
+    for index in indices {
+        if *index == removed {
+            *index = OUT_OF_RANGE;
+        } else if *index > removed {
+            *index -= 1;
+        }
+    }
+}
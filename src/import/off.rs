@@ -0,0 +1,85 @@
+use glam::Vec3;
+use std::path::Path;
+
+use super::{triangulate_polygon, ImportError};
+use crate::mesh::TriangleMesh;
+
+pub(super) fn load_off(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let text = std::fs::read_to_string(path)?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("off");
+    parse_off(&text, name).map(|mesh| vec![mesh])
+}
+
+// Parse an OFF file. Color/normal variants (COFF, NOFF, ...) are accepted; only
+// the first three values of a vertex line and the index list of a face line are used.
+fn parse_off(text: &str, name: &str) -> Result<TriangleMesh, ImportError> {
+    // Non-empty lines with comments removed
+    let mut lines = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty());
+
+    let invalid = |message: &str| ImportError::Parse(format!("invalid OFF file: {}", message));
+    let parse_usize = |token: &str| token.parse::<usize>().map_err(|_| invalid("bad count"));
+
+    // The "OFF" keyword may be followed by the counts on the same line
+    let first = lines.next().ok_or_else(|| invalid("empty file"))?;
+    let mut tokens: Vec<&str> = first.split_whitespace().collect();
+    if tokens[0].ends_with("OFF") {
+        tokens.remove(0);
+        if tokens.is_empty() {
+            tokens = lines
+                .next()
+                .ok_or_else(|| invalid("missing counts"))?
+                .split_whitespace()
+                .collect();
+        }
+    }
+    if tokens.len() < 2 {
+        return Err(invalid("missing counts"));
+    }
+    let vertex_count = parse_usize(tokens[0])?;
+    let face_count = parse_usize(tokens[1])?;
+
+    // The counts come from the file, so the vertices are not preallocated
+    let mut positions = Vec::new();
+    for _ in 0..vertex_count {
+        let line = lines.next().ok_or_else(|| invalid("missing vertices"))?;
+        let coords: Vec<f32> = line
+            .split_whitespace()
+            .take(3)
+            .map(|token| token.parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("bad vertex coordinate"))?;
+        if coords.len() != 3 {
+            return Err(invalid("vertex needs three coordinates"));
+        }
+        positions.push(Vec3::new(coords[0], coords[1], coords[2]));
+    }
+
+    let mut indices = Vec::new();
+    let mut polygon = Vec::new();
+    for _ in 0..face_count {
+        let line = lines.next().ok_or_else(|| invalid("missing faces"))?;
+        let mut tokens = line.split_whitespace();
+        let count = parse_usize(tokens.next().unwrap_or_default())?;
+
+        polygon.clear();
+        for token in tokens.take(count) {
+            // Indices past the vertices are kept; their triangles are skipped as out of range
+            let index = token
+                .parse::<u32>()
+                .map_err(|_| invalid("bad vertex index"))?;
+            polygon.push(index);
+        }
+        if polygon.len() != count {
+            return Err(invalid("face has fewer indices than declared"));
+        }
+        triangulate_polygon(&polygon, &mut indices);
+    }
+
+    Ok(TriangleMesh::new(name, positions, indices))
+}
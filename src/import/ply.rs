@@ -0,0 +1,279 @@
+use glam::Vec3;
+use std::path::Path;
+
+use super::{triangulate_polygon, ImportError};
+use crate::mesh::TriangleMesh;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, ImportError> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            other => {
+                return Err(ImportError::Parse(format!(
+                    "unknown PLY property type {}",
+                    other
+                )))
+            }
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    // Count type and item type of a list property
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values of the body, read either from whitespace separated text or binary data
+enum BodyReader<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary {
+        data: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, ImportError> {
+        let eof = || ImportError::Parse("unexpected end of PLY data".to_string());
+        match self {
+            BodyReader::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(eof)?;
+                token
+                    .parse()
+                    .map_err(|_| ImportError::Parse(format!("invalid PLY value {}", token)))
+            }
+            BodyReader::Binary {
+                data,
+                pos,
+                big_endian,
+            } => {
+                let size = ty.size();
+                let bytes = data.get(*pos..*pos + size).ok_or_else(eof)?;
+                *pos += size;
+
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                let value = match ty {
+                    ScalarType::I8 => buf[0] as i8 as f64,
+                    ScalarType::U8 => buf[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(buf),
+                };
+                Ok(value)
+            }
+        }
+    }
+}
+
+pub(super) fn load_ply(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let data = std::fs::read(path)?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("ply");
+    parse_ply(&data, name).map(|mesh| vec![mesh])
+}
+
+fn parse_ply(data: &[u8], name: &str) -> Result<TriangleMesh, ImportError> {
+    let (format, elements, body_start) = parse_header(data)?;
+
+    let mut reader = match format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(&data[body_start..])
+                .map_err(|_| ImportError::Parse("ASCII PLY body is not valid UTF-8".to_string()))?;
+            BodyReader::Ascii(text.split_ascii_whitespace())
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => BodyReader::Binary {
+            data,
+            pos: body_start,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+
+    for element in &elements {
+        // Positions of x/y/z in the vertex element, and the face index list
+        let coordinate = |axis: &str| element.properties.iter().position(|p| p.name == axis);
+        let xyz = [coordinate("x"), coordinate("y"), coordinate("z")];
+        let face_list = element
+            .properties
+            .iter()
+            .position(|p| p.name == "vertex_indices" || p.name == "vertex_index");
+
+        let mut values = vec![0.0; element.properties.len()];
+        let mut polygon = Vec::new();
+
+        for _ in 0..element.count {
+            polygon.clear();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.ty {
+                    PropertyType::Scalar(ty) => values[i] = reader.read(ty)?,
+                    PropertyType::List(count_ty, item_ty) => {
+                        let count = reader.read(count_ty)? as usize;
+                        for _ in 0..count {
+                            let item = reader.read(item_ty)?;
+                            if Some(i) == face_list {
+                                polygon.push(vertex_index(item)?);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                let [Some(x), Some(y), Some(z)] = xyz else {
+                    return Err(ImportError::Parse(
+                        "PLY vertex element has no x/y/z properties".to_string(),
+                    ));
+                };
+                positions.push(Vec3::new(
+                    values[x] as f32,
+                    values[y] as f32,
+                    values[z] as f32,
+                ));
+            } else if element.name == "face" {
+                triangulate_polygon(&polygon, &mut indices);
+            }
+        }
+    }
+
+    Ok(TriangleMesh::new(name, positions, indices))
+}
+
+// Convert a face list entry to a vertex index, rejecting negative and fractional values
+fn vertex_index(value: f64) -> Result<u32, ImportError> {
+    if !(0.0..=u32::MAX as f64).contains(&value) || value.fract() != 0.0 {
+        return Err(ImportError::Parse(format!(
+            "invalid PLY vertex index {}",
+            value
+        )));
+    }
+    Ok(value as u32)
+}
+
+// Parse the header, returning the body format, the elements and the offset of the body
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), ImportError> {
+    const END_HEADER: &[u8] = b"end_header";
+    let header_end = data
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or_else(|| ImportError::Parse("PLY header has no end_header".to_string()))?;
+    // The body starts after the end_header line's newline
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |newline| header_end + newline + 1);
+
+    let header = std::str::from_utf8(&data[..header_end])
+        .map_err(|_| ImportError::Parse("PLY header is not valid UTF-8".to_string()))?;
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(ImportError::Parse("missing PLY magic".to_string()));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let invalid = || ImportError::Parse(format!("invalid PLY header line: {}", line));
+
+        match tokens.as_slice() {
+            ["format", kind, _version] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid()),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid())?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => {
+                let element = elements.last_mut().ok_or_else(invalid)?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::List(
+                        ScalarType::parse(count_ty)?,
+                        ScalarType::parse(item_ty)?,
+                    ),
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements.last_mut().ok_or_else(invalid)?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::Scalar(ScalarType::parse(ty)?),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid()),
+        }
+    }
+
+    let format =
+        format.ok_or_else(|| ImportError::Parse("PLY header has no format".to_string()))?;
+    Ok((format, elements, body_start))
+}
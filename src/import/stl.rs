@@ -0,0 +1,137 @@
+use glam::Vec3;
+use std::path::Path;
+
+use super::ImportError;
+use crate::mesh::TriangleMesh;
+
+// Binary STL: 80 byte header, u32 triangle count, 50 bytes per triangle
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+pub(super) fn load_stl(path: &Path) -> Result<Vec<TriangleMesh>, ImportError> {
+    let data = std::fs::read(path)?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("stl");
+    parse_stl(&data, name)
+}
+
+// Parse binary or ASCII STL data. ASCII files with several solids produce one mesh per solid.
+fn parse_stl(data: &[u8], name: &str) -> Result<Vec<TriangleMesh>, ImportError> {
+    // Some binary files start their header with "solid" as well, so trust the size check first
+    if is_binary(data) {
+        Ok(vec![parse_binary(data, name)])
+    } else if let Some(text) = ascii_text(data) {
+        parse_ascii(text, name)
+    } else if data.len() >= HEADER_SIZE {
+        Err(ImportError::Parse(format!(
+            "binary STL size mismatch: header declares {} triangles but file has {} bytes",
+            triangle_count(data),
+            data.len()
+        )))
+    } else {
+        Err(ImportError::Parse("file too short for an STL".to_string()))
+    }
+}
+
+fn triangle_count(data: &[u8]) -> usize {
+    u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize
+}
+
+// The file as text if it looks like an ASCII STL
+fn ascii_text(data: &[u8]) -> Option<&str> {
+    std::str::from_utf8(data)
+        .ok()
+        .filter(|text| text.trim_start().starts_with("solid"))
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE
+        && triangle_count(data)
+            .checked_mul(TRIANGLE_SIZE)
+            .is_some_and(|size| HEADER_SIZE + size == data.len())
+}
+
+fn parse_binary(data: &[u8], name: &str) -> TriangleMesh {
+    let read_vec3 = |bytes: &[u8]| {
+        let component =
+            |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Vec3::new(component(0), component(4), component(8))
+    };
+
+    let triangles = data[HEADER_SIZE..]
+        .chunks_exact(TRIANGLE_SIZE)
+        .map(|record| {
+            // Skip the 12 byte facet normal; the 2 byte attribute count follows the vertices
+            [
+                read_vec3(&record[12..24]),
+                read_vec3(&record[24..36]),
+                read_vec3(&record[36..48]),
+            ]
+        });
+
+    TriangleMesh::from_triangles(name, triangles)
+}
+
+fn parse_ascii(text: &str, name: &str) -> Result<Vec<TriangleMesh>, ImportError> {
+    let mut meshes = Vec::new();
+    let mut triangles = Vec::new();
+    let mut solid_name: Option<String> = None;
+    let mut facet = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error =
+            |message: &str| ImportError::Parse(format!("line {}: {}", line_number + 1, message));
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("solid") => {
+                let rest: Vec<&str> = tokens.collect();
+                solid_name = Some(if rest.is_empty() {
+                    name.to_string()
+                } else {
+                    rest.join(" ")
+                });
+            }
+            Some("facet") => facet.clear(),
+            Some("vertex") => {
+                let coords: Vec<f32> = tokens
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| error("invalid vertex coordinate"))?;
+                if coords.len() != 3 {
+                    return Err(error("vertex needs three coordinates"));
+                }
+                facet.push(Vec3::new(coords[0], coords[1], coords[2]));
+            }
+            Some("endfacet") => {
+                if facet.len() < 3 {
+                    return Err(error("facet has fewer than three vertices"));
+                }
+                // Facets should be triangles; fan triangulate anything larger
+                for i in 1..facet.len() - 1 {
+                    triangles.push([facet[0], facet[i], facet[i + 1]]);
+                }
+                facet.clear();
+            }
+            Some("endsolid") => {
+                let solid_name = solid_name.take().unwrap_or_else(|| name.to_string());
+                meshes.push(TriangleMesh::from_triangles(
+                    solid_name,
+                    std::mem::take(&mut triangles),
+                ));
+            }
+            // "outer loop", "endloop" and blank lines carry no geometry
+            _ => {}
+        }
+    }
+
+    // Tolerate a missing final "endsolid"
+    if !triangles.is_empty() {
+        let solid_name = solid_name.unwrap_or_else(|| name.to_string());
+        meshes.push(TriangleMesh::from_triangles(solid_name, triangles));
+    }
+
+    Ok(meshes)
+}
@@ -0,0 +1,13 @@
+// Headless voxelization library: load triangle meshes, voxelize them into a
+// sparse voxel octree and build a cube mesh of the result. The Bevy app in
+// `main.rs` is built on top of this API; the library itself only uses glam.
+
+pub mod export;
+pub mod import;
+pub mod log;
+pub mod mesh;
+pub mod meshing;
+pub mod octree;
+pub mod project;
+pub mod sdf;
+pub mod voxelization;
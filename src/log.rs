@@ -0,0 +1,33 @@
+// Messages of the library, such as voxelization statistics and mesh check warnings.
+// The library only records them; callers that want them, like the app's log
+// window, take them with `take_messages` and decide whether to print them.
+use std::collections::VecDeque;
+use std::sync::{Mutex, PoisonError};
+
+// Messages kept until they are taken; older ones are dropped
+pub const LOG_CAPACITY: usize = 1000;
+
+static MESSAGES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// Record a message formatted like `format!`
+macro_rules! record {
+    ($($arg:tt)*) => {
+        $crate::log::record_message(format!($($arg)*))
+    };
+}
+pub(crate) use record;
+
+// Record a message for `take_messages`
+pub fn record_message(message: String) {
+    let mut messages = MESSAGES.lock().unwrap_or_else(PoisonError::into_inner);
+    if messages.len() == LOG_CAPACITY {
+        messages.pop_front();
+    }
+    messages.push_back(message);
+}
+
+// The messages recorded since the last call, oldest first
+pub fn take_messages() -> Vec<String> {
+    let mut messages = MESSAGES.lock().unwrap_or_else(PoisonError::into_inner);
+    messages.drain(..).collect()
+}
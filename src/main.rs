@@ -1,18 +1,44 @@
+// Record a message for the log window; it is printed to stdout when collected
+#[cfg(feature = "gui")]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        model_voxelization::log::record_message(format!($($arg)*))
+    };
+}
+
+#[cfg(feature = "gui")]
 mod camera;
+mod cli;
+#[cfg(feature = "gui")]
 mod model;
+#[cfg(feature = "gui")]
 mod ui;
-mod voxelization;
 
+#[cfg(feature = "gui")]
 use bevy::prelude::*;
+#[cfg(feature = "gui")]
 use bevy::window::WindowPlugin;
+#[cfg(feature = "gui")]
 use bevy_egui::EguiPlugin;
 
+#[cfg(feature = "gui")]
 use camera::{camera_controller_system, setup_camera};
-use model::{load_model_system, ModelResource};
+#[cfg(feature = "gui")]
+use model::{
+    collect_log_system, load_model_system, model_export_system, sdf_export_system, ExportSettings,
+    LogMessages, ModelResource, VoxelSettings,
+};
+#[cfg(feature = "gui")]
 use ui::ui_system;
-use voxelization::VoxelizationSettings;
 
 fn main() {
+    // Batch mode: voxelize files from the command line without opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_cli_invocation(&args) || cfg!(not(feature = "gui")) {
+        std::process::exit(cli::run(&args));
+    }
+
+    #[cfg(feature = "gui")]
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
@@ -23,11 +49,16 @@ fn main() {
         }))
         .add_plugins(EguiPlugin)
         .init_resource::<ModelResource>()
-        .init_resource::<VoxelizationSettings>()
+        .init_resource::<VoxelSettings>()
+        .init_resource::<ExportSettings>()
+        .init_resource::<LogMessages>()
         .add_systems(Startup, setup_camera)
         // Ensure systems run in correct order
         .add_systems(Update, ui_system)
         .add_systems(Update, load_model_system)
         .add_systems(Update, camera_controller_system)
+        .add_systems(Update, sdf_export_system)
+        .add_systems(Update, model_export_system)
+        .add_systems(Update, collect_log_system)
         .run();
 }
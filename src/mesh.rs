@@ -0,0 +1,245 @@
+use glam::Vec3;
+use std::collections::HashSet;
+
+use crate::voxelization::COORDINATE_RANGE;
+
+// Indexed triangle mesh, the input of the voxelizer
+#[derive(Clone, Debug, Default)]
+pub struct TriangleMesh {
+    pub name: String,
+    pub positions: Vec<Vec3>,
+    // Three indices into `positions` per triangle
+    pub indices: Vec<u32>,
+}
+
+impl TriangleMesh {
+    pub fn new(name: impl Into<String>, positions: Vec<Vec3>, indices: Vec<u32>) -> Self {
+        Self {
+            name: name.into(),
+            positions,
+            indices,
+        }
+    }
+
+    // Build a mesh from unindexed triangles
+    pub fn from_triangles(
+        name: impl Into<String>,
+        triangles: impl IntoIterator<Item = [Vec3; 3]>,
+    ) -> Self {
+        let mut positions = Vec::new();
+        for triangle in triangles {
+            positions.extend(triangle);
+        }
+        let indices = (0..positions.len() as u32).collect();
+        Self::new(name, positions, indices)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // Corners of every usable triangle. Triangles with an index out of range, a NaN
+    // or infinite corner or zero area are skipped; `diagnose` counts them.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|tri| self.check_triangle(tri).ok())
+    }
+
+    // Count the problems in the mesh, including the triangles `triangles` skips
+    pub fn diagnose(&self) -> MeshDiagnostics {
+        let mut diagnostics = MeshDiagnostics {
+            trailing_indices: self.indices.len() % 3,
+            ..Default::default()
+        };
+
+        for tri in self.indices.chunks_exact(3) {
+            match self.check_triangle(tri) {
+                Ok(_) => {}
+                Err(TriangleProblem::IndexOutOfRange) => diagnostics.out_of_range_triangles += 1,
+                Err(TriangleProblem::NonFinite) => diagnostics.non_finite_triangles += 1,
+                Err(TriangleProblem::Degenerate) => diagnostics.degenerate_triangles += 1,
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for position in &self.positions {
+            if !position.is_finite() {
+                diagnostics.non_finite_vertices += 1;
+            // Adding zero turns -0.0 into 0.0, so both count as the same position
+            } else if !seen.insert((*position + Vec3::ZERO).to_array().map(f32::to_bits)) {
+                diagnostics.duplicate_vertices += 1;
+            }
+        }
+
+        diagnostics
+    }
+
+    fn check_triangle(&self, tri: &[u32]) -> Result<[Vec3; 3], TriangleProblem> {
+        let corner = |i: u32| self.positions.get(i as usize).copied();
+        let (Some(a), Some(b), Some(c)) = (corner(tri[0]), corner(tri[1]), corner(tri[2])) else {
+            return Err(TriangleProblem::IndexOutOfRange);
+        };
+        if !(a.is_finite() && b.is_finite() && c.is_finite()) {
+            return Err(TriangleProblem::NonFinite);
+        }
+        // Repeated or collinear corners, or a triangle too small for its normal to
+        // be computed in f32
+        if (b - a).cross(c - a).try_normalize().is_none() {
+            return Err(TriangleProblem::Degenerate);
+        }
+        Ok([a, b, c])
+    }
+}
+
+enum TriangleProblem {
+    IndexOutOfRange,
+    NonFinite,
+    Degenerate,
+}
+
+// Problems found in a triangle mesh by `TriangleMesh::diagnose`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MeshDiagnostics {
+    // Triangles referencing a vertex past the end of `positions`, skipped
+    pub out_of_range_triangles: usize,
+    // Triangles with a NaN or infinite corner, skipped
+    pub non_finite_triangles: usize,
+    // Triangles with zero area, skipped
+    pub degenerate_triangles: usize,
+    // Indices after the last complete triangle, ignored
+    pub trailing_indices: usize,
+    // Vertices with a NaN or infinite coordinate
+    pub non_finite_vertices: usize,
+    // Vertices at exactly the position of an earlier vertex. Harmless, but a sign
+    // of an unwelded mesh; STL files always repeat the corners of every triangle.
+    pub duplicate_vertices: usize,
+}
+
+impl MeshDiagnostics {
+    pub fn skipped_triangles(&self) -> usize {
+        self.out_of_range_triangles + self.non_finite_triangles + self.degenerate_triangles
+    }
+
+    // Whether anything in the mesh was left out of the voxelization
+    pub fn has_errors(&self) -> bool {
+        self.skipped_triangles() > 0 || self.trailing_indices > 0 || self.non_finite_vertices > 0
+    }
+
+    // One line per kind of problem found
+    pub fn messages(&self) -> Vec<String> {
+        [
+            (
+                self.out_of_range_triangles,
+                "triangles with out of range indices skipped",
+            ),
+            (
+                self.non_finite_triangles,
+                "triangles with NaN or infinite corners skipped",
+            ),
+            (
+                self.degenerate_triangles,
+                "degenerate (zero area) triangles skipped",
+            ),
+            (
+                self.trailing_indices,
+                "indices after the last complete triangle ignored",
+            ),
+            (
+                self.non_finite_vertices,
+                "vertices with NaN or infinite coordinates",
+            ),
+            (self.duplicate_vertices, "duplicate vertices"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, message)| format!("{} {}", count, message))
+        .collect()
+    }
+}
+
+impl std::ops::AddAssign for MeshDiagnostics {
+    fn add_assign(&mut self, other: Self) {
+        self.out_of_range_triangles += other.out_of_range_triangles;
+        self.non_finite_triangles += other.non_finite_triangles;
+        self.degenerate_triangles += other.degenerate_triangles;
+        self.trailing_indices += other.trailing_indices;
+        self.non_finite_vertices += other.non_finite_vertices;
+        self.duplicate_vertices += other.duplicate_vertices;
+    }
+}
+
+// Transform that fits a model into the voxelizer's coordinate range:
+// normalized = (original - center) * scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalization {
+    pub center: Vec3,
+    pub scale: f32,
+    // Bounding box dimensions of the original model
+    pub size: Vec3,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            scale: 1.0,
+            size: Vec3::ZERO,
+        }
+    }
+}
+
+impl Normalization {
+    // Compute the normalization for the overall bounding box of all meshes
+    pub fn from_meshes(meshes: &[TriangleMesh]) -> Self {
+        let mut min = Vec3::MAX;
+        let mut max = Vec3::MIN;
+        // NaN and infinite positions are never part of a voxelized triangle
+        let positions = meshes.iter().flat_map(|mesh| &mesh.positions);
+        for position in positions.filter(|position| position.is_finite()) {
+            min = min.min(*position);
+            max = max.max(*position);
+        }
+        if min.cmpgt(max).any() {
+            return Self::default();
+        }
+
+        let center = (min + max) / 2.0;
+        let size = max - min;
+        let max_dimension = size.max_element();
+
+        // Calculate scaling factor to fit model in -1~1 range
+        let world_size = COORDINATE_RANGE * 2.0 * 0.95; // Use -0.95~0.95 actual range
+        let scale = if max_dimension > 0.0 {
+            world_size / max_dimension
+        } else {
+            1.0
+        };
+
+        Self {
+            center,
+            scale,
+            size,
+        }
+    }
+
+    pub fn apply(&self, position: Vec3) -> Vec3 {
+        (position - self.center) * self.scale
+    }
+
+    // Map a normalized position back to the original model units
+    pub fn invert(&self, position: Vec3) -> Vec3 {
+        position / self.scale + self.center
+    }
+}
+
+// Center and scale all meshes into the -1~1 range, returning the applied transform
+pub fn normalize_meshes(meshes: &mut [TriangleMesh]) -> Normalization {
+    let normalization = Normalization::from_meshes(meshes);
+    for mesh in meshes.iter_mut() {
+        for position in &mut mesh.positions {
+            *position = normalization.apply(*position);
+        }
+    }
+    normalization
+}
@@ -0,0 +1,295 @@
+use glam::Vec3;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::octree::SparseVoxelOctree;
+use crate::voxelization::{VoxelCoord, VoxelGrid};
+
+mod surface_nets;
+
+pub use surface_nets::create_surface_nets_mesh;
+
+// Indexed triangle list with per-vertex normals and UVs, laid out like Bevy's mesh attributes
+#[derive(Clone, Debug, Default)]
+pub struct SurfaceMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl SurfaceMesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+// How the voxel surface is turned into triangles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshingMode {
+    // One quad per exposed voxel face
+    #[default]
+    Cubes,
+    // Adjacent coplanar faces merged into larger quads
+    Greedy,
+    // Smooth isosurface of the occupancy grid
+    SurfaceNets,
+}
+
+impl MeshingMode {
+    pub const ALL: [MeshingMode; 3] = [
+        MeshingMode::Cubes,
+        MeshingMode::Greedy,
+        MeshingMode::SurfaceNets,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MeshingMode::Cubes => "Cubes (one quad per face)",
+            MeshingMode::Greedy => "Greedy (merged faces)",
+            MeshingMode::SurfaceNets => "Smooth (Surface Nets)",
+        }
+    }
+}
+
+// One side of a voxel: the neighbor that hides it, its corners in the order of
+// `box_corners` and its outward normal
+struct CubeFace {
+    offset: [i32; 3],
+    corners: [usize; 4],
+    normal: [f32; 3],
+}
+
+const CUBE_FACES: [CubeFace; 6] = [
+    // right
+    CubeFace {
+        offset: [1, 0, 0],
+        corners: [1, 2, 6, 5],
+        normal: [1.0, 0.0, 0.0],
+    },
+    // left
+    CubeFace {
+        offset: [-1, 0, 0],
+        corners: [0, 4, 7, 3],
+        normal: [-1.0, 0.0, 0.0],
+    },
+    // top
+    CubeFace {
+        offset: [0, 1, 0],
+        corners: [4, 5, 6, 7],
+        normal: [0.0, 1.0, 0.0],
+    },
+    // bottom
+    CubeFace {
+        offset: [0, -1, 0],
+        corners: [0, 3, 2, 1],
+        normal: [0.0, -1.0, 0.0],
+    },
+    // front
+    CubeFace {
+        offset: [0, 0, 1],
+        corners: [3, 7, 6, 2],
+        normal: [0.0, 0.0, 1.0],
+    },
+    // back
+    CubeFace {
+        offset: [0, 0, -1],
+        corners: [0, 1, 5, 4],
+        normal: [0.0, 0.0, -1.0],
+    },
+];
+
+impl CubeFace {
+    // Axis the face is perpendicular to
+    fn axis(&self) -> usize {
+        self.offset.iter().position(|&o| o != 0).unwrap()
+    }
+
+    // Voxels of the box from `origin` with `size` voxels per axis that have this
+    // face on the box surface; the faces of the others touch the box itself
+    fn box_side(&self, origin: VoxelCoord, size: i32) -> impl Iterator<Item = VoxelCoord> {
+        let axis = self.axis();
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut start = [origin.0, origin.1, origin.2];
+        if self.offset[axis] > 0 {
+            start[axis] += size - 1;
+        }
+        (0..size * size).map(move |i| {
+            let mut cell = start;
+            cell[u] += i % size;
+            cell[v] += i / size;
+            VoxelCoord(cell[0], cell[1], cell[2])
+        })
+    }
+
+    fn is_exposed(&self, octree: &SparseVoxelOctree, voxel: VoxelCoord) -> bool {
+        let [dx, dy, dz] = self.offset;
+        !octree.is_occupied(VoxelCoord(voxel.0 + dx, voxel.1 + dy, voxel.2 + dz))
+    }
+
+    // Voxels whose face on this side is exposed. Only voxels on the surface of a
+    // full node's box can have one, so the cost grows with the surface, not the volume.
+    fn exposed_voxels<'a>(
+        &'a self,
+        octree: &'a SparseVoxelOctree,
+    ) -> impl Iterator<Item = VoxelCoord> + 'a {
+        octree
+            .iter_nodes()
+            .flat_map(move |(origin, size)| self.box_side(origin, size))
+            .filter(move |&voxel| self.is_exposed(octree, voxel))
+    }
+}
+
+// Build the surface mesh of the occupied voxels with the given mesher
+pub fn build_surface_mesh(octree: &SparseVoxelOctree, mode: MeshingMode) -> SurfaceMesh {
+    match mode {
+        MeshingMode::Cubes => create_voxelized_mesh(octree),
+        MeshingMode::Greedy => create_greedy_mesh(octree),
+        MeshingMode::SurfaceNets => create_surface_nets_mesh(octree),
+    }
+}
+
+// Number of voxel faces not shared by two voxels, i.e. the quads of the cube mesh
+pub fn count_exposed_faces(octree: &SparseVoxelOctree) -> usize {
+    CUBE_FACES
+        .iter()
+        .map(|face| face.exposed_voxels(octree).count())
+        .sum()
+}
+
+// Build a cube mesh of the occupied voxels, skipping faces shared by two voxels
+pub fn create_voxelized_mesh(octree: &SparseVoxelOctree) -> SurfaceMesh {
+    let grid = VoxelGrid::new(octree.depth());
+    let mut mesh = SurfaceMesh::default();
+
+    // Render visible voxel faces; only voxels on the surface of a full node's box
+    // can have any
+    for (origin, size) in octree.iter_nodes() {
+        for face in &CUBE_FACES {
+            // Only add geometry for faces without an adjacent voxel
+            for voxel in face.box_side(origin, size) {
+                if face.is_exposed(octree, voxel) {
+                    let cell = [voxel.0, voxel.1, voxel.2];
+                    let corners = box_corners(cell, cell.map(|c| c + 1), &grid);
+                    add_cube_face(&mut mesh, &corners, face.corners, face.normal);
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+// Build a mesh of the same surface as `create_voxelized_mesh`, with the exposed faces
+// of every layer merged into as few rectangles as possible. The surface can have
+// T-junctions where merged quads of different sizes meet.
+pub fn create_greedy_mesh(octree: &SparseVoxelOctree) -> SurfaceMesh {
+    let grid = VoxelGrid::new(octree.depth());
+    let mut mesh = SurfaceMesh::default();
+
+    for face in &CUBE_FACES {
+        // The face lies in a layer along `axis` and spans the `u` and `v` axes
+        let axis = face.axis();
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        // Exposed faces grouped by layer, as (u, v) cell positions
+        let mut layers: BTreeMap<i32, HashSet<(i32, i32)>> = BTreeMap::new();
+        for voxel in face.exposed_voxels(octree) {
+            let cell = [voxel.0, voxel.1, voxel.2];
+            layers
+                .entry(cell[axis])
+                .or_default()
+                .insert((cell[u], cell[v]));
+        }
+
+        for (layer, mut cells) in layers {
+            // Visit cells row by row so the output does not depend on hash order
+            let mut starts: Vec<(i32, i32)> = cells.iter().copied().collect();
+            starts.sort_unstable_by_key(|&(cu, cv)| (cv, cu));
+
+            for (u_start, v_start) in starts {
+                if !cells.contains(&(u_start, v_start)) {
+                    continue;
+                }
+
+                // Grow the rectangle along u, then add rows while they are complete
+                let mut u_end = u_start + 1;
+                while cells.contains(&(u_end, v_start)) {
+                    u_end += 1;
+                }
+                let mut v_end = v_start + 1;
+                while (u_start..u_end).all(|cu| cells.contains(&(cu, v_end))) {
+                    v_end += 1;
+                }
+
+                for cv in v_start..v_end {
+                    for cu in u_start..u_end {
+                        cells.remove(&(cu, cv));
+                    }
+                }
+
+                let mut min = [0; 3];
+                let mut max = [0; 3];
+                (min[axis], max[axis]) = (layer, layer + 1);
+                (min[u], max[u]) = (u_start, u_end);
+                (min[v], max[v]) = (v_start, v_end);
+                let corners = box_corners(min, max, &grid);
+                add_cube_face(&mut mesh, &corners, face.corners, face.normal);
+            }
+        }
+    }
+
+    mesh
+}
+
+// Corners of the box covering the voxels from `min` up to but excluding `max`
+fn box_corners(min: [i32; 3], max: [i32; 3], grid: &VoxelGrid) -> [Vec3; 8] {
+    let to_world = |cell: [i32; 3]| grid.to_world(Vec3::from(cell.map(|c| c as f32)));
+    let (lo, hi) = (to_world(min), to_world(max));
+
+    [
+        Vec3::new(lo.x, lo.y, lo.z), // 0: back bottom left
+        Vec3::new(hi.x, lo.y, lo.z), // 1: back bottom right
+        Vec3::new(hi.x, lo.y, hi.z), // 2: front bottom right
+        Vec3::new(lo.x, lo.y, hi.z), // 3: front bottom left
+        Vec3::new(lo.x, hi.y, lo.z), // 4: back top left
+        Vec3::new(hi.x, hi.y, lo.z), // 5: back top right
+        Vec3::new(hi.x, hi.y, hi.z), // 6: front top right
+        Vec3::new(lo.x, hi.y, hi.z), // 7: front top left
+    ]
+}
+
+// Add a face to the cube
+fn add_cube_face(
+    mesh: &mut SurfaceMesh,
+    corners: &[Vec3; 8],
+    face_indices: [usize; 4],
+    normal: [f32; 3],
+) {
+    let start_idx = mesh.positions.len() as u32;
+
+    // Add four vertices
+    for &idx in &face_indices {
+        mesh.positions
+            .push([corners[idx].x, corners[idx].y, corners[idx].z]);
+        mesh.normals.push(normal);
+    }
+
+    // Add UV coordinates
+    mesh.uvs.push([0.0, 0.0]);
+    mesh.uvs.push([1.0, 0.0]);
+    mesh.uvs.push([1.0, 1.0]);
+    mesh.uvs.push([0.0, 1.0]);
+
+    // Add two triangles
+    mesh.indices.push(start_idx);
+    mesh.indices.push(start_idx + 2);
+    mesh.indices.push(start_idx + 1);
+
+    mesh.indices.push(start_idx);
+    mesh.indices.push(start_idx + 3);
+    mesh.indices.push(start_idx + 2);
+}
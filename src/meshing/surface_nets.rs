@@ -0,0 +1,153 @@
+use glam::Vec3;
+use std::collections::HashMap;
+
+use super::{SurfaceMesh, CUBE_FACES};
+use crate::octree::SparseVoxelOctree;
+use crate::voxelization::{VoxelCoord, VoxelGrid};
+
+// Relaxation passes that move every vertex towards the mean of its neighbors
+const SMOOTHING_ITERATIONS: usize = 8;
+
+// Offsets along the two other axes of the four cells around an exposed face,
+// counterclockwise around the face's axis
+const QUAD_CELLS: [(i32, i32); 4] = [(-1, -1), (0, -1), (0, 0), (-1, 0)];
+
+// Smooth mesh of the occupied voxels using naive Surface Nets. The dual grid has
+// a cell between every eight neighboring voxel centers; each cell with both
+// occupied and empty corners gets one vertex, and each exposed voxel face becomes
+// a quad joining the four cells around it. Vertices start at the mean of their
+// cell's sign changes and are then smoothed, but never leave their cell, so the
+// surface stays within half a voxel of the cube mesh.
+pub fn create_surface_nets_mesh(octree: &SparseVoxelOctree) -> SurfaceMesh {
+    let grid = VoxelGrid::new(octree.depth());
+    let occupied = |cell: [i32; 3]| octree.is_occupied(VoxelCoord(cell[0], cell[1], cell[2]));
+
+    // Dual cells, named after their lowest corner voxel, and the vertex of each
+    let mut cells: Vec<[i32; 3]> = Vec::new();
+    let mut vertices: HashMap<[i32; 3], u32> = HashMap::new();
+    let mut quads: Vec<[u32; 4]> = Vec::new();
+
+    // Only exposed faces make quads, so the cells are found from the faces on the
+    // surface of the full nodes rather than by visiting every voxel
+    for face in &CUBE_FACES {
+        let axis = face.axis();
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for voxel in face.exposed_voxels(octree) {
+            let voxel = [voxel.0, voxel.1, voxel.2];
+            let neighbor = [0, 1, 2].map(|k| voxel[k] + face.offset[k]);
+
+            // The cells around the edge from the voxel center to the empty neighbor
+            let mut base = voxel;
+            base[axis] = voxel[axis].min(neighbor[axis]);
+            let quad = QUAD_CELLS.map(|(du, dv)| {
+                let mut cell = base;
+                cell[u] += du;
+                cell[v] += dv;
+                *vertices.entry(cell).or_insert_with(|| {
+                    cells.push(cell);
+                    cells.len() as u32 - 1
+                })
+            });
+
+            // Counterclockwise around +axis faces outwards on positive faces only
+            quads.push(if face.offset[axis] > 0 {
+                quad
+            } else {
+                [quad[0], quad[3], quad[2], quad[1]]
+            });
+        }
+    }
+
+    // Positions in grid coordinates shifted by half a voxel, so voxel x is centered
+    // at x and the dual cell named x spans x..x + 1
+    let mut positions: Vec<Vec3> = cells
+        .iter()
+        .map(|&cell| sign_change_center(cell, occupied))
+        .collect();
+
+    let mut neighbors: Vec<Vec<u32>> = vec![Vec::new(); cells.len()];
+    for quad in &quads {
+        for i in 0..4 {
+            let (a, b) = (quad[i], quad[(i + 1) % 4]);
+            if !neighbors[a as usize].contains(&b) {
+                neighbors[a as usize].push(b);
+                neighbors[b as usize].push(a);
+            }
+        }
+    }
+
+    for _ in 0..SMOOTHING_ITERATIONS {
+        positions = positions
+            .iter()
+            .zip(&neighbors)
+            .zip(&cells)
+            .map(|((&position, neighbors), cell)| {
+                if neighbors.is_empty() {
+                    return position;
+                }
+                let mean = neighbors
+                    .iter()
+                    .map(|&n| positions[n as usize])
+                    .sum::<Vec3>()
+                    / neighbors.len() as f32;
+                let min = Vec3::from(cell.map(|c| c as f32));
+                mean.clamp(min, min + Vec3::ONE)
+            })
+            .collect();
+    }
+
+    // Split every quad along its shorter diagonal
+    let mut indices = Vec::with_capacity(quads.len() * 6);
+    for [a, b, c, d] in quads {
+        let [pa, pb, pc, pd] = [a, b, c, d].map(|i| positions[i as usize]);
+        if pa.distance_squared(pc) <= pb.distance_squared(pd) {
+            indices.extend_from_slice(&[a, b, c, a, c, d]);
+        } else {
+            indices.extend_from_slice(&[a, b, d, b, c, d]);
+        }
+    }
+
+    // Area weighted vertex normals
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| positions[triangle[k] as usize]);
+        let normal = (b - a).cross(c - a);
+        for &i in triangle {
+            normals[i as usize] += normal;
+        }
+    }
+
+    SurfaceMesh {
+        positions: positions
+            .iter()
+            .map(|&p| grid.to_world(p + Vec3::splat(0.5)).to_array())
+            .collect(),
+        normals: normals
+            .iter()
+            .map(|n| n.normalize_or_zero().to_array())
+            .collect(),
+        uvs: vec![[0.0, 0.0]; positions.len()],
+        indices,
+    }
+}
+
+// Mean of the midpoints of the cell edges whose two corners differ in occupancy
+fn sign_change_center(cell: [i32; 3], occupied: impl Fn([i32; 3]) -> bool) -> Vec3 {
+    let corner = |i: usize| [0, 1, 2].map(|k| cell[k] + ((i >> k) & 1) as i32);
+    let inside: [bool; 8] = std::array::from_fn(|i| occupied(corner(i)));
+
+    let mut sum = Vec3::ZERO;
+    let mut count = 0;
+    for a in 0..8 {
+        for bit in [1, 2, 4] {
+            let b = a | bit;
+            if b != a && inside[a] != inside[b] {
+                let [pa, pb] = [a, b].map(|i| Vec3::from(corner(i).map(|c| c as f32)));
+                sum += (pa + pb) / 2.0;
+                count += 1;
+            }
+        }
+    }
+    // Cells only get a vertex when they lie on the surface, so count is never 0
+    sum / count.max(1) as f32
+}
@@ -1,16 +1,234 @@
-use crate::voxelization::{create_voxelized_mesh, VoxelizationSettings, COORDINATE_RANGE};
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use model_voxelization::{
+    export::{
+        export_model_with_progress, export_sdf, ExportFormat, ExportOptions, ExportProgress,
+        SdfFormat,
+    },
+    import::ImportError,
+    log::{take_messages, LOG_CAPACITY},
+    meshing::{build_surface_mesh, count_exposed_faces, MeshingMode, SurfaceMesh},
+    project::hash_file,
+    sdf::sdf_file_with_progress,
+    voxelization::{
+        voxelize_file_with_progress, VoxelizationProgress, VoxelizationSettings, VoxelizedModel,
+    },
+};
+use std::collections::VecDeque;
+use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 // Component marker for mesh entities
 #[derive(Component)]
 pub struct ModelMesh;
 
+// Viewer meshes of a voxelization, built on the async compute task pool
+pub struct ModelMeshes {
+    meshing: MeshingMode,
+    surfaces: Vec<SurfaceMesh>,
+    // Quads the cube mesher needs, for comparison with the chosen mesher
+    cube_face_count: usize,
+}
+
+impl ModelMeshes {
+    fn build(voxelized: &VoxelizedModel, meshing: MeshingMode) -> Self {
+        let mut surfaces = Vec::new();
+        let mut cube_face_count = 0;
+        for voxelized_mesh in &voxelized.meshes {
+            let surface = build_surface_mesh(&voxelized_mesh.octree, meshing);
+            cube_face_count += match meshing {
+                MeshingMode::Cubes => surface.triangle_count() / 2,
+                _ => count_exposed_faces(&voxelized_mesh.octree),
+            };
+            surfaces.push(surface);
+        }
+        Self {
+            meshing,
+            surfaces,
+            cube_face_count,
+        }
+    }
+}
+
+// The voxels, the hash of the file they were made from and their meshes
+type VoxelizationResult = Result<(VoxelizedModel, Option<u64>, ModelMeshes), ImportError>;
+
+// Voxelization of a model file running on the async compute task pool
+pub struct VoxelizationJob {
+    pub path: PathBuf,
+    pub progress: Arc<VoxelizationProgress>,
+    task: Task<VoxelizationResult>,
+}
+
+// Signed distance field computation and export running on the async compute task pool
+pub struct SdfExportJob {
+    // File the field is written to
+    pub path: PathBuf,
+    pub progress: Arc<VoxelizationProgress>,
+    task: Task<Result<(), ImportError>>,
+}
+
+impl SdfExportJob {
+    // Compute the field of the model at `source` and write it to `path`
+    pub fn spawn(
+        source: PathBuf,
+        path: PathBuf,
+        depth: usize,
+        format: SdfFormat,
+        options: ExportOptions,
+    ) -> Self {
+        let progress = Arc::new(VoxelizationProgress::new());
+        let task_progress = progress.clone();
+        let task_path = path.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let sdf = sdf_file_with_progress(&source, depth, &task_progress)?;
+            export_sdf(&sdf, &task_path, format, &options)?;
+            Ok::<_, ImportError>(())
+        });
+        Self {
+            path,
+            progress,
+            task,
+        }
+    }
+}
+
+// Export of the voxels running on the async compute task pool
+pub struct ExportJob {
+    // File the voxels are written to
+    pub path: PathBuf,
+    pub progress: Arc<ExportProgress>,
+    task: Task<io::Result<()>>,
+}
+
+impl ExportJob {
+    // Write `voxelized` to `path`; the job owns its copy, so the shown model can
+    // change in the meantime
+    pub fn spawn(
+        voxelized: VoxelizedModel,
+        path: PathBuf,
+        format: ExportFormat,
+        options: ExportOptions,
+    ) -> Self {
+        let progress = Arc::new(ExportProgress::new());
+        let task_progress = progress.clone();
+        let task_path = path.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            export_model_with_progress(&voxelized, &task_path, format, &options, &task_progress)
+        });
+        Self {
+            path,
+            progress,
+            task,
+        }
+    }
+}
+
+// Model or project file that could not be loaded, shown until it is closed or
+// another model is loaded
+pub struct LoadError {
+    pub path: PathBuf,
+    pub error: ImportError,
+}
+
 // Resource for storing model path
 #[derive(Resource, Default)]
 pub struct ModelResource {
+    // Model file to voxelize; None while a project without a source model is shown
     pub path: Option<PathBuf>,
+    // Project file the shown voxels were opened from
+    pub project_path: Option<PathBuf>,
+    // Whether loading the current path with the current settings has been started
     pub loaded: bool,
+    // Voxelization in progress; replacing or dropping it does not stop it, cancel
+    // its progress first
+    pub job: Option<VoxelizationJob>,
+    // Why the last load failed, if it did
+    pub error: Option<LoadError>,
+    // Whether building the displayed meshes of the voxels with the chosen mesher
+    // has been started
+    pub meshed: bool,
+    // Meshes being rebuilt after the mesher changed
+    pub meshing_job: Option<Task<ModelMeshes>>,
+    // Signed distance field export in progress
+    pub sdf_job: Option<SdfExportJob>,
+    // Voxel export in progress
+    pub export_job: Option<ExportJob>,
+    // Totals over all submodels of the loaded model
+    pub voxel_count: usize,
+    pub octree_node_count: usize,
+    // Size of the displayed meshes, and the number of quads the cube mesher
+    // needs for comparison
+    pub mesh_vertex_count: usize,
+    pub mesh_triangle_count: usize,
+    pub cube_face_count: usize,
+    // Voxelization result of the loaded model, used for export
+    pub voxelized: Option<VoxelizedModel>,
+    // Hash of the model file when it was voxelized, saved with projects
+    pub source_hash: Option<u64>,
+    // Voxels of an opened project, shown on the next load instead of voxelizing
+    pub project_voxels: Option<VoxelizedModel>,
+}
+
+// Voxelization settings used by the app
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct VoxelSettings(pub VoxelizationSettings);
+
+// Export options chosen in the File > Export menu. The mesher is chosen in the
+// Settings menu and is used for the viewer as well.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ExportSettings(pub ExportOptions);
+
+// Messages of the app and the library for the log window, newest last
+#[derive(Resource, Default)]
+pub struct LogMessages(pub VecDeque<String>);
+
+// Print the messages recorded since the last frame and keep them for the log window
+pub fn collect_log_system(mut log_messages: ResMut<LogMessages>) {
+    for message in take_messages() {
+        println!("{}", message);
+        if log_messages.0.len() == LOG_CAPACITY {
+            log_messages.0.pop_front();
+        }
+        log_messages.0.push_back(message);
+    }
+}
+
+// Make a finished voxelization the displayed one
+fn show_voxels(model_resource: &mut ModelResource, voxelized: VoxelizedModel) {
+    let normalization = voxelized.normalization;
+    let size = normalization.size;
+    let center = normalization.center;
+
+    log!(
+        "Model info: dimensions [{:.2}, {:.2}, {:.2}], max size {:.2}, center [{:.2}, {:.2}, {:.2}], scale factor {:.4}",
+        size.x, size.y, size.z, size.max_element(), center.x, center.y, center.z, normalization.scale
+    );
+
+    model_resource.voxel_count = voxelized.voxel_count();
+    model_resource.octree_node_count = voxelized.octree_node_count();
+    model_resource.voxelized = Some(voxelized);
+    model_resource.meshed = false;
+}
+
+// Convert the library's surface mesh into a Bevy render mesh
+fn to_bevy_mesh(surface: SurfaceMesh) -> Mesh {
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, surface.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, surface.normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, surface.uvs);
+    mesh.insert_indices(Indices::U32(surface.indices));
+    mesh
 }
 
 pub fn load_model_system(
@@ -18,118 +236,161 @@ pub fn load_model_system(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut model_resource: ResMut<ModelResource>,
-    voxel_settings: Res<VoxelizationSettings>,
+    voxel_settings: Res<VoxelSettings>,
+    export_settings: Res<ExportSettings>,
     model_query: Query<Entity, With<ModelMesh>>,
 ) {
-    if let Some(path) = &model_resource.path.clone() {
-        if !model_resource.loaded {
-            // Remove previous model
-            for entity in model_query.iter() {
-                commands.entity(entity).despawn_recursive();
-            }
+    let meshing = export_settings.meshing;
+    // Projects can be shown without their source model
+    let has_model = model_resource.path.is_some() || model_resource.project_voxels.is_some();
+    if has_model && !model_resource.loaded {
+        model_resource.loaded = true;
+        // A new file or new settings make a running voxelization obsolete
+        if let Some(job) = model_resource.job.take() {
+            job.progress.cancel();
+        }
+        // Nothing is shown until the new voxels are ready
+        model_resource.error = None;
+        model_resource.voxelized = None;
+        model_resource.voxel_count = 0;
+        model_resource.octree_node_count = 0;
+        model_resource.meshed = false;
 
-            // Load model
-            if let Ok(loaded_obj) = tobj::load_obj(
-                path,
-                &tobj::LoadOptions {
-                    triangulate: true,
-                    ..Default::default()
-                },
-            ) {
-                let (mut models, _materials_maybe) = loaded_obj;
-
-                // Calculate overall bounding box
-                let mut min_x = f32::MAX;
-                let mut min_y = f32::MAX;
-                let mut min_z = f32::MAX;
-                let mut max_x = f32::MIN;
-                let mut max_y = f32::MIN;
-                let mut max_z = f32::MIN;
-
-                // Process all submodels
-                for model in &models {
-                    let positions = &model.mesh.positions;
-
-                    for i in 0..positions.len() / 3 {
-                        let x = positions[i * 3];
-                        let y = positions[i * 3 + 1];
-                        let z = positions[i * 3 + 2];
-
-                        min_x = min_x.min(x);
-                        min_y = min_y.min(y);
-                        min_z = min_z.min(z);
-                        max_x = max_x.max(x);
-                        max_y = max_y.max(y);
-                        max_z = max_z.max(z);
-                    }
+        // Voxelize and mesh in the background, unless a project supplied the voxels
+        if let Some(voxelized) = model_resource.project_voxels.take() {
+            show_voxels(&mut model_resource, voxelized);
+        } else if let Some(path) = model_resource.path.clone() {
+            let progress = Arc::new(VoxelizationProgress::new());
+            let task_progress = progress.clone();
+            let task_path = path.clone();
+            let settings = **voxel_settings;
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                let hash = hash_file(&task_path).ok();
+                let voxelized = voxelize_file_with_progress(&task_path, &settings, &task_progress)?;
+                let model_meshes = ModelMeshes::build(&voxelized, meshing);
+                if task_progress.is_cancelled() {
+                    return Err(ImportError::Cancelled);
                 }
+                Ok::<_, ImportError>((voxelized, hash, model_meshes))
+            });
+            model_resource.job = Some(VoxelizationJob {
+                path,
+                progress,
+                task,
+            });
+        }
+    }
 
-                // Calculate model center
-                let center_x = (min_x + max_x) / 2.0;
-                let center_y = (min_y + max_y) / 2.0;
-                let center_z = (min_z + max_z) / 2.0;
-
-                // Calculate model dimensions
-                let size_x = max_x - min_x;
-                let size_y = max_y - min_y;
-                let size_z = max_z - min_z;
-                let max_dimension = size_x.max(size_y.max(size_z));
-
-                // Calculate scaling factor to fit model in -1~1 range
-                let world_size = COORDINATE_RANGE * 2.0 * 0.95; // Use -0.95~0.95 actual range
-                let scale_factor = if max_dimension > 0.0 {
-                    world_size / max_dimension
-                } else {
-                    1.0
-                };
-
-                println!(
-                    "Model info: dimensions [{:.2}, {:.2}, {:.2}], max size {:.2}, center [{:.2}, {:.2}, {:.2}], scale factor {:.4}",
-                    size_x, size_y, size_z, max_dimension, center_x, center_y, center_z, scale_factor
-                );
-
-                // Transform all vertices
-                for model in &mut models {
-                    let positions = &mut model.mesh.positions;
-
-                    // Center and scale each vertex
-                    for i in 0..positions.len() / 3 {
-                        let x_idx = i * 3;
-                        let y_idx = i * 3 + 1;
-                        let z_idx = i * 3 + 2;
-
-                        positions[x_idx] = (positions[x_idx] - center_x) * scale_factor;
-                        positions[y_idx] = (positions[y_idx] - center_y) * scale_factor;
-                        positions[z_idx] = (positions[z_idx] - center_z) * scale_factor;
-                    }
-                }
+    // Pick up the result of a finished voxelization
+    let mut finished_meshes = None;
+    if let Some(mut job) = model_resource.job.take() {
+        match block_on(future::poll_once(&mut job.task)) {
+            None => model_resource.job = Some(job),
+            Some(Ok((voxelized, hash, model_meshes))) => {
+                model_resource.source_hash = hash;
+                show_voxels(&mut model_resource, voxelized);
+                // Meshes made for a mesher that was changed meanwhile are rebuilt
+                model_resource.meshed = model_meshes.meshing == meshing;
+                finished_meshes = Some(model_meshes);
+                log!("Model loaded and voxelized: {}", job.path.display());
+            }
+            Some(Err(ImportError::Cancelled)) => {
+                log!("Voxelization cancelled: {}", job.path.display());
+            }
+            Some(Err(err)) => {
+                log!("Failed to load model: {}: {}", job.path.display(), err);
+                model_resource.error = Some(LoadError {
+                    path: job.path,
+                    error: err,
+                });
+            }
+        }
+    }
 
-                // Voxelize each transformed model
-                for model in models {
-                    let mesh = create_voxelized_mesh(&model, voxel_settings.octree_depth);
-                    let mesh_handle = meshes.add(mesh);
-
-                    // Create material
-                    let material_handle = materials.add(StandardMaterial {
-                        base_color: Color::srgb(0.8, 0.7, 0.6),
-                        perceptual_roughness: 0.9,
-                        ..default()
-                    });
-
-                    // Spawn model entity
-                    commands.spawn((
-                        Mesh3d(mesh_handle),
-                        MeshMaterial3d(material_handle),
-                        Transform::from_xyz(0.0, 0.0, 0.0),
-                        ModelMesh,
-                    ));
-                }
+    // Rebuild the displayed meshes in the background after loading a project or
+    // when the mesher changes. Dropping an older meshing task discards it.
+    if !model_resource.meshed {
+        model_resource.meshed = true;
+        model_resource.meshing_job = model_resource.voxelized.clone().map(|voxelized| {
+            AsyncComputeTaskPool::get()
+                .spawn(async move { ModelMeshes::build(&voxelized, meshing) })
+        });
+        // Without voxels the old meshes are removed right away
+        if model_resource.voxelized.is_none() {
+            finished_meshes = Some(ModelMeshes {
+                meshing,
+                surfaces: Vec::new(),
+                cube_face_count: 0,
+            });
+        }
+    }
+    if let Some(mut task) = model_resource.meshing_job.take() {
+        match block_on(future::poll_once(&mut task)) {
+            None => model_resource.meshing_job = Some(task),
+            Some(model_meshes) => finished_meshes = Some(model_meshes),
+        }
+    }
 
-                model_resource.loaded = true;
-                println!("Model loaded and voxelized: {}", path.display());
-            } else {
-                println!("Failed to load model: {}", path.display());
-            }
+    let Some(model_meshes) = finished_meshes else {
+        return;
+    };
+
+    // Replace the displayed meshes
+    for entity in model_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let (mut vertex_count, mut triangle_count) = (0, 0);
+    for surface in model_meshes.surfaces {
+        vertex_count += surface.vertex_count();
+        triangle_count += surface.triangle_count();
+        let mesh_handle = meshes.add(to_bevy_mesh(surface));
+
+        // Create material
+        let material_handle = materials.add(StandardMaterial {
+            base_color: Color::srgb(0.8, 0.7, 0.6),
+            perceptual_roughness: 0.9,
+            ..default()
+        });
+
+        // Spawn model entity
+        commands.spawn((
+            Mesh3d(mesh_handle),
+            MeshMaterial3d(material_handle),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            ModelMesh,
+        ));
+    }
+
+    model_resource.mesh_vertex_count = vertex_count;
+    model_resource.mesh_triangle_count = triangle_count;
+    model_resource.cube_face_count = model_meshes.cube_face_count;
+}
+
+// Report a finished signed distance field export
+pub fn sdf_export_system(mut model_resource: ResMut<ModelResource>) {
+    let Some(mut job) = model_resource.sdf_job.take() else {
+        return;
+    };
+    match block_on(future::poll_once(&mut job.task)) {
+        None => model_resource.sdf_job = Some(job),
+        Some(Ok(())) => log!("Exported {}", job.path.display()),
+        Some(Err(ImportError::Cancelled)) => log!("Export cancelled: {}", job.path.display()),
+        Some(Err(err)) => log!("Failed to export {}: {}", job.path.display(), err),
+    }
+}
+
+// Report a finished voxel export
+pub fn model_export_system(mut model_resource: ResMut<ModelResource>) {
+    let Some(mut job) = model_resource.export_job.take() else {
+        return;
+    };
+    match block_on(future::poll_once(&mut job.task)) {
+        None => model_resource.export_job = Some(job),
+        Some(Ok(())) => log!("Exported {}", job.path.display()),
+        Some(Err(_)) if job.progress.is_cancelled() => {
+            log!("Export cancelled: {}", job.path.display())
         }
+        Some(Err(err)) => log!("Failed to export {}: {}", job.path.display(), err),
     }
 }
@@ -0,0 +1,494 @@
+use std::io::{self, Read, Write};
+
+use crate::voxelization::VoxelCoord;
+
+// Octree node. Subtrees that are entirely full or entirely empty are collapsed
+// into a single node, so memory scales with the surface instead of the volume.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Empty,
+    Full,
+    Branch(Box<[Node; 8]>),
+}
+
+impl Node {
+    fn new_branch() -> Self {
+        Node::Branch(Box::new(std::array::from_fn(|_| Node::Empty)))
+    }
+}
+
+// Number of nodes of each kind on one level of the octree
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LevelNodeCount {
+    pub branch: usize,
+    pub full: usize,
+    pub empty: usize,
+}
+
+impl LevelNodeCount {
+    pub fn total(&self) -> usize {
+        self.branch + self.full + self.empty
+    }
+}
+
+// Sparse voxel octree covering a cubic grid of 2^depth voxels per axis.
+// Voxel coordinates are signed and range from -2^(depth-1) to 2^(depth-1) - 1,
+// matching the voxel coordinates used by the voxelizer. Octrees built by `insert`
+// and `merge` have the same nodes for the same voxels, whatever the order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseVoxelOctree {
+    depth: usize,
+    root: Node,
+}
+
+impl SparseVoxelOctree {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            root: Node::Empty,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Number of voxels per axis at the given level
+    fn resolution(level: usize) -> i32 {
+        1 << level
+    }
+
+    // Convert a signed voxel coordinate into the unsigned grid position, if inside the grid
+    fn to_local(&self, coord: VoxelCoord) -> Option<[u32; 3]> {
+        let offset = Self::resolution(self.depth) / 2;
+        let size = Self::resolution(self.depth);
+        let local = [coord.0 + offset, coord.1 + offset, coord.2 + offset];
+        if local.iter().all(|&c| (0..size).contains(&c)) {
+            Some(local.map(|c| c as u32))
+        } else {
+            None
+        }
+    }
+
+    // Index of the child containing `local` when `bit` levels remain below the children
+    fn child_index(local: [u32; 3], bit: usize) -> usize {
+        (((local[0] >> bit) & 1) | (((local[1] >> bit) & 1) << 1) | (((local[2] >> bit) & 1) << 2))
+            as usize
+    }
+
+    // Mark a voxel as occupied. Returns true if the voxel was newly inserted;
+    // coordinates outside the grid are ignored.
+    pub fn insert(&mut self, coord: VoxelCoord) -> bool {
+        match self.to_local(coord) {
+            Some(local) => Self::insert_node(&mut self.root, local, self.depth),
+            None => false,
+        }
+    }
+
+    fn insert_node(node: &mut Node, local: [u32; 3], remaining: usize) -> bool {
+        match node {
+            Node::Full => return false,
+            Node::Empty if remaining == 0 => {
+                *node = Node::Full;
+                return true;
+            }
+            Node::Empty => *node = Node::new_branch(),
+            Node::Branch(_) => {}
+        }
+
+        let Node::Branch(children) = node else {
+            unreachable!()
+        };
+        let child = Self::child_index(local, remaining - 1);
+        let inserted = Self::insert_node(&mut children[child], local, remaining - 1);
+
+        // Collapse the subtree once all of its children are full
+        if inserted && children.iter().all(|c| *c == Node::Full) {
+            *node = Node::Full;
+        }
+        inserted
+    }
+
+    // Add all voxels of another octree of the same depth
+    pub fn merge(&mut self, other: SparseVoxelOctree) {
+        assert_eq!(self.depth, other.depth, "merged octrees differ in depth");
+
+        fn merge_node(node: &mut Node, other: Node) {
+            match (&mut *node, other) {
+                (Node::Full, _) | (_, Node::Empty) => {}
+                (Node::Empty, other) | (_, other @ Node::Full) => *node = other,
+                (Node::Branch(children), Node::Branch(others)) => {
+                    for (child, other) in children.iter_mut().zip(*others) {
+                        merge_node(child, other);
+                    }
+                    if children.iter().all(|c| *c == Node::Full) {
+                        *node = Node::Full;
+                    }
+                }
+            }
+        }
+        merge_node(&mut self.root, other.root);
+    }
+
+    pub fn is_occupied(&self, coord: VoxelCoord) -> bool {
+        let Some(local) = self.to_local(coord) else {
+            return false;
+        };
+
+        let mut node = &self.root;
+        let mut remaining = self.depth;
+        loop {
+            match node {
+                Node::Empty => return false,
+                Node::Full => return true,
+                Node::Branch(children) => {
+                    remaining -= 1;
+                    node = &children[Self::child_index(local, remaining)];
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == Node::Empty
+    }
+
+    // Number of occupied voxels at full resolution
+    pub fn len(&self) -> usize {
+        fn count(node: &Node, remaining: usize) -> usize {
+            match node {
+                Node::Empty => 0,
+                Node::Full => 1 << (3 * remaining),
+                Node::Branch(children) => children.iter().map(|c| count(c, remaining - 1)).sum(),
+            }
+        }
+        count(&self.root, self.depth)
+    }
+
+    // Occupied cells at the given level (0 = root, `depth` = individual voxels).
+    // A cell is occupied if any voxel inside it is occupied; coordinates are signed
+    // cell coordinates on that level's grid of 2^level cells per axis. Cells are
+    // produced while walking the tree, so full subtrees are never collected.
+    pub fn iter_leaves(&self, level: usize) -> impl Iterator<Item = VoxelCoord> + '_ {
+        let level = level.min(self.depth);
+        let offset = Self::resolution(level) / 2;
+        self.occupied_nodes(level)
+            .flat_map(move |(origin, node_level)| {
+                // Expand a collapsed subtree down to the requested level
+                let span = 1u32 << (level - node_level);
+                (0..span * span * span).map(move |i| {
+                    let cell = [i % span, i / span % span, i / span / span];
+                    [0, 1, 2].map(|axis| (origin[axis] * span + cell[axis]) as i32 - offset)
+                })
+            })
+            .map(|[x, y, z]| VoxelCoord(x, y, z))
+    }
+
+    // Full nodes as boxes: the lowest voxel and the number of voxels per axis.
+    // A collapsed subtree is a single box, so solid regions can be handled without
+    // visiting each of their voxels.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = (VoxelCoord, i32)> + '_ {
+        let depth = self.depth;
+        let offset = Self::resolution(depth) / 2;
+        self.occupied_nodes(depth).map(move |(origin, level)| {
+            let size = Self::resolution(depth - level);
+            let [x, y, z] = origin.map(|c| c as i32 * size - offset);
+            (VoxelCoord(x, y, z), size)
+        })
+    }
+
+    // Full nodes down to `level` and the branches on `level`, with their origin on
+    // the grid of their own level and that level, in depth-first child order
+    fn occupied_nodes(&self, level: usize) -> OccupiedNodes<'_> {
+        OccupiedNodes {
+            level,
+            stack: vec![(&self.root, [0; 3], 0)],
+        }
+    }
+
+    // Node counts for every level from the root (index 0) down to the voxels
+    pub fn node_counts(&self) -> Vec<LevelNodeCount> {
+        fn visit(node: &Node, level: usize, counts: &mut [LevelNodeCount]) {
+            match node {
+                Node::Empty => counts[level].empty += 1,
+                Node::Full => counts[level].full += 1,
+                Node::Branch(children) => {
+                    counts[level].branch += 1;
+                    for child in children.iter() {
+                        visit(child, level + 1, counts);
+                    }
+                }
+            }
+        }
+
+        let mut counts = vec![LevelNodeCount::default(); self.depth + 1];
+        visit(&self.root, 0, &mut counts);
+        counts
+    }
+
+    // Write the node structure in pre-order, one byte per node: 0 for empty,
+    // 1 for full and 2 for a branch followed by its eight children
+    pub fn write_nodes(&self, writer: &mut impl Write) -> io::Result<()> {
+        fn write(node: &Node, writer: &mut impl Write) -> io::Result<()> {
+            match node {
+                Node::Empty => writer.write_all(&[0]),
+                Node::Full => writer.write_all(&[1]),
+                Node::Branch(children) => {
+                    writer.write_all(&[2])?;
+                    children.iter().try_for_each(|child| write(child, writer))
+                }
+            }
+        }
+        write(&self.root, writer)
+    }
+
+    // Read an octree of the given depth written by `write_nodes`
+    pub fn read_nodes(depth: usize, reader: &mut impl Read) -> io::Result<Self> {
+        fn read(reader: &mut impl Read, remaining: usize) -> io::Result<Node> {
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+            match tag[0] {
+                0 => Ok(Node::Empty),
+                1 => Ok(Node::Full),
+                2 if remaining > 0 => {
+                    let mut branch = Node::new_branch();
+                    if let Node::Branch(children) = &mut branch {
+                        for child in children.iter_mut() {
+                            *child = read(reader, remaining - 1)?;
+                        }
+                        // Collapse uniform branches, as `insert` and `merge` would
+                        for uniform in [Node::Empty, Node::Full] {
+                            if children.iter().all(|c| *c == uniform) {
+                                return Ok(uniform);
+                            }
+                        }
+                    }
+                    Ok(branch)
+                }
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid octree node",
+                )),
+            }
+        }
+
+        Ok(Self {
+            depth,
+            root: read(reader, depth)?,
+        })
+    }
+}
+
+// Iterator of `SparseVoxelOctree::occupied_nodes`
+struct OccupiedNodes<'a> {
+    level: usize,
+    // Nodes still to visit, with their origin and level
+    stack: Vec<(&'a Node, [u32; 3], usize)>,
+}
+
+impl Iterator for OccupiedNodes<'_> {
+    type Item = ([u32; 3], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, origin, node_level)) = self.stack.pop() {
+            match node {
+                Node::Empty => {}
+                Node::Full => return Some((origin, node_level)),
+                Node::Branch(_) if node_level == self.level => return Some((origin, node_level)),
+                Node::Branch(children) => {
+                    // Pushed in reverse so that the first child is visited first
+                    for (i, child) in children.iter().enumerate().rev() {
+                        let i = i as u32;
+                        let child_origin = [
+                            origin[0] * 2 + (i & 1),
+                            origin[1] * 2 + ((i >> 1) & 1),
+                            origin[2] * 2 + ((i >> 2) & 1),
+                        ];
+                        self.stack.push((child, child_origin, node_level + 1));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The 2x2x2 block of voxels from `origin`
+    fn block(origin: VoxelCoord) -> Vec<VoxelCoord> {
+        (0..8)
+            .map(|i| {
+                VoxelCoord(
+                    origin.0 + (i & 1),
+                    origin.1 + (i >> 1 & 1),
+                    origin.2 + (i >> 2),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insert_marks_voxels_once() {
+        let mut octree = SparseVoxelOctree::new(3);
+        assert!(octree.is_empty());
+        assert!(octree.insert(VoxelCoord(-4, 0, 3)));
+        assert!(!octree.insert(VoxelCoord(-4, 0, 3)));
+        assert!(octree.is_occupied(VoxelCoord(-4, 0, 3)));
+        assert!(!octree.is_occupied(VoxelCoord(-4, 0, 2)));
+
+        // Outside the grid of -4..4
+        assert!(!octree.insert(VoxelCoord(4, 0, 0)));
+        assert!(!octree.insert(VoxelCoord(0, -5, 0)));
+        assert!(!octree.is_occupied(VoxelCoord(4, 0, 0)));
+        assert_eq!(octree.len(), 1);
+    }
+
+    #[test]
+    fn full_subtrees_collapse() {
+        let mut octree = SparseVoxelOctree::new(3);
+        for voxel in block(VoxelCoord(2, 2, 2)) {
+            octree.insert(voxel);
+        }
+        assert_eq!(octree.len(), 8);
+
+        let counts = octree.node_counts();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(
+            counts[0],
+            LevelNodeCount {
+                branch: 1,
+                full: 0,
+                empty: 0
+            }
+        );
+        assert_eq!(
+            counts[1],
+            LevelNodeCount {
+                branch: 1,
+                full: 0,
+                empty: 7
+            }
+        );
+        // The block is a single full node one level above the voxels
+        assert_eq!(
+            counts[2],
+            LevelNodeCount {
+                branch: 0,
+                full: 1,
+                empty: 7
+            }
+        );
+        assert_eq!(counts[3].total(), 0);
+        assert_eq!(
+            octree.iter_nodes().collect::<Vec<_>>(),
+            [(VoxelCoord(2, 2, 2), 2)]
+        );
+
+        // Filling the whole grid collapses it into the root
+        for z in -4..4 {
+            for y in -4..4 {
+                for x in -4..4 {
+                    octree.insert(VoxelCoord(x, y, z));
+                }
+            }
+        }
+        assert_eq!(octree.len(), 512);
+        assert_eq!(octree.node_counts()[0].full, 1);
+        assert_eq!(
+            octree.iter_nodes().collect::<Vec<_>>(),
+            [(VoxelCoord(-4, -4, -4), 8)]
+        );
+    }
+
+    #[test]
+    fn merge_matches_inserting_in_any_order() {
+        let voxels: Vec<VoxelCoord> = block(VoxelCoord(-2, 0, 0))
+            .into_iter()
+            .chain([VoxelCoord(3, 3, 3), VoxelCoord(-4, 1, -1)])
+            .collect();
+
+        let mut inserted = SparseVoxelOctree::new(3);
+        for &voxel in &voxels {
+            inserted.insert(voxel);
+        }
+
+        let mut merged = SparseVoxelOctree::new(3);
+        let mut other = SparseVoxelOctree::new(3);
+        for (i, &voxel) in voxels.iter().rev().enumerate() {
+            if i % 2 == 0 {
+                merged.insert(voxel);
+            } else {
+                other.insert(voxel);
+            }
+        }
+        merged.merge(other);
+
+        assert_eq!(merged, inserted);
+        assert_eq!(merged.len(), 10);
+    }
+
+    #[test]
+    fn leaves_expand_full_nodes_to_the_requested_level() {
+        let mut octree = SparseVoxelOctree::new(3);
+        for voxel in block(VoxelCoord(0, 0, 0)) {
+            octree.insert(voxel);
+        }
+        octree.insert(VoxelCoord(-3, -3, -3));
+
+        let mut voxels: Vec<VoxelCoord> = octree.iter_leaves(3).collect();
+        voxels.sort_by_key(|v| (v.2, v.1, v.0));
+        let mut expected = block(VoxelCoord(0, 0, 0));
+        expected.insert(0, VoxelCoord(-3, -3, -3));
+        expected.sort_by_key(|v| (v.2, v.1, v.0));
+        assert_eq!(voxels, expected);
+
+        // Cells of the 4x4x4 grid one level up, and the two root children
+        let cells: Vec<VoxelCoord> = octree.iter_leaves(2).collect();
+        assert_eq!(cells, [VoxelCoord(-2, -2, -2), VoxelCoord(0, 0, 0)]);
+        let cells: Vec<VoxelCoord> = octree.iter_leaves(1).collect();
+        assert_eq!(cells, [VoxelCoord(-1, -1, -1), VoxelCoord(0, 0, 0)]);
+    }
+
+    #[test]
+    fn nodes_round_trip() {
+        let mut octree = SparseVoxelOctree::new(4);
+        for voxel in block(VoxelCoord(-8, 6, 0)) {
+            octree.insert(voxel);
+        }
+        octree.insert(VoxelCoord(7, -1, 2));
+
+        let mut bytes = Vec::new();
+        octree.write_nodes(&mut bytes).unwrap();
+        let read = SparseVoxelOctree::read_nodes(4, &mut bytes.as_slice()).unwrap();
+        assert_eq!(read, octree);
+
+        // Truncated data, and a branch below the voxel level
+        assert!(SparseVoxelOctree::read_nodes(4, &mut &bytes[..bytes.len() - 1]).is_err());
+        let error = SparseVoxelOctree::read_nodes(0, &mut [2u8].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn uniform_branches_collapse_on_read() {
+        // A root branch of eight full children, and one of eight empty children
+        // next to a full one
+        let full = SparseVoxelOctree::read_nodes(1, &mut [2, 1, 1, 1, 1, 1, 1, 1, 1].as_slice());
+        assert_eq!(
+            full.unwrap(),
+            SparseVoxelOctree::read_nodes(1, &mut [1u8].as_slice()).unwrap()
+        );
+
+        let mut bytes = vec![2, 1];
+        bytes.extend([2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend([0; 6]);
+        let read = SparseVoxelOctree::read_nodes(2, &mut bytes.as_slice()).unwrap();
+        let mut expected = SparseVoxelOctree::new(2);
+        for voxel in block(VoxelCoord(-2, -2, -2)) {
+            expected.insert(voxel);
+        }
+        assert_eq!(read, expected);
+    }
+}
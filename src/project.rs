@@ -0,0 +1,273 @@
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use glam::{Quat, Vec3};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::import::ImportError;
+use crate::mesh::Normalization;
+use crate::octree::SparseVoxelOctree;
+use crate::voxelization::{
+    FillMode, IntersectionTest, VoxelizationSettings, VoxelizedMesh, VoxelizedModel,
+    MAX_OCTREE_DEPTH,
+};
+
+// File extension of project files
+pub const PROJECT_EXTENSION: &str = "mvproj";
+
+const MAGIC: &[u8; 8] = b"MVXPROJ\0";
+// Bumped whenever the layout changes; older versions stay readable
+const VERSION: u32 = 1;
+
+// Viewer camera placement saved with a project
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraState {
+    pub position: Vec3,
+    pub rotation: Quat,
+    // Point the camera orbits around, and its distance from it
+    pub target: Vec3,
+    pub distance: f32,
+}
+
+// Everything needed to resume work without voxelizing the model again
+#[derive(Clone, Debug)]
+pub struct Project {
+    pub model: VoxelizedModel,
+    // Model file the voxels were made from
+    pub source_path: Option<PathBuf>,
+    // `hash_file` of the source when it was voxelized
+    pub source_hash: Option<u64>,
+    pub camera: Option<CameraState>,
+}
+
+// Stable 64 bit FNV-1a hash of a file's contents, to notice when a project's
+// source model has changed
+pub fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut buffer = [0; 8192];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for &byte in &buffer[..read] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+// Project file layout: magic, u32 version, then a zlib stream with the settings,
+// normalization, source reference, camera and the octree of every submodel
+pub fn save_project(project: &Project, path: &Path) -> io::Result<()> {
+    // The source path is stored as UTF-8, so it loads the same on every platform
+    let source_path = match &project.source_path {
+        Some(source) => source.to_str().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "source path {} is not valid UTF-8 and can't be stored in a project",
+                    source.display()
+                ),
+            )
+        })?,
+        None => "",
+    };
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+
+    let mut body = ZlibEncoder::new(writer, Compression::default());
+    let model = &project.model;
+
+    let settings = &model.settings;
+    write_u32(&mut body, settings.octree_depth as u32)?;
+    let test = IntersectionTest::ALL
+        .iter()
+        .position(|test| *test == settings.intersection_test);
+    let fill = FillMode::ALL
+        .iter()
+        .position(|mode| *mode == settings.fill_mode);
+    body.write_all(&[test.unwrap_or(0) as u8, fill.unwrap_or(0) as u8])?;
+
+    let normalization = &model.normalization;
+    write_vec3(&mut body, normalization.center)?;
+    write_f32(&mut body, normalization.scale)?;
+    write_vec3(&mut body, normalization.size)?;
+
+    write_string(&mut body, source_path)?;
+    match project.source_hash {
+        Some(hash) => {
+            body.write_all(&[1])?;
+            body.write_all(&hash.to_le_bytes())?;
+        }
+        None => body.write_all(&[0])?,
+    }
+
+    match &project.camera {
+        Some(camera) => {
+            body.write_all(&[1])?;
+            write_vec3(&mut body, camera.position)?;
+            for component in camera.rotation.to_array() {
+                write_f32(&mut body, component)?;
+            }
+            write_vec3(&mut body, camera.target)?;
+            write_f32(&mut body, camera.distance)?;
+        }
+        None => body.write_all(&[0])?,
+    }
+
+    write_u32(&mut body, model.meshes.len() as u32)?;
+    for mesh in &model.meshes {
+        write_string(&mut body, &mesh.name)?;
+        mesh.octree.write_nodes(&mut body)?;
+    }
+
+    body.finish()?.flush()
+}
+
+pub fn load_project(path: &Path) -> Result<Project, ImportError> {
+    let invalid = |message: &str| ImportError::Parse(format!("invalid project file: {}", message));
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a project file"));
+    }
+    let version = read_u32(&mut reader)?;
+    if version > VERSION {
+        return Err(invalid(&format!(
+            "version {} is newer than this program supports ({})",
+            version, VERSION
+        )));
+    }
+
+    let mut body = ZlibDecoder::new(reader);
+
+    let octree_depth = read_u32(&mut body)? as usize;
+    if !(1..=MAX_OCTREE_DEPTH).contains(&octree_depth) {
+        return Err(invalid("octree depth out of range"));
+    }
+    let [test, fill] = read_bytes::<2>(&mut body)?;
+    let settings = VoxelizationSettings {
+        octree_depth,
+        intersection_test: *IntersectionTest::ALL
+            .get(test as usize)
+            .ok_or_else(|| invalid("unknown surface test"))?,
+        fill_mode: *FillMode::ALL
+            .get(fill as usize)
+            .ok_or_else(|| invalid("unknown fill mode"))?,
+    };
+
+    let normalization = Normalization {
+        center: read_vec3(&mut body)?,
+        scale: read_f32(&mut body)?,
+        size: read_vec3(&mut body)?,
+    };
+
+    let source_path = Some(read_string(&mut body)?)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    let source_hash = match read_bytes::<1>(&mut body)? {
+        [0] => None,
+        _ => Some(u64::from_le_bytes(read_bytes(&mut body)?)),
+    };
+
+    let camera = match read_bytes::<1>(&mut body)? {
+        [0] => None,
+        _ => Some(CameraState {
+            position: read_vec3(&mut body)?,
+            rotation: Quat::from_array([
+                read_f32(&mut body)?,
+                read_f32(&mut body)?,
+                read_f32(&mut body)?,
+                read_f32(&mut body)?,
+            ]),
+            target: read_vec3(&mut body)?,
+            distance: read_f32(&mut body)?,
+        }),
+    };
+
+    let mesh_count = read_u32(&mut body)?;
+    let mut meshes = Vec::new();
+    for _ in 0..mesh_count {
+        meshes.push(VoxelizedMesh {
+            name: read_string(&mut body)?,
+            octree: SparseVoxelOctree::read_nodes(octree_depth, &mut body)?,
+            diagnostics: None,
+        });
+    }
+    // Reading up to the end of the stream also verifies its checksum
+    if body.read(&mut [0])? != 0 {
+        return Err(invalid("unexpected data after the last submodel"));
+    }
+
+    Ok(Project {
+        model: VoxelizedModel {
+            meshes,
+            normalization,
+            settings,
+        },
+        source_path,
+        source_hash,
+        camera,
+    })
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_vec3(writer: &mut impl Write, value: Vec3) -> io::Result<()> {
+    value
+        .to_array()
+        .iter()
+        .try_for_each(|&component| write_f32(writer, component))
+}
+
+fn write_string(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len() as u32)?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    read_bytes(reader).map(u32::from_le_bytes)
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    read_bytes(reader).map(f32::from_le_bytes)
+}
+
+fn read_vec3(reader: &mut impl Read) -> io::Result<Vec3> {
+    Ok(Vec3::new(
+        read_f32(reader)?,
+        read_f32(reader)?,
+        read_f32(reader)?,
+    ))
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, ImportError> {
+    let length = read_u32(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(ImportError::Parse(
+            "invalid project file: truncated string".to_string(),
+        ));
+    }
+    String::from_utf8(bytes)
+        .map_err(|_| ImportError::Parse("invalid project file: string is not UTF-8".to_string()))
+}
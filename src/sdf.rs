@@ -0,0 +1,414 @@
+use glam::Vec3;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::import::{is_voxel_file, load_model, ImportError};
+use crate::mesh::{normalize_meshes, Normalization, TriangleMesh};
+use crate::voxelization::{
+    VoxelCoord, VoxelGrid, VoxelizationProgress, NEIGHBOR_OFFSETS, PROGRESS_INTERVAL,
+};
+
+// Dense fields take 16 bytes per voxel while they are computed, 256 MB at depth 8
+pub const SDF_MAX_DEPTH: usize = 8;
+
+// Grid points within this many voxels of a triangle get the exact distance; the
+// rest is propagated from them. Must exceed the √3 voxel diagonal so that the sign
+// can be passed on between neighbors.
+const EXACT_BAND: f32 = 2.0;
+
+// Signed distance to the surface of all submodels, sampled at the voxel centers
+// the voxelizer tests (`VoxelGrid::cell_center`). Distances are in
+// the normalized -1~1 units, negative inside. Without any triangles every value
+// is infinite.
+#[derive(Clone, Debug)]
+pub struct SignedDistanceField {
+    depth: usize,
+    // Transform that was applied to the model before sampling
+    pub normalization: Normalization,
+    // One value per voxel, x running fastest, then y, then z
+    values: Vec<f32>,
+}
+
+impl SignedDistanceField {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Number of samples per axis
+    pub fn resolution(&self) -> usize {
+        1 << self.depth
+    }
+
+    pub fn voxel_size(&self) -> f32 {
+        self.grid().cell_size
+    }
+
+    pub fn grid(&self) -> VoxelGrid {
+        VoxelGrid::new(self.depth)
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    // Distance at a voxel, or None outside the grid
+    pub fn get(&self, coord: VoxelCoord) -> Option<f32> {
+        let half = self.resolution() as i32 / 2;
+        let local = [coord.0, coord.1, coord.2].map(|c| c + half);
+        if local.iter().all(|&c| (0..2 * half).contains(&c)) {
+            let [x, y, z] = local.map(|c| c as usize);
+            Some(self.values[(z * self.resolution() + y) * self.resolution() + x])
+        } else {
+            None
+        }
+    }
+
+    // Every voxel with its distance
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelCoord, f32)> + '_ {
+        let resolution = self.resolution();
+        let half = resolution as i32 / 2;
+        self.values.iter().enumerate().map(move |(i, &value)| {
+            let [x, y, z] = [
+                i % resolution,
+                i / resolution % resolution,
+                i / resolution / resolution,
+            ]
+            .map(|c| c as i32 - half);
+            (VoxelCoord(x, y, z), value)
+        })
+    }
+
+    // Normalized position of the sample of a voxel
+    pub fn sample_position(&self, coord: VoxelCoord) -> Vec3 {
+        self.grid().cell_center(coord)
+    }
+}
+
+// Load a model file, normalize it like `voxelize_file` and compute its signed
+// distance field. Voxel files have no triangles and are rejected, as are depths
+// outside 1..=`SDF_MAX_DEPTH`.
+pub fn sdf_file(path: &Path, depth: usize) -> Result<SignedDistanceField, ImportError> {
+    sdf_file_with_progress(path, depth, &VoxelizationProgress::new())
+}
+
+// Compute the signed distance field of a model file like `sdf_file`, reporting the
+// triangles processed to `progress` and stopping early with `ImportError::Cancelled`
+// when it is cancelled. All triangles are done before the distances are propagated.
+pub fn sdf_file_with_progress(
+    path: &Path,
+    depth: usize,
+    progress: &VoxelizationProgress,
+) -> Result<SignedDistanceField, ImportError> {
+    if !(1..=SDF_MAX_DEPTH).contains(&depth) {
+        return Err(ImportError::InvalidSdfDepth(depth));
+    }
+    if is_voxel_file(path) {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        return Err(ImportError::UnsupportedFormat(format!(
+            "{} (signed distance fields need a triangle mesh)",
+            extension
+        )));
+    }
+
+    let mut meshes = load_model(path)?;
+    let normalization = normalize_meshes(&mut meshes);
+    compute_sdf_with_progress(&meshes, normalization, depth, progress).ok_or(ImportError::Cancelled)
+}
+
+// Compute the signed distance field of already normalized meshes on the voxel grid
+// of the given depth, which must not exceed `SDF_MAX_DEPTH`.
+//
+// Samples near the surface get the exact distance to the nearest triangle, with the
+// sign taken from the angle weighted pseudo normal of the nearest feature, so the
+// meshes should be closed. The remaining samples inherit the nearest surface point
+// from their neighbors in order of distance, which gives nearly exact distances.
+pub fn compute_sdf(
+    meshes: &[TriangleMesh],
+    normalization: Normalization,
+    depth: usize,
+) -> SignedDistanceField {
+    let progress = VoxelizationProgress::new();
+    compute_sdf_with_progress(meshes, normalization, depth, &progress)
+        .expect("computation was not cancelled")
+}
+
+// Compute the field like `compute_sdf`, or return None as soon as `progress` is cancelled
+fn compute_sdf_with_progress(
+    meshes: &[TriangleMesh],
+    normalization: Normalization,
+    depth: usize,
+    progress: &VoxelizationProgress,
+) -> Option<SignedDistanceField> {
+    assert!(
+        (1..=SDF_MAX_DEPTH).contains(&depth),
+        "signed distance field depth {} out of range",
+        depth
+    );
+
+    let resolution = 1usize << depth;
+    let grid = VoxelGrid::new(depth);
+    let voxel_size = grid.cell_size;
+    let half = (resolution / 2) as i32;
+    let index = |[x, y, z]: [usize; 3]| (z * resolution + y) * resolution + x;
+    let position = |local: [usize; 3]| {
+        let [x, y, z] = local.map(|c| c as i32 - half);
+        grid.cell_center(VoxelCoord(x, y, z))
+    };
+
+    let surface = PseudoNormalMesh::new(meshes);
+    progress.set_total(surface.triangles.len());
+    let mut values = vec![f32::INFINITY; resolution * resolution * resolution];
+    let mut nearest = vec![Vec3::ZERO; values.len()];
+
+    // Exact distances within the band around every triangle
+    let band = EXACT_BAND * voxel_size;
+    for batch in surface.triangles.chunks(PROGRESS_INTERVAL) {
+        if progress.is_cancelled() {
+            return None;
+        }
+        for triangle in batch {
+            let [a, b, c] = triangle.corners;
+            let (min, max) = (a.min(b).min(c), a.max(b).max(c));
+            // Samples within the band around the bounding box, empty outside the grid.
+            // Sample i sits at grid coordinate i - half + 0.5.
+            let offset = Vec3::splat(half as f32 - 0.5);
+            let first = grid.to_grid(min - Vec3::splat(band)) + offset;
+            let last = grid.to_grid(max + Vec3::splat(band)) + offset;
+            let range = |first: f32, last: f32| {
+                first.ceil().max(0.0) as usize
+                    ..(last.floor() + 1.0).clamp(0.0, resolution as f32) as usize
+            };
+
+            for z in range(first.z, last.z) {
+                for y in range(first.y, last.y) {
+                    for x in range(first.x, last.x) {
+                        let point = position([x, y, z]);
+                        let (closest, feature) = closest_point_on_triangle(point, triangle.corners);
+                        let distance = point.distance(closest);
+                        let i = index([x, y, z]);
+                        if distance <= band && distance < values[i].abs() {
+                            let normal = surface.pseudo_normal(triangle, feature);
+                            let sign = if (point - closest).dot(normal) < 0.0 {
+                                -1.0
+                            } else {
+                                1.0
+                            };
+                            values[i] = sign * distance;
+                            nearest[i] = closest;
+                        }
+                    }
+                }
+            }
+        }
+        progress.add_done(batch.len());
+    }
+
+    // Propagate the nearest surface points outward, roughly closest samples first.
+    // Samples wait in buckets of half a voxel of distance; a sample that improves
+    // later is queued again, so the order only affects the speed.
+    let bucket = |distance: f32| (distance / voxel_size * 2.0) as usize;
+    let mut buckets: Vec<Vec<u32>> = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        if value.is_finite() {
+            enqueue(&mut buckets, bucket(value.abs()), i);
+        }
+    }
+
+    let mut k = 0;
+    let mut steps = 0usize;
+    while k < buckets.len() {
+        // Checking on every step would slow down the propagation
+        steps += 1;
+        if steps.is_multiple_of(PROGRESS_INTERVAL * PROGRESS_INTERVAL) && progress.is_cancelled() {
+            return None;
+        }
+        let Some(i) = buckets[k].pop() else {
+            k += 1;
+            continue;
+        };
+        let i = i as usize;
+        let local = [
+            i % resolution,
+            i / resolution % resolution,
+            i / resolution / resolution,
+        ];
+        let sign = values[i].signum();
+        let closest = nearest[i];
+
+        for offset in NEIGHBOR_OFFSETS {
+            let Some(neighbor) = offset_local(local, offset, resolution) else {
+                continue;
+            };
+            let n = index(neighbor);
+            let candidate = position(neighbor).distance(closest);
+            if candidate < values[n].abs() {
+                values[n] = sign * candidate;
+                nearest[n] = closest;
+                enqueue(&mut buckets, bucket(candidate).max(k), n);
+            }
+        }
+    }
+
+    Some(SignedDistanceField {
+        depth,
+        normalization,
+        values,
+    })
+}
+
+// Part of a triangle that a closest point lies on, by corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Feature {
+    Face,
+    Edge(usize, usize),
+    Vertex(usize),
+}
+
+struct SurfaceTriangle {
+    corners: [Vec3; 3],
+    // Welded vertex ids, shared by triangles that meet at a corner
+    vertices: [u32; 3],
+    normal: Vec3,
+}
+
+// Triangles with the angle weighted pseudo normals of their edges and vertices,
+// which tell inside from outside at any closest point of a closed mesh
+// (Bærentzen and Aanæs)
+struct PseudoNormalMesh {
+    triangles: Vec<SurfaceTriangle>,
+    vertex_normals: Vec<Vec3>,
+    edge_normals: HashMap<(u32, u32), Vec3>,
+}
+
+impl PseudoNormalMesh {
+    fn new(meshes: &[TriangleMesh]) -> Self {
+        let mut triangles = Vec::new();
+        let mut vertex_normals = Vec::new();
+        let mut edge_normals: HashMap<(u32, u32), Vec3> = HashMap::new();
+
+        for mesh in meshes {
+            // Weld corners of this mesh by position; many formats repeat them per face
+            let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+            for corners in mesh.triangles() {
+                // Zero area triangles have no normal and no effect on the distance
+                let [a, b, c] = corners;
+                let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+                    continue;
+                };
+
+                let vertices = corners.map(|corner| {
+                    // Adding zero turns -0.0 into 0.0, so both weld together
+                    let key = (corner + Vec3::ZERO).to_array().map(f32::to_bits);
+                    *welded.entry(key).or_insert_with(|| {
+                        vertex_normals.push(Vec3::ZERO);
+                        vertex_normals.len() as u32 - 1
+                    })
+                });
+
+                for k in 0..3 {
+                    let (prev, next) = (corners[(k + 2) % 3], corners[(k + 1) % 3]);
+                    let angle = (next - corners[k]).angle_between(prev - corners[k]);
+                    vertex_normals[vertices[k] as usize] += angle * normal;
+                    *edge_normals
+                        .entry(edge_key(vertices[k], vertices[(k + 1) % 3]))
+                        .or_default() += normal;
+                }
+
+                triangles.push(SurfaceTriangle {
+                    corners,
+                    vertices,
+                    normal,
+                });
+            }
+        }
+
+        Self {
+            triangles,
+            vertex_normals,
+            edge_normals,
+        }
+    }
+
+    fn pseudo_normal(&self, triangle: &SurfaceTriangle, feature: Feature) -> Vec3 {
+        match feature {
+            Feature::Face => triangle.normal,
+            Feature::Edge(i, j) => {
+                self.edge_normals[&edge_key(triangle.vertices[i], triangle.vertices[j])]
+            }
+            Feature::Vertex(i) => self.vertex_normals[triangle.vertices[i] as usize],
+        }
+    }
+}
+
+fn enqueue(buckets: &mut Vec<Vec<u32>>, bucket: usize, sample: usize) {
+    if bucket >= buckets.len() {
+        buckets.resize_with(bucket + 1, Vec::new);
+    }
+    buckets[bucket].push(sample as u32);
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+// Closest point on a triangle and the feature it lies on (Ericson, Real-Time
+// Collision Detection, 5.1.5)
+fn closest_point_on_triangle(point: Vec3, [a, b, c]: [Vec3; 3]) -> (Vec3, Feature) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, Feature::Vertex(0));
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, Feature::Vertex(1));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return (a + v * ab, Feature::Edge(0, 1));
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, Feature::Vertex(2));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return (a + w * ac, Feature::Edge(0, 2));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (b + w * (c - b), Feature::Edge(1, 2));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+    (a + ab * v + ac * w, Feature::Face)
+}
+
+fn offset_local(local: [usize; 3], offset: [i32; 3], resolution: usize) -> Option<[usize; 3]> {
+    let mut result = [0; 3];
+    for k in 0..3 {
+        let c = local[k] as i64 + offset[k] as i64;
+        if !(0..resolution as i64).contains(&c) {
+            return None;
+        }
+        result[k] = c as usize;
+    }
+    Some(result)
+}
@@ -1,25 +1,160 @@
-use crate::model::ModelResource;
-use crate::voxelization::VoxelizationSettings;
+use crate::camera::CameraController;
+use crate::model::{
+    ExportJob, ExportSettings, LoadError, LogMessages, ModelResource, SdfExportJob, VoxelSettings,
+};
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
+use model_voxelization::export::{
+    BlockPalette, ExportFormat, ExportOptions, ExportProgress, MeshUnits, SdfFormat, SliceAxis,
+    VdbGrid,
+};
+use model_voxelization::import::{is_voxel_file, ImportError, SUPPORTED_EXTENSIONS};
+use model_voxelization::mesh::MeshDiagnostics;
+use model_voxelization::meshing::MeshingMode;
+use model_voxelization::project::{
+    hash_file, load_project, save_project, CameraState, Project, PROJECT_EXTENSION,
+};
+use model_voxelization::sdf::SDF_MAX_DEPTH;
+use model_voxelization::voxelization::{
+    FillMode, IntersectionTest, VoxelizationProgress, MAX_OCTREE_DEPTH,
+};
+use std::path::Path;
 
 pub fn ui_system(
     mut contexts: EguiContexts,
     mut model_resource: ResMut<ModelResource>,
-    mut voxel_settings: ResMut<VoxelizationSettings>,
+    mut voxel_settings: ResMut<VoxelSettings>,
+    mut export_settings: ResMut<ExportSettings>,
+    mut camera_query: Query<(&mut Transform, &mut CameraController), With<Camera3d>>,
+    mut log_messages: ResMut<LogMessages>,
+    mut show_log: Local<bool>,
 ) {
     egui::TopBottomPanel::top("top_panel").show(contexts.ctx_mut(), |ui| {
         egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
                 if ui.button("Open").clicked() {
                     if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("3D Models", &["obj", "stl", "fbx"])
+                        .add_filter("3D Models", SUPPORTED_EXTENSIONS)
                         .pick_file()
                     {
                         model_resource.path = Some(path);
+                        model_resource.project_path = None;
                         model_resource.loaded = false;
                     }
                 }
+                if ui.button("Open Project").clicked() {
+                    open_project_dialog(
+                        &mut model_resource,
+                        &mut voxel_settings,
+                        &mut camera_query,
+                    );
+                    ui.close_menu();
+                }
+                let save_button = egui::Button::new("Save Project");
+                if ui
+                    .add_enabled(model_resource.voxelized.is_some(), save_button)
+                    .clicked()
+                {
+                    save_project_dialog(&model_resource, &camera_query);
+                    ui.close_menu();
+                }
+                ui.separator();
+                ui.menu_button("Export", |ui| {
+                    // One export at a time, like signed distance fields
+                    let enabled =
+                        model_resource.voxelized.is_some() && model_resource.export_job.is_none();
+                    for format in ExportFormat::ALL {
+                        let button = egui::Button::new(format!(
+                            "{} (.{})",
+                            format.label(),
+                            format.extension()
+                        ));
+                        let response = ui
+                            .add_enabled(enabled, button)
+                            .on_disabled_hover_text("Needs a voxelized model and no running export");
+                        let response = match format {
+                            ExportFormat::Gltf | ExportFormat::Glb => response.on_hover_text(
+                                "Vertex colors are one color per submodel, not the source model's textures or colors",
+                            ),
+                            _ => response,
+                        };
+                        if response.clicked() {
+                            export_dialog(&mut model_resource, format, &export_settings);
+                            ui.close_menu();
+                        }
+                    }
+
+                    ui.separator();
+                    ui.label("Units");
+                    for units in MeshUnits::ALL {
+                        ui.radio_value(&mut export_settings.mesh_units, units, units.label());
+                    }
+
+                    ui.separator();
+                    ui.label("Minecraft blocks");
+                    let single = matches!(export_settings.block_palette, BlockPalette::Single(_));
+                    if ui.radio(single, "Single block").clicked() && !single {
+                        export_settings.block_palette = BlockPalette::default();
+                    }
+                    if ui
+                        .radio(!single, "Nearest concrete per submodel")
+                        .on_hover_text("Each submodel becomes the concrete block closest to its submodel color")
+                        .clicked()
+                        && single
+                    {
+                        export_settings.block_palette = BlockPalette::concrete();
+                    }
+                    if let BlockPalette::Single(block) = &mut export_settings.block_palette {
+                        ui.text_edit_singleline(block);
+                    }
+
+                    ui.separator();
+                    ui.label("OpenVDB grid");
+                    for grid in VdbGrid::ALL {
+                        ui.radio_value(&mut export_settings.vdb_grid, grid, grid.label());
+                    }
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("PNG slice axis");
+                        for axis in SliceAxis::ALL {
+                            ui.radio_value(&mut export_settings.slice_axis, axis, axis.label());
+                        }
+                    });
+                });
+                ui.menu_button("Export SDF", |ui| {
+                    // The field is computed from the source triangles on the current grid
+                    let depth = model_resource
+                        .voxelized
+                        .as_ref()
+                        .map(|voxelized| voxelized.settings.octree_depth);
+                    let has_mesh = model_resource
+                        .path
+                        .as_ref()
+                        .is_some_and(|path| !is_voxel_file(path));
+                    // One field at a time; each takes a lot of memory
+                    let enabled = has_mesh
+                        && depth.is_some_and(|depth| depth <= SDF_MAX_DEPTH)
+                        && model_resource.sdf_job.is_none();
+                    for format in SdfFormat::ALL {
+                        let button = egui::Button::new(format!(
+                            "{} (.{})",
+                            format.label(),
+                            format.extension()
+                        ));
+                        if ui
+                            .add_enabled(enabled, button)
+                            .on_disabled_hover_text(format!(
+                                "Needs the source triangle mesh of a voxelization at depth {} or less, and no running export",
+                                SDF_MAX_DEPTH
+                            ))
+                            .clicked()
+                        {
+                            export_sdf_dialog(&mut model_resource, format, &export_settings);
+                            ui.close_menu();
+                        }
+                    }
+                });
                 ui.separator();
                 if ui.button("Exit").clicked() {
                     std::process::exit(0);
@@ -30,7 +165,7 @@ pub fn ui_system(
                 // Octree depth slider
                 let mut depth = voxel_settings.octree_depth as i32;
                 ui.add(
-                    egui::Slider::new(&mut depth, 1..=10)
+                    egui::Slider::new(&mut depth, 1..=MAX_OCTREE_DEPTH as i32)
                         .text("Octree Depth")
                         .integer(),
                 );
@@ -39,37 +174,228 @@ pub fn ui_system(
                 let old_depth = voxel_settings.octree_depth;
                 voxel_settings.octree_depth = depth as usize;
 
-                if old_depth != voxel_settings.octree_depth {
-                    if model_resource.path.is_some() {
-                        model_resource.loaded = false;
-                    }
+                if old_depth != voxel_settings.octree_depth && model_resource.path.is_some() {
+                    model_resource.loaded = false;
                 }
 
                 // Display current voxel size
                 ui.label(format!("Voxel size: {:.6}", voxel_settings.voxel_size()));
+
+                ui.separator();
+
+                // Triangle/voxel intersection test
+                ui.label("Surface test");
+                let old_test = voxel_settings.intersection_test;
+                for test in IntersectionTest::ALL {
+                    ui.radio_value(&mut voxel_settings.intersection_test, test, test.label());
+                }
+
+                if old_test != voxel_settings.intersection_test && model_resource.path.is_some() {
+                    model_resource.loaded = false;
+                }
+
+                ui.separator();
+
+                // Surface shell or filled interior
+                ui.label("Fill mode");
+                let old_fill_mode = voxel_settings.fill_mode;
+                for mode in FillMode::ALL {
+                    ui.radio_value(&mut voxel_settings.fill_mode, mode, mode.label());
+                }
+
+                if old_fill_mode != voxel_settings.fill_mode && model_resource.path.is_some() {
+                    model_resource.loaded = false;
+                }
+
+                ui.separator();
+
+                // Mesher of the viewer and the mesh exports; only needs new meshes
+                ui.label("Mesher");
+                let old_meshing = export_settings.meshing;
+                for mode in MeshingMode::ALL {
+                    ui.radio_value(&mut export_settings.meshing, mode, mode.label());
+                }
+
+                if old_meshing != export_settings.meshing {
+                    model_resource.meshed = false;
+                }
+            });
+
+            ui.menu_button("View", |ui| {
+                ui.checkbox(&mut show_log, "Log");
             });
         });
     });
 
-    // Handle model info window or help screen
-    if let Some(path) = &model_resource.path {
+    // Progress of background voxelization and exports
+    if let Some(job) = &model_resource.job {
+        progress_window(
+            contexts.ctx_mut(),
+            "Voxelizing",
+            &job.path,
+            &job.progress,
+            "Building meshes",
+        );
+    }
+    if let Some(job) = &model_resource.sdf_job {
+        progress_window(
+            contexts.ctx_mut(),
+            "Exporting SDF",
+            &job.path,
+            &job.progress,
+            "Propagating distances",
+        );
+    }
+    if let Some(job) = &model_resource.export_job {
+        export_progress_window(contexts.ctx_mut(), &job.path, &job.progress);
+    }
+
+    // Why the last model or project could not be loaded
+    if let Some(LoadError { path, error }) = &model_resource.error {
+        // Only a failed model load can be retried; projects are picked again
+        let can_retry = model_resource.path.as_ref() == Some(path);
+        let (mut retry, mut close) = (false, false);
+        egui::Window::new("Loading Failed")
+            .collapsible(false)
+            .resizable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.colored_label(ui.visuals().error_fg_color, error_title(error));
+                ui.label(path.display().to_string());
+                ui.label(error.to_string());
+                ui.horizontal(|ui| {
+                    if can_retry {
+                        retry = ui.button("Retry").clicked();
+                    }
+                    close = ui.button("Close").clicked();
+                });
+            });
+
+        if retry {
+            model_resource.loaded = false;
+        } else if close {
+            model_resource.error = None;
+            // Without voxels there is nothing left to show but the help screen
+            if model_resource.voxelized.is_none() {
+                model_resource.path = None;
+                model_resource.project_path = None;
+            }
+        }
+    }
+
+    // Messages printed by the app and the voxelization
+    if *show_log {
+        egui::Window::new("Log")
+            .open(&mut show_log)
+            .default_size([480.0, 240.0])
+            .show(contexts.ctx_mut(), |ui| {
+                if ui.button("Clear").clicked() {
+                    log_messages.0.clear();
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for message in &log_messages.0 {
+                            ui.monospace(message);
+                        }
+                    });
+            });
+    }
+
+    // Handle model info window or help screen; a project without a source model
+    // shows the project file
+    let shown_path = model_resource
+        .path
+        .as_ref()
+        .or(model_resource.project_path.as_ref());
+    if let Some(path) = shown_path {
         // Create a local clone of the path to avoid borrowing model_resource inside the closure
         let path_display = path.display().to_string();
-        let octree_depth = voxel_settings.octree_depth;
-        let voxel_size = voxel_settings.voxel_size();
+        // Only a model file can be voxelized again
+        let has_source = model_resource.path.is_some();
+        // Voxel files keep their own depth, so prefer the settings of the loaded voxelization
+        let settings = model_resource
+            .voxelized
+            .as_ref()
+            .map_or(**voxel_settings, |voxelized| voxelized.settings);
+        let octree_depth = settings.octree_depth;
+        let voxel_size = settings.voxel_size();
+        let voxel_count = model_resource.voxel_count;
+        let octree_node_count = model_resource.octree_node_count;
+        let mesh_vertex_count = model_resource.mesh_vertex_count;
+        let mesh_triangle_count = model_resource.mesh_triangle_count;
+        let cube_face_count = model_resource.cube_face_count;
+        // Problems in the source triangles of each submodel, when they were checked
+        let mesh_checks: Vec<(String, MeshDiagnostics)> = model_resource
+            .voxelized
+            .iter()
+            .flat_map(|voxelized| &voxelized.meshes)
+            .filter_map(|mesh| Some((mesh.name.clone(), mesh.diagnostics?)))
+            .collect();
 
         // Track if we need to reload the model
         let mut should_reload = false;
 
-        // Show model info window when model is loaded
-        egui::Window::new("Model Info").show(contexts.ctx_mut(), |ui| {
-            ui.label(format!("Loaded model: {}", path_display));
-            ui.label(format!("Octree depth: {}", octree_depth));
-            ui.label(format!("Voxel size: {:.6}", voxel_size));
-            if ui.button("Reload").clicked() {
-                should_reload = true;
-            }
-        });
+        // Show model info window when model is loaded; a failed load shows its error instead
+        if model_resource.error.is_none() {
+            egui::Window::new("Model Info").show(contexts.ctx_mut(), |ui| {
+                if has_source {
+                    ui.label(format!("Loaded model: {}", path_display));
+                } else {
+                    ui.label(format!("Project: {} (no source model)", path_display));
+                }
+                ui.label(format!("Octree depth: {}", octree_depth));
+                ui.label(format!("Voxel size: {:.6}", voxel_size));
+                ui.label(format!("Voxels: {}", voxel_count));
+                ui.label(format!("Octree nodes: {}", octree_node_count));
+                ui.label(format!(
+                    "Mesh: {} triangles, {} vertices",
+                    mesh_triangle_count, mesh_vertex_count
+                ));
+                ui.label(format!(
+                    "Cube mesher: {} faces, {} vertices",
+                    cube_face_count,
+                    cube_face_count * 4
+                ));
+                if !mesh_checks.is_empty() {
+                    let problems: Vec<&(String, MeshDiagnostics)> = mesh_checks
+                        .iter()
+                        .filter(|(_, diagnostics)| *diagnostics != MeshDiagnostics::default())
+                        .collect();
+                    if problems.is_empty() {
+                        ui.label("Mesh check: no problems found");
+                    } else {
+                        let skipped: usize = problems
+                            .iter()
+                            .map(|(_, diagnostics)| diagnostics.skipped_triangles())
+                            .sum();
+                        let header = format!("Mesh check: {} triangles skipped", skipped);
+                        egui::CollapsingHeader::new(header).show(ui, |ui| {
+                            for (name, diagnostics) in problems {
+                                let color = if diagnostics.has_errors() {
+                                    ui.visuals().warn_fg_color
+                                } else {
+                                    ui.visuals().text_color()
+                                };
+                                ui.colored_label(color, format!("{}:", name));
+                                for message in diagnostics.messages() {
+                                    ui.label(format!("  {}", message));
+                                }
+                            }
+                        });
+                    }
+                }
+                let reload = egui::Button::new("Reload");
+                if ui
+                    .add_enabled(has_source, reload)
+                    .on_disabled_hover_text("The project has no source model to voxelize again")
+                    .clicked()
+                {
+                    should_reload = true;
+                }
+            });
+        }
 
         // Apply reload flag after the closure is done
         if should_reload {
@@ -95,8 +421,12 @@ pub fn ui_system(
                 );
                 ui.add_space(10.0);
 
+                let formats: Vec<String> = SUPPORTED_EXTENSIONS
+                    .iter()
+                    .map(|ext| format!(".{}", ext))
+                    .collect();
                 ui.label(
-                    egui::RichText::new("Supported formats: .obj, .stl, .fbx")
+                    egui::RichText::new(format!("Supported formats: {}", formats.join(", ")))
                         .text_style(egui::TextStyle::Body)
                         .size(16.0),
                 );
@@ -104,10 +434,11 @@ pub fn ui_system(
 
                 if ui.button("Open Model...").clicked() {
                     if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("3D Models", &["obj", "stl", "fbx"])
+                        .add_filter("3D Models", SUPPORTED_EXTENSIONS)
                         .pick_file()
                     {
                         model_resource.path = Some(path);
+                        model_resource.project_path = None;
                         model_resource.loaded = false;
                     }
                 }
@@ -147,3 +478,245 @@ pub fn ui_system(
         });
     }
 }
+
+// Window with the triangles a background job has processed and a button to cancel
+// it; `finishing` describes the work after the last triangle
+fn progress_window(
+    ctx: &egui::Context,
+    title: &str,
+    path: &Path,
+    progress: &VoxelizationProgress,
+    finishing: &str,
+) {
+    let (done, total) = progress.triangles();
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(file_name);
+            if total == 0 || done == total {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(if total == 0 {
+                        "Reading model file"
+                    } else {
+                        finishing
+                    });
+                });
+            } else {
+                let bar = egui::ProgressBar::new(done as f32 / total as f32)
+                    .text(format!("{} / {} triangles", done, total));
+                ui.add(bar);
+            }
+            if ui.button("Cancel").clicked() {
+                progress.cancel();
+            }
+        });
+}
+
+// Window with the bytes a background export has written and a button to cancel it
+fn export_progress_window(ctx: &egui::Context, path: &Path, progress: &ExportProgress) {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    egui::Window::new("Exporting")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(file_name);
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!(
+                    "{:.1} MB written",
+                    progress.bytes_written() as f64 / 1e6
+                ));
+            });
+            if ui.button("Cancel").clicked() {
+                progress.cancel();
+            }
+        });
+}
+
+// Short explanation of a load failure, above the error's details
+fn error_title(error: &ImportError) -> &'static str {
+    match error {
+        ImportError::Io(_) => "The file could not be read",
+        ImportError::Parse(_) => "The file is damaged or not in the expected format",
+        ImportError::UnsupportedFormat(_) => "This file format is not supported",
+        ImportError::EmptyModel => "The model has no triangles to voxelize",
+        ImportError::GridTooLarge(_) => "The voxel grid is too large",
+        ImportError::InvalidSdfDepth(_) => "The signed distance field resolution is not supported",
+        ImportError::Cancelled => "Loading was cancelled",
+    }
+}
+
+// Ask for a file name and export the current voxelization in the background
+fn export_dialog(
+    model_resource: &mut ModelResource,
+    format: ExportFormat,
+    options: &ExportOptions,
+) {
+    let Some(voxelized) = &model_resource.voxelized else {
+        return;
+    };
+
+    let file_name = model_resource
+        .path
+        .as_ref()
+        .or(model_resource.project_path.as_ref())
+        .and_then(|path| path.file_stem())
+        .map(|stem| format!("{}.{}", stem.to_string_lossy(), format.extension()))
+        .unwrap_or_else(|| format!("voxels.{}", format.extension()));
+
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter(format.label(), &[format.extension()])
+        .set_file_name(file_name)
+        .save_file()
+    {
+        model_resource.export_job = Some(ExportJob::spawn(
+            voxelized.clone(),
+            path,
+            format,
+            options.clone(),
+        ));
+    }
+}
+
+// Ask for a file name, then compute and export the signed distance field of the
+// current model on its voxel grid in the background
+fn export_sdf_dialog(
+    model_resource: &mut ModelResource,
+    format: SdfFormat,
+    options: &ExportOptions,
+) {
+    let (Some(source), Some(voxelized)) = (&model_resource.path, &model_resource.voxelized) else {
+        return;
+    };
+
+    let file_name = source
+        .file_stem()
+        .map(|stem| format!("{}_sdf.{}", stem.to_string_lossy(), format.extension()))
+        .unwrap_or_else(|| format!("sdf.{}", format.extension()));
+
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter(format.label(), &[format.extension()])
+        .set_file_name(file_name)
+        .save_file()
+    {
+        model_resource.sdf_job = Some(SdfExportJob::spawn(
+            source.clone(),
+            path,
+            voxelized.settings.octree_depth,
+            format,
+            options.clone(),
+        ));
+    }
+}
+
+// Ask for a file name and save the current voxelization with the camera as a project
+fn save_project_dialog(
+    model_resource: &ModelResource,
+    camera_query: &Query<(&mut Transform, &mut CameraController), With<Camera3d>>,
+) {
+    let Some(voxelized) = &model_resource.voxelized else {
+        return;
+    };
+
+    let file_name = model_resource
+        .path
+        .as_ref()
+        .or(model_resource.project_path.as_ref())
+        .and_then(|path| path.file_stem())
+        .map(|stem| format!("{}.{}", stem.to_string_lossy(), PROJECT_EXTENSION))
+        .unwrap_or_else(|| format!("project.{}", PROJECT_EXTENSION));
+
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter("Project", &[PROJECT_EXTENSION])
+        .set_file_name(file_name)
+        .save_file()
+    {
+        let camera = camera_query
+            .get_single()
+            .ok()
+            .map(|(transform, controller)| CameraState {
+                position: transform.translation,
+                rotation: transform.rotation,
+                target: controller.target,
+                distance: controller.distance,
+            });
+        let project = Project {
+            model: voxelized.clone(),
+            source_path: model_resource.path.clone(),
+            source_hash: model_resource.source_hash,
+            camera,
+        };
+
+        match save_project(&project, &path) {
+            Ok(()) => log!("Saved project {}", path.display()),
+            Err(err) => log!("Failed to save project {}: {}", path.display(), err),
+        }
+    }
+}
+
+// Ask for a project file and show its voxels, settings and camera
+fn open_project_dialog(
+    model_resource: &mut ModelResource,
+    voxel_settings: &mut VoxelSettings,
+    camera_query: &mut Query<(&mut Transform, &mut CameraController), With<Camera3d>>,
+) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Project", &[PROJECT_EXTENSION])
+        .pick_file()
+    else {
+        return;
+    };
+
+    let project = match load_project(&path) {
+        Ok(project) => project,
+        Err(err) => {
+            log!("Failed to open project {}: {}", path.display(), err);
+            model_resource.error = Some(LoadError { path, error: err });
+            return;
+        }
+    };
+
+    // The voxels are kept either way, but reloading needs the unchanged source model
+    if let Some(source) = &project.source_path {
+        match hash_file(source) {
+            Ok(hash) if Some(hash) != project.source_hash => log!(
+                "Warning: {} changed since the project was saved",
+                source.display()
+            ),
+            Err(err) => log!(
+                "Warning: cannot read source model {}: {}",
+                source.display(),
+                err
+            ),
+            Ok(_) => {}
+        }
+    }
+
+    if let (Some(camera), Ok((mut transform, mut controller))) =
+        (project.camera, camera_query.get_single_mut())
+    {
+        transform.translation = camera.position;
+        transform.rotation = camera.rotation;
+        controller.target = camera.target;
+        controller.distance = camera.distance;
+    }
+
+    **voxel_settings = project.model.settings;
+    model_resource.path = project.source_path;
+    model_resource.project_path = Some(path.clone());
+    model_resource.source_hash = project.source_hash;
+    model_resource.project_voxels = Some(project.model);
+    model_resource.loaded = false;
+    log!("Opened project {}", path.display());
+}
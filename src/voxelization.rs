@@ -1,230 +1,436 @@
-use bevy::{
-    prelude::*,
-    render::{
-        mesh::{Indices, PrimitiveTopology},
-        render_asset::RenderAssetUsages,
-    },
-};
-use std::collections::HashSet;
+use glam::{IVec3, Vec3};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use crate::import::{is_voxel_file, load_model, load_voxels, ImportError};
+use crate::log::record;
+use crate::mesh::{normalize_meshes, MeshDiagnostics, Normalization, TriangleMesh};
+use crate::octree::SparseVoxelOctree;
+
+// How a triangle is tested against a voxel during surface voxelization
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntersectionTest {
+    // Separating axis triangle/box overlap test
+    #[default]
+    Exact,
+    // Distance from the voxel center to the triangle plane (fast, over-fills slanted triangles)
+    Approximate,
+}
+
+impl IntersectionTest {
+    pub const ALL: [IntersectionTest; 2] = [IntersectionTest::Exact, IntersectionTest::Approximate];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IntersectionTest::Exact => "Exact (SAT)",
+            IntersectionTest::Approximate => "Fast (approximate)",
+        }
+    }
+}
+
+// Whether only the surface shell or also the enclosed interior is voxelized
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillMode {
+    #[default]
+    Surface,
+    // Fill voxels enclosed by the surface shell
+    Solid,
+}
+
+impl FillMode {
+    pub const ALL: [FillMode; 2] = [FillMode::Surface, FillMode::Solid];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FillMode::Surface => "Surface (shell)",
+            FillMode::Solid => "Solid (filled)",
+        }
+    }
+}
 
-#[derive(Resource)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct VoxelizationSettings {
     pub octree_depth: usize,
+    pub intersection_test: IntersectionTest,
+    pub fill_mode: FillMode,
 }
 
 impl Default for VoxelizationSettings {
     fn default() -> Self {
         Self {
             octree_depth: 6, // Default octree depth
+            intersection_test: IntersectionTest::default(),
+            fill_mode: FillMode::default(),
         }
     }
 }
 
 impl VoxelizationSettings {
     pub fn voxel_size(&self) -> f32 {
-        // Calculate voxel size from octree depth (range -1~1, width=2)
-        2.0 / (1 << self.octree_depth) as f32
+        self.grid().cell_size
+    }
+
+    pub fn grid(&self) -> VoxelGrid {
+        VoxelGrid::new(self.octree_depth)
     }
 }
 
-// Voxel coordinate for HashSet
-#[derive(Hash, Eq, PartialEq, Clone, Copy)]
-struct VoxelCoord(i32, i32, i32);
+// Integer voxel coordinate
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct VoxelCoord(pub i32, pub i32, pub i32);
 
 // Coordinate range from -1 to 1
 pub const COORDINATE_RANGE: f32 = 1.0;
 
-pub fn create_voxelized_mesh(model: &tobj::Model, octree_depth: usize) -> Mesh {
-    // Calculate voxel size
-    let voxel_size = 2.0 / (1 << octree_depth) as f32;
+// Deepest octree that can be voxelized or loaded, 1024 voxels per axis
+pub const MAX_OCTREE_DEPTH: usize = 10;
 
-    let positions = &model.mesh.positions;
-    let indices = &model.mesh.indices;
+// Fewest triangles worth starting another voxelization thread for
+const MIN_TRIANGLES_PER_THREAD: usize = 4096;
 
-    // Create voxel grid using HashSet for efficient lookups
-    let mut filled_voxels = HashSet::new();
+// Triangles a voxelization thread rasterizes between progress updates
+pub(crate) const PROGRESS_INTERVAL: usize = 1024;
 
-    // Process all triangles for voxelization
-    for i in 0..indices.len() / 3 {
-        let idx1 = indices[i * 3] as usize;
-        let idx2 = indices[i * 3 + 1] as usize;
-        let idx3 = indices[i * 3 + 2] as usize;
+// Offsets of the 26 voxels around a voxel, x changing fastest
+pub(crate) const NEIGHBOR_OFFSETS: [[i32; 3]; 26] = {
+    let mut offsets = [[0; 3]; 26];
+    let mut i = 0;
+    let mut n = 0;
+    while n < 27 {
+        if n != 13 {
+            offsets[i] = [n % 3 - 1, n / 3 % 3 - 1, n / 9 - 1];
+            i += 1;
+        }
+        n += 1;
+    }
+    offsets
+};
 
-        let p1 = Vec3::new(
-            positions[idx1 * 3],
-            positions[idx1 * 3 + 1],
-            positions[idx1 * 3 + 2],
-        );
-        let p2 = Vec3::new(
-            positions[idx2 * 3],
-            positions[idx2 * 3 + 1],
-            positions[idx2 * 3 + 2],
-        );
-        let p3 = Vec3::new(
-            positions[idx3 * 3],
-            positions[idx3 * 3 + 1],
-            positions[idx3 * 3 + 2],
-        );
+// Progress of a voxelization or signed distance field computation, shared with
+// other threads to watch or cancel it
+#[derive(Debug, Default)]
+pub struct VoxelizationProgress {
+    triangles_done: AtomicUsize,
+    triangles_total: AtomicUsize,
+    cancelled: AtomicBool,
+}
 
-        voxelize_triangle(p1, p2, p3, voxel_size, &mut filled_voxels);
+impl VoxelizationProgress {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    println!(
-        "Voxelization complete: depth {}, voxel size {:.6}, generated {} voxels",
-        octree_depth,
-        voxel_size,
-        filled_voxels.len()
-    );
+    // Triangles processed so far and in total. The total is 0 while the model file
+    // is still being read.
+    pub fn triangles(&self) -> (usize, usize) {
+        (
+            self.triangles_done.load(Ordering::Relaxed),
+            self.triangles_total.load(Ordering::Relaxed),
+        )
+    }
 
-    // Create voxelized mesh
-    let mut mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::RENDER_WORLD,
-    );
-    let cube_size = voxel_size;
-
-    let mut vertices = Vec::new();
-    let mut normals = Vec::new();
-    let mut uvs = Vec::new();
-    let mut mesh_indices = Vec::new();
-
-    // Render visible voxel faces
-    for voxel_coord in &filled_voxels {
-        // Calculate voxel center position
-        let voxel_center = Vec3::new(
-            voxel_coord.0 as f32 * voxel_size,
-            voxel_coord.1 as f32 * voxel_size,
-            voxel_coord.2 as f32 * voxel_size,
-        );
+    // Ask the voxelization to stop; it returns `ImportError::Cancelled` shortly after
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
 
-        // Check which faces are visible (no adjacent voxels)
-        let neighbors = [
-            VoxelCoord(voxel_coord.0 + 1, voxel_coord.1, voxel_coord.2), // right
-            VoxelCoord(voxel_coord.0 - 1, voxel_coord.1, voxel_coord.2), // left
-            VoxelCoord(voxel_coord.0, voxel_coord.1 + 1, voxel_coord.2), // top
-            VoxelCoord(voxel_coord.0, voxel_coord.1 - 1, voxel_coord.2), // bottom
-            VoxelCoord(voxel_coord.0, voxel_coord.1, voxel_coord.2 + 1), // front
-            VoxelCoord(voxel_coord.0, voxel_coord.1, voxel_coord.2 - 1), // back
-        ];
-
-        let half = cube_size / 2.0;
-
-        // Define the 8 corners of the cube
-        let corners = [
-            voxel_center + Vec3::new(-half, -half, -half), // 0: back bottom left
-            voxel_center + Vec3::new(half, -half, -half),  // 1: back bottom right
-            voxel_center + Vec3::new(half, -half, half),   // 2: front bottom right
-            voxel_center + Vec3::new(-half, -half, half),  // 3: front bottom left
-            voxel_center + Vec3::new(-half, half, -half),  // 4: back top left
-            voxel_center + Vec3::new(half, half, -half),   // 5: back top right
-            voxel_center + Vec3::new(half, half, half),    // 6: front top right
-            voxel_center + Vec3::new(-half, half, half),   // 7: front top left
-        ];
-
-        // Only add geometry for visible faces
-        for (i, neighbor) in neighbors.iter().enumerate() {
-            if !filled_voxels.contains(neighbor) {
-                match i {
-                    0 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [1, 2, 6, 5],
-                        [1.0, 0.0, 0.0],
-                    ), // right face
-                    1 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [0, 4, 7, 3],
-                        [-1.0, 0.0, 0.0],
-                    ), // left face
-                    2 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [4, 5, 6, 7],
-                        [0.0, 1.0, 0.0],
-                    ), // top face
-                    3 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [0, 3, 2, 1],
-                        [0.0, -1.0, 0.0],
-                    ), // bottom face
-                    4 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [3, 7, 6, 2],
-                        [0.0, 0.0, 1.0],
-                    ), // front face
-                    5 => add_cube_face(
-                        &mut vertices,
-                        &mut normals,
-                        &mut uvs,
-                        &mut mesh_indices,
-                        &corners,
-                        [0, 1, 5, 4],
-                        [0.0, 0.0, -1.0],
-                    ), // back face
-                    _ => {}
-                }
-            }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_total(&self, triangles: usize) {
+        self.triangles_total.store(triangles, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_done(&self, triangles: usize) {
+        self.triangles_done.fetch_add(triangles, Ordering::Relaxed);
+    }
+}
+
+// Placement of the voxels in normalized model space. Voxel (x, y, z) is the cell
+// from origin + (x, y, z) * cell_size to origin + (x + 1, y + 1, z + 1) * cell_size,
+// so in grid coordinates voxel x spans x..x + 1 and is centered at x + 0.5. The
+// voxelizer, the meshers and every export use this one convention.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelGrid {
+    // Lowest corner of voxel (0, 0, 0)
+    pub origin: Vec3,
+    pub cell_size: f32,
+}
+
+impl VoxelGrid {
+    // Grid of an octree of the given depth: 2^depth voxels per axis covering the
+    // -1~1 range, from voxel -2^(depth-1) to 2^(depth-1) - 1
+    pub fn new(depth: usize) -> Self {
+        Self {
+            origin: Vec3::ZERO,
+            cell_size: 2.0 * COORDINATE_RANGE / (1 << depth) as f32,
         }
     }
 
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.insert_indices(Indices::U32(mesh_indices));
+    // Model space position of a point in grid coordinates
+    pub fn to_world(&self, point: Vec3) -> Vec3 {
+        self.origin + point * self.cell_size
+    }
 
-    mesh
+    // Grid coordinates of a model space position
+    pub fn to_grid(&self, position: Vec3) -> Vec3 {
+        (position - self.origin) / self.cell_size
+    }
+
+    // Lowest corner of a voxel
+    pub fn cell_min(&self, coord: VoxelCoord) -> Vec3 {
+        self.to_world(IVec3::new(coord.0, coord.1, coord.2).as_vec3())
+    }
+
+    pub fn cell_center(&self, coord: VoxelCoord) -> Vec3 {
+        self.cell_min(coord) + Vec3::splat(0.5 * self.cell_size)
+    }
+
+    // Voxel containing a model space position; positions on a boundary between two
+    // voxels belong to the upper one
+    pub fn cell_at(&self, position: Vec3) -> VoxelCoord {
+        let cell = self.to_grid(position).floor().as_ivec3();
+        VoxelCoord(cell.x, cell.y, cell.z)
+    }
 }
 
-// Add a face to the cube
-fn add_cube_face(
-    vertices: &mut Vec<[f32; 3]>,
-    normals: &mut Vec<[f32; 3]>,
-    uvs: &mut Vec<[f32; 2]>,
-    indices: &mut Vec<u32>,
-    corners: &[Vec3; 8],
-    face_indices: [usize; 4],
-    normal: [f32; 3],
-) {
-    let start_idx = vertices.len() as u32;
+// Voxelization of one submodel
+#[derive(Clone, Debug)]
+pub struct VoxelizedMesh {
+    pub name: String,
+    pub octree: SparseVoxelOctree,
+    // Problems in the source triangles, if the voxels were made from triangles in
+    // this session (not loaded from a voxel or project file)
+    pub diagnostics: Option<MeshDiagnostics>,
+}
+
+// Voxelization of all submodels of a model, together with the transform that
+// mapped the model into the -1~1 range
+#[derive(Clone, Debug)]
+pub struct VoxelizedModel {
+    pub meshes: Vec<VoxelizedMesh>,
+    pub normalization: Normalization,
+    pub settings: VoxelizationSettings,
+}
+
+impl VoxelizedModel {
+    pub fn voxel_count(&self) -> usize {
+        self.meshes.iter().map(|mesh| mesh.octree.len()).sum()
+    }
+
+    pub fn octree_node_count(&self) -> usize {
+        self.meshes
+            .iter()
+            .flat_map(|mesh| mesh.octree.node_counts())
+            .map(|counts| counts.total())
+            .sum()
+    }
+
+    // Problems in the source triangles of all submodels, if any were checked
+    pub fn diagnostics(&self) -> Option<MeshDiagnostics> {
+        self.meshes
+            .iter()
+            .filter_map(|mesh| mesh.diagnostics)
+            .reduce(|mut total, diagnostics| {
+                total += diagnostics;
+                total
+            })
+    }
+}
+
+// Load a model file, normalize it into the -1~1 range and voxelize every submodel.
+// Voxel files are loaded as they are, with the depth stored in the file. Models
+// without a single usable triangle are an `ImportError::EmptyModel`.
+pub fn voxelize_file(
+    path: &Path,
+    settings: &VoxelizationSettings,
+) -> Result<VoxelizedModel, ImportError> {
+    voxelize_file_with_progress(path, settings, &VoxelizationProgress::new())
+}
+
+// Voxelize a model file like `voxelize_file`, reporting the triangles processed to
+// `progress` and stopping early with `ImportError::Cancelled` when it is cancelled
+pub fn voxelize_file_with_progress(
+    path: &Path,
+    settings: &VoxelizationSettings,
+    progress: &VoxelizationProgress,
+) -> Result<VoxelizedModel, ImportError> {
+    if is_voxel_file(path) {
+        return load_voxels(path);
+    }
+    if settings.octree_depth > MAX_OCTREE_DEPTH {
+        return Err(ImportError::GridTooLarge(1 << settings.octree_depth));
+    }
 
-    // Add four vertices
-    for &idx in &face_indices {
-        vertices.push([corners[idx].x, corners[idx].y, corners[idx].z]);
-        normals.push(normal);
+    let mut meshes = load_model(path)?;
+    let normalization = normalize_meshes(&mut meshes);
+    // Checked after scaling, since triangles too small for a normal in the file's
+    // units may be usable once normalized
+    if meshes.iter().all(|mesh| mesh.triangles().next().is_none()) {
+        return Err(ImportError::EmptyModel);
     }
+    let total = meshes.iter().map(TriangleMesh::triangle_count).sum();
+    progress.set_total(total);
+
+    voxelize_meshes_with_progress(&meshes, normalization, settings, progress)
+        .ok_or(ImportError::Cancelled)
+}
 
-    // Add UV coordinates
-    uvs.push([0.0, 0.0]);
-    uvs.push([1.0, 0.0]);
-    uvs.push([1.0, 1.0]);
-    uvs.push([0.0, 1.0]);
+// Voxelize already normalized meshes, checking each for malformed triangles
+pub fn voxelize_meshes(
+    meshes: &[TriangleMesh],
+    normalization: Normalization,
+    settings: &VoxelizationSettings,
+) -> VoxelizedModel {
+    let progress = VoxelizationProgress::new();
+    voxelize_meshes_with_progress(meshes, normalization, settings, &progress)
+        .expect("voxelization was not cancelled")
+}
 
-    // Add two triangles
-    indices.push(start_idx);
-    indices.push(start_idx + 2);
-    indices.push(start_idx + 1);
+fn voxelize_meshes_with_progress(
+    meshes: &[TriangleMesh],
+    normalization: Normalization,
+    settings: &VoxelizationSettings,
+    progress: &VoxelizationProgress,
+) -> Option<VoxelizedModel> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut voxelized_meshes = Vec::new();
+    for mesh in meshes {
+        let diagnostics = mesh.diagnose();
+        if diagnostics.has_errors() {
+            record!("Warning: problems in mesh {}:", mesh.name);
+            for message in diagnostics.messages() {
+                record!("  {}", message);
+            }
+        }
+        voxelized_meshes.push(VoxelizedMesh {
+            name: mesh.name.clone(),
+            octree: rasterize_mesh(mesh, settings, threads, progress)?,
+            diagnostics: Some(diagnostics),
+        });
+    }
+
+    Some(VoxelizedModel {
+        meshes: voxelized_meshes,
+        normalization,
+        settings: *settings,
+    })
+}
+
+// Voxelize a triangle mesh into a sparse voxel octree of depth `settings.octree_depth`,
+// using every available core. The mesh is expected to be normalized into the -1~1
+// coordinate range. Malformed triangles are skipped, see `TriangleMesh::diagnose`.
+pub fn voxelize_mesh(mesh: &TriangleMesh, settings: &VoxelizationSettings) -> SparseVoxelOctree {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    voxelize_mesh_with_threads(mesh, settings, threads)
+}
 
-    indices.push(start_idx);
-    indices.push(start_idx + 3);
-    indices.push(start_idx + 2);
+// Voxelize a triangle mesh like `voxelize_mesh` on at most `threads` threads. Each
+// thread rasterizes a contiguous share of the triangles into its own octree and the
+// octrees are merged at the end, so the result is the same for any thread count.
+pub fn voxelize_mesh_with_threads(
+    mesh: &TriangleMesh,
+    settings: &VoxelizationSettings,
+    threads: usize,
+) -> SparseVoxelOctree {
+    let progress = VoxelizationProgress::new();
+    rasterize_mesh(mesh, settings, threads, &progress).expect("voxelization was not cancelled")
+}
+
+// Voxelize one mesh, or return None as soon as `progress` is cancelled
+fn rasterize_mesh(
+    mesh: &TriangleMesh,
+    settings: &VoxelizationSettings,
+    threads: usize,
+    progress: &VoxelizationProgress,
+) -> Option<SparseVoxelOctree> {
+    let octree_depth = settings.octree_depth;
+    let grid = settings.grid();
+    let start = Instant::now();
+
+    let triangles: Vec<[Vec3; 3]> = mesh.triangles().collect();
+    // Skipped triangles count as processed
+    progress.add_done(mesh.triangle_count() - triangles.len());
+    let rasterize = |triangles: &[[Vec3; 3]]| {
+        let mut octree = SparseVoxelOctree::new(octree_depth);
+        for batch in triangles.chunks(PROGRESS_INTERVAL) {
+            if progress.is_cancelled() {
+                return None;
+            }
+            for &[p1, p2, p3] in batch {
+                voxelize_triangle(p1, p2, p3, &grid, settings.intersection_test, &mut octree);
+            }
+            progress.add_done(batch.len());
+        }
+        Some(octree)
+    };
+
+    let threads = threads
+        .min(triangles.len() / MIN_TRIANGLES_PER_THREAD)
+        .max(1);
+    let mut octree = if threads == 1 {
+        rasterize(&triangles)?
+    } else {
+        let chunk_size = triangles.len().div_ceil(threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> = triangles
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || rasterize(chunk)))
+                .collect();
+
+            let mut octree = SparseVoxelOctree::new(octree_depth);
+            for worker in workers {
+                let part = worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                octree.merge(part?);
+            }
+            Some(octree)
+        })?
+    };
+    record!(
+        "Rasterized {} triangles on {} thread(s) in {:.2?}",
+        triangles.len(),
+        threads,
+        start.elapsed()
+    );
+
+    if progress.is_cancelled() {
+        return None;
+    }
+    if settings.fill_mode == FillMode::Solid {
+        let surface_count = octree.len();
+        fill_interior(&mut octree, progress)?;
+        record!(
+            "Interior fill: {} surface voxels, {} interior voxels",
+            surface_count,
+            octree.len() - surface_count
+        );
+    }
+
+    let node_counts = octree.node_counts();
+    record!(
+        "Voxelization complete: depth {}, voxel size {:.6}, generated {} voxels, {} octree nodes",
+        octree_depth,
+        grid.cell_size,
+        octree.len(),
+        node_counts.iter().map(|c| c.total()).sum::<usize>()
+    );
+    for (level, counts) in node_counts.iter().enumerate() {
+        record!(
+            "  level {}: {} branch, {} full, {} empty",
+            level,
+            counts.branch,
+            counts.full,
+            counts.empty
+        );
+    }
+
+    Some(octree)
 }
 
 // Triangle voxelization using voxel grid
@@ -232,30 +438,28 @@ fn voxelize_triangle(
     p1: Vec3,
     p2: Vec3,
     p3: Vec3,
-    voxel_size: f32,
-    filled_voxels: &mut HashSet<VoxelCoord>,
+    grid: &VoxelGrid,
+    intersection_test: IntersectionTest,
+    octree: &mut SparseVoxelOctree,
 ) {
-    // Calculate triangle bounding box
-    let bb_min_x = p1.x.min(p2.x.min(p3.x));
-    let bb_min_y = p1.y.min(p2.y.min(p3.y));
-    let bb_min_z = p1.z.min(p2.z.min(p3.z));
+    let voxel_size = grid.cell_size;
 
-    let bb_max_x = p1.x.max(p2.x.max(p3.x));
-    let bb_max_y = p1.y.max(p2.y.max(p3.y));
-    let bb_max_z = p1.z.max(p2.z.max(p3.z));
+    // Calculate triangle bounding box in grid coordinates
+    let bb_min = grid.to_grid(p1.min(p2).min(p3));
+    let bb_max = grid.to_grid(p1.max(p2).max(p3));
 
     // Convert to voxel coordinates
-    let min_voxel_x = (bb_min_x / voxel_size).floor() as i32;
-    let min_voxel_y = (bb_min_y / voxel_size).floor() as i32;
-    let min_voxel_z = (bb_min_z / voxel_size).floor() as i32;
+    let min_voxel_x = bb_min.x.floor() as i32;
+    let min_voxel_y = bb_min.y.floor() as i32;
+    let min_voxel_z = bb_min.z.floor() as i32;
 
-    let max_voxel_x = (bb_max_x / voxel_size).ceil() as i32;
-    let max_voxel_y = (bb_max_y / voxel_size).ceil() as i32;
-    let max_voxel_z = (bb_max_z / voxel_size).ceil() as i32;
+    let max_voxel_x = bb_max.x.ceil() as i32;
+    let max_voxel_y = bb_max.y.ceil() as i32;
+    let max_voxel_z = bb_max.z.ceil() as i32;
 
-    // Clamp coordinates to valid range
-    let max_idx = (COORDINATE_RANGE / voxel_size) as i32;
-    let min_idx = -max_idx;
+    // Clamp coordinates to the octree grid
+    let max_idx = (COORDINATE_RANGE / voxel_size) as i32 - 1;
+    let min_idx = -max_idx - 1;
 
     let min_voxel_x = min_voxel_x.max(min_idx);
     let min_voxel_y = min_voxel_y.max(min_idx);
@@ -265,7 +469,7 @@ fn voxelize_triangle(
     let max_voxel_y = max_voxel_y.min(max_idx);
     let max_voxel_z = max_voxel_z.min(max_idx);
 
-    // Calculate triangle normal
+    // Calculate triangle normal; `TriangleMesh::triangles` skips zero area triangles
     let edge1 = p2 - p1;
     let edge2 = p3 - p1;
     let normal = edge1.cross(edge2).normalize();
@@ -274,21 +478,368 @@ fn voxelize_triangle(
     for x in min_voxel_x..=max_voxel_x {
         for y in min_voxel_y..=max_voxel_y {
             for z in min_voxel_z..=max_voxel_z {
-                let voxel_center = Vec3::new(
-                    (x as f32 + 0.5) * voxel_size,
-                    (y as f32 + 0.5) * voxel_size,
-                    (z as f32 + 0.5) * voxel_size,
-                );
+                let voxel = VoxelCoord(x, y, z);
+                let voxel_center = grid.cell_center(voxel);
 
                 // Check if voxel intersects with triangle
-                // Simplified method: check if distance from voxel center to triangle plane is less than voxel radius
-                let dist_to_plane = (voxel_center - p1).dot(normal).abs();
+                let intersects = match intersection_test {
+                    IntersectionTest::Exact => {
+                        triangle_box_overlap(voxel_center, voxel_size / 2.0, p1, p2, p3)
+                    }
+                    IntersectionTest::Approximate => {
+                        // Simplified method: check if distance from voxel center to triangle plane is less than voxel radius
+                        let dist_to_plane = (voxel_center - p1).dot(normal).abs();
+                        dist_to_plane <= voxel_size * 0.87 // sqrt(3)/2 ≈ 0.87
+                    }
+                };
+
+                if intersects {
+                    octree.insert(voxel);
+                }
+            }
+        }
+    }
+}
+
+// Fill all voxels enclosed by the surface voxels, or return None as soon as
+// `progress` is cancelled. The surface voxels split every row of voxels along x
+// into empty runs. Runs before the first or after the last surface voxel of a row
+// are outside, and so is any run next to an outside run or a row without surface
+// voxels in one of the four neighboring rows. The remaining runs are interior.
+// Only rows with surface voxels are stored, so memory scales with the surface.
+fn fill_interior(octree: &mut SparseVoxelOctree, progress: &VoxelizationProgress) -> Option<()> {
+    // Empty runs between the surface voxels of a row, as inclusive x ranges in
+    // increasing order, and whether each has been found to be outside
+    struct Row {
+        first: i32,
+        last: i32,
+        runs: Vec<(i32, i32)>,
+        outside: Vec<bool>,
+    }
+
+    impl Row {
+        // Whether the x range touches this row's empty space before the first or
+        // after the last surface voxel
+        fn touches_ends(&self, (x0, x1): (i32, i32)) -> bool {
+            x0 < self.first || x1 > self.last
+        }
+
+        // Indices of the runs overlapping the x range
+        fn overlapping(&self, (x0, x1): (i32, i32)) -> impl Iterator<Item = usize> + '_ {
+            let start = self.runs.partition_point(|&(_, end)| end < x0);
+            (start..self.runs.len()).take_while(move |&i| self.runs[i].0 <= x1)
+        }
+    }
+
+    let mut surface_rows: HashMap<(i32, i32), Vec<i32>> = HashMap::new();
+    for voxel in octree.iter_leaves(octree.depth()) {
+        surface_rows
+            .entry((voxel.1, voxel.2))
+            .or_default()
+            .push(voxel.0);
+    }
+    let mut rows: HashMap<(i32, i32), Row> = surface_rows
+        .into_iter()
+        .map(|(key, mut xs)| {
+            xs.sort_unstable();
+            let runs: Vec<(i32, i32)> = xs
+                .windows(2)
+                .filter(|pair| pair[1] > pair[0] + 1)
+                .map(|pair| (pair[0] + 1, pair[1] - 1))
+                .collect();
+            let row = Row {
+                first: xs[0],
+                last: xs[xs.len() - 1],
+                outside: vec![false; runs.len()],
+                runs,
+            };
+            (key, row)
+        })
+        .collect();
+
+    let neighbors = |(y, z): (i32, i32)| [(y - 1, z), (y + 1, z), (y, z - 1), (y, z + 1)];
+
+    // Runs that touch outside space directly
+    let mut queue = Vec::new();
+    for (&key, row) in &rows {
+        for (i, &run) in row.runs.iter().enumerate() {
+            let outside = neighbors(key)
+                .iter()
+                .any(|neighbor| match rows.get(neighbor) {
+                    Some(neighbor) => neighbor.touches_ends(run),
+                    None => true,
+                });
+            if outside {
+                queue.push((key, i));
+            }
+        }
+    }
+    for &(key, i) in &queue {
+        rows.get_mut(&key).unwrap().outside[i] = true;
+    }
+
+    // Spread to the runs connected to them
+    let mut visited = 0usize;
+    let mut reached = Vec::new();
+    while let Some((key, i)) = queue.pop() {
+        visited += 1;
+        if visited.is_multiple_of(PROGRESS_INTERVAL) && progress.is_cancelled() {
+            return None;
+        }
+        let run = rows[&key].runs[i];
+        for neighbor in neighbors(key) {
+            if let Some(row) = rows.get(&neighbor) {
+                reached.extend(
+                    row.overlapping(run)
+                        .filter(|&j| !row.outside[j])
+                        .map(|j| (neighbor, j)),
+                );
+            }
+        }
+        for (neighbor, j) in reached.drain(..) {
+            rows.get_mut(&neighbor).unwrap().outside[j] = true;
+            queue.push((neighbor, j));
+        }
+    }
+
+    // Whatever was not reached is enclosed by the surface
+    for (row_index, ((y, z), row)) in rows.iter().enumerate() {
+        if row_index.is_multiple_of(PROGRESS_INTERVAL) && progress.is_cancelled() {
+            return None;
+        }
+        for (&(x0, x1), &outside) in row.runs.iter().zip(&row.outside) {
+            if !outside {
+                for x in x0..=x1 {
+                    octree.insert(VoxelCoord(x, *y, *z));
+                }
+            }
+        }
+    }
+
+    Some(())
+}
+
+// Triangle/axis-aligned box overlap test based on the separating axis theorem
+// (Akenine-Möller). The box is a cube given by its center and half edge length.
+fn triangle_box_overlap(box_center: Vec3, half_size: f32, p1: Vec3, p2: Vec3, p3: Vec3) -> bool {
+    // Move the triangle so the box is centered at the origin
+    let v0 = p1 - box_center;
+    let v1 = p2 - box_center;
+    let v2 = p3 - box_center;
+
+    // Box face normals: compare the triangle's bounding box against the box
+    let tri_min = v0.min(v1).min(v2);
+    let tri_max = v0.max(v1).max(v2);
+    if tri_min.cmpgt(Vec3::splat(half_size)).any() || tri_max.cmplt(Vec3::splat(-half_size)).any() {
+        return false;
+    }
+
+    let edges = [v1 - v0, v2 - v1, v0 - v2];
+
+    // Triangle normal: the plane must pass through the box
+    let normal = edges[0].cross(edges[1]);
+    let plane_dist = normal.dot(v0);
+    let plane_radius = half_size * normal.abs().element_sum();
+    if plane_dist.abs() > plane_radius {
+        return false;
+    }
+
+    // Cross products of the box axes with the triangle edges
+    for edge in edges {
+        for box_axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let axis = box_axis.cross(edge);
+            let d0 = axis.dot(v0);
+            let d1 = axis.dot(v1);
+            let d2 = axis.dot(v2);
+            let radius = half_size * axis.abs().element_sum();
+            if d0.min(d1).min(d2) > radius || d0.max(d1).max(d2) < -radius {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit box at the origin
+    fn overlaps(p1: [f32; 3], p2: [f32; 3], p3: [f32; 3]) -> bool {
+        triangle_box_overlap(Vec3::ZERO, 0.5, p1.into(), p2.into(), p3.into())
+    }
+
+    #[test]
+    fn slanted_triangles_near_the_box_do_not_overlap() {
+        // The plane x + z = 1.1 is 0.78 from the center, within the 0.87 the
+        // approximate test accepts, but misses the box
+        assert!(!overlaps(
+            [-3.0, -3.0, 4.1],
+            [4.1, -3.0, -3.0],
+            [0.55, 3.0, 0.55]
+        ));
+        // The plane z = x passes through the center, but the triangle only covers
+        // x + y >= 1.3 within it
+        assert!(!overlaps(
+            [-0.4, 1.7, -0.4],
+            [1.7, -0.4, 1.7],
+            [1.7, 1.7, 1.7]
+        ));
+        // The same triangle moved to x + y >= 0.7 cuts the box's corner
+        assert!(overlaps(
+            [-0.7, 1.4, -0.7],
+            [1.4, -0.7, 1.4],
+            [1.4, 1.4, 1.4]
+        ));
+    }
+
+    #[test]
+    fn edge_contact_overlaps() {
+        // An edge through the box with all vertices outside
+        assert!(overlaps([-2.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 3.0, 3.0]));
+        // An edge lying on a box edge, the rest of the triangle outside
+        assert!(overlaps([-2.0, 0.5, 0.5], [2.0, 0.5, 0.5], [0.0, 2.0, 2.0]));
+        assert!(!overlaps(
+            [-2.0, 0.51, 0.5],
+            [2.0, 0.51, 0.5],
+            [0.0, 2.0, 2.0]
+        ));
+    }
+
+    #[test]
+    fn vertex_contact_overlaps() {
+        // A vertex on the box corner, the rest of the triangle outside
+        assert!(overlaps([0.5, 0.5, 0.5], [2.0, 0.5, 1.0], [0.5, 2.0, 1.0]));
+        assert!(!overlaps(
+            [0.51, 0.51, 0.51],
+            [2.0, 0.51, 1.0],
+            [0.51, 2.0, 1.0]
+        ));
+    }
+
+    #[test]
+    fn degenerate_triangles_overlap_like_their_segment() {
+        // Collinear vertices on a segment through the box, and on one beside it
+        assert!(overlaps([-1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0]));
+        assert!(!overlaps(
+            [-1.0, 2.2, 0.0],
+            [2.2, -1.0, 0.0],
+            [0.6, 0.6, 0.0]
+        ));
+        // All vertices at one point
+        assert!(overlaps([0.1, 0.2, 0.3], [0.1, 0.2, 0.3], [0.1, 0.2, 0.3]));
+        assert!(!overlaps([0.6, 0.2, 0.3], [0.6, 0.2, 0.3], [0.6, 0.2, 0.3]));
+    }
 
-                if dist_to_plane <= voxel_size * 0.87 {
-                    // sqrt(3)/2 ≈ 0.87
-                    filled_voxels.insert(VoxelCoord(x, y, z));
+    // Cube from -half to half with outward facing triangles, without the faces
+    // whose normal axis and sign are listed in `open`
+    fn cube(half: f32, open: &[(usize, f32)]) -> TriangleMesh {
+        let mut triangles = Vec::new();
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for sign in [-1.0, 1.0] {
+                if open.contains(&(axis, sign)) {
+                    continue;
+                }
+                let corner = |a: f32, b: f32| {
+                    let mut corner = Vec3::splat(0.0);
+                    corner[axis] = sign * half;
+                    corner[u] = a * half;
+                    corner[v] = b * half;
+                    corner
+                };
+                let [p00, p10, p11, p01] =
+                    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(a, b)| corner(a, b));
+                if sign > 0.0 {
+                    triangles.extend([[p00, p10, p11], [p00, p11, p01]]);
+                } else {
+                    triangles.extend([[p00, p11, p10], [p00, p01, p11]]);
+                }
+            }
+        }
+        TriangleMesh::from_triangles("cube", triangles)
+    }
+
+    fn solid(depth: usize) -> VoxelizationSettings {
+        VoxelizationSettings {
+            octree_depth: depth,
+            fill_mode: FillMode::Solid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn closed_cube_fills_completely() {
+        // At depth 4 the voxels are 0.125 wide, so -0.45~0.45 touches voxels -4..4
+        let octree = voxelize_mesh_with_threads(&cube(0.45, &[]), &solid(4), 1);
+        assert_eq!(octree.len(), 8 * 8 * 8);
+        // One full 4x4x4 node in each child of the root
+        assert!(octree.iter_nodes().all(|(_, size)| size == 4));
+    }
+
+    #[test]
+    fn hollow_sphere_is_filled() {
+        // Voxels whose centers are 5 to 6 voxels from the center form a shell
+        // without face-connected gaps
+        let center_distance = |x: i32, y: i32, z: i32| {
+            (Vec3::new(x as f32, y as f32, z as f32) + Vec3::splat(0.5)).length()
+        };
+        let mut octree = SparseVoxelOctree::new(4);
+        let mut inside = 0;
+        for z in -8..8 {
+            for y in -8..8 {
+                for x in -8..8 {
+                    let distance = center_distance(x, y, z);
+                    if (5.0..6.0).contains(&distance) {
+                        octree.insert(VoxelCoord(x, y, z));
+                    } else if distance < 5.0 {
+                        inside += 1;
+                    }
                 }
             }
         }
+        let shell = octree.len();
+        assert!(inside > 0);
+
+        fill_interior(&mut octree, &VoxelizationProgress::new()).unwrap();
+        assert_eq!(octree.len(), shell + inside);
+        for z in -8..8 {
+            for y in -8..8 {
+                for x in -8..8 {
+                    let distance = center_distance(x, y, z);
+                    assert_eq!(octree.is_occupied(VoxelCoord(x, y, z)), distance < 6.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn open_meshes_are_not_filled() {
+        let surface = VoxelizationSettings {
+            fill_mode: FillMode::Surface,
+            ..solid(4)
+        };
+        for open in [(0, 1.0), (1, -1.0), (2, 1.0)] {
+            let mesh = cube(0.45, &[open]);
+            let hollow = voxelize_mesh_with_threads(&mesh, &surface, 1);
+            let filled = voxelize_mesh_with_threads(&mesh, &solid(4), 1);
+            assert_eq!(filled, hollow, "open side {:?}", open);
+        }
+
+        // A single missing voxel in a closed shell lets the outside in
+        let mut octree = voxelize_mesh_with_threads(&cube(0.45, &[]), &surface, 1);
+        let mut holed = SparseVoxelOctree::new(4);
+        for voxel in octree.iter_leaves(4) {
+            if voxel != VoxelCoord(0, 0, 3) {
+                holed.insert(voxel);
+            }
+        }
+        let surface_count = holed.len();
+        fill_interior(&mut holed, &VoxelizationProgress::new()).unwrap();
+        assert_eq!(holed.len(), surface_count);
+
+        fill_interior(&mut octree, &VoxelizationProgress::new()).unwrap();
+        assert_eq!(octree.len(), 8 * 8 * 8);
     }
 }
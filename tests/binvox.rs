@@ -0,0 +1,88 @@
+mod common;
+
+use common::temp_path;
+use glam::Vec3;
+use model_voxelization::export::write_binvox;
+use model_voxelization::import::load_voxels;
+use model_voxelization::mesh::Normalization;
+use model_voxelization::octree::SparseVoxelOctree;
+use model_voxelization::voxelization::{
+    VoxelCoord, VoxelizationSettings, VoxelizedMesh, VoxelizedModel,
+};
+use std::collections::HashSet;
+
+const DEPTH: usize = 5;
+
+fn mesh(name: &str, voxels: impl IntoIterator<Item = VoxelCoord>) -> VoxelizedMesh {
+    let mut octree = SparseVoxelOctree::new(DEPTH);
+    for voxel in voxels {
+        octree.insert(voxel);
+    }
+    VoxelizedMesh {
+        name: name.to_string(),
+        octree,
+        diagnostics: None,
+    }
+}
+
+#[test]
+fn binvox_round_trip() {
+    // Two whole x layers of the 32³ grid are 2048 voxels in a row, and a scattered
+    // submodel that overlaps them
+    let layers = (-16..16)
+        .flat_map(|z| (-16..16).flat_map(move |y| (3..5).map(move |x| VoxelCoord(x, y, z))));
+    let scattered = (0..40).map(|i| VoxelCoord(i % 9 - 4, (i * 7) % 32 - 16, (i * 5) % 32 - 16));
+    let model = VoxelizedModel {
+        meshes: vec![mesh("layers", layers), mesh("scattered", scattered)],
+        normalization: Normalization {
+            center: Vec3::new(1.5, -2.0, 10.0),
+            scale: 0.25,
+            size: Vec3::new(8.0, 6.0, 4.0),
+        },
+        settings: VoxelizationSettings {
+            octree_depth: DEPTH,
+            ..Default::default()
+        },
+    };
+
+    let path = temp_path("round_trip.binvox");
+    let mut bytes = Vec::new();
+    write_binvox(&model, &mut bytes).unwrap();
+    std::fs::write(&path, &bytes).unwrap();
+    let loaded = load_voxels(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.unwrap();
+
+    // The grid spans 8 units from center - 4 on each axis
+    let data = bytes.windows(5).position(|w| w == b"data\n").unwrap() + 5;
+    let header = std::str::from_utf8(&bytes[..data]).unwrap();
+    assert_eq!(
+        header,
+        "#binvox 1\ndim 32 32 32\ntranslate -2.5 -6 6\nscale 8\ndata\n"
+    );
+
+    // The run of occupied voxels is split into pairs of at most 255
+    let runs: Vec<&[u8]> = bytes[data..].chunks_exact(2).collect();
+    assert!(runs.iter().all(|run| run[1] > 0));
+    assert_eq!(
+        runs.iter().map(|run| run[1] as usize).sum::<usize>(),
+        32 * 32 * 32
+    );
+    assert!(runs
+        .windows(2)
+        .any(|pair| pair[0] == [1, 255] && pair[1][0] == 1));
+
+    assert_eq!(loaded.settings.octree_depth, DEPTH);
+    assert!((loaded.normalization.center - model.normalization.center).length() < 1e-5);
+    assert!((loaded.normalization.scale - model.normalization.scale).abs() < 1e-6);
+
+    let expected: HashSet<VoxelCoord> = model
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.octree.iter_leaves(DEPTH))
+        .collect();
+    assert_eq!(loaded.meshes.len(), 1);
+    let voxels: HashSet<VoxelCoord> = loaded.meshes[0].octree.iter_leaves(DEPTH).collect();
+    assert_eq!(loaded.voxel_count(), expected.len());
+    assert_eq!(voxels, expected);
+}
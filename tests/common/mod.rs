@@ -0,0 +1,52 @@
+// Helpers shared by the integration tests; each test file uses only some of them
+#![allow(dead_code)]
+
+use glam::Vec3;
+use model_voxelization::mesh::TriangleMesh;
+use std::path::PathBuf;
+
+// Path of a file in tests/fixtures
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+// File in the system temp directory, unique to this test process
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "model_voxelization_{}_{}",
+        std::process::id(),
+        name
+    ))
+}
+
+// Closed box mesh from `min` to `max` with outward facing triangles
+pub fn box_mesh(min: Vec3, max: Vec3) -> TriangleMesh {
+    let corner = |i: u32| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    };
+    let positions: Vec<Vec3> = (0..8).map(corner).collect();
+    let center = (min + max) / 2.0;
+
+    let mut indices = Vec::new();
+    for [a, b, c, d] in [
+        [0, 2, 6, 4],
+        [1, 3, 7, 5],
+        [0, 1, 5, 4],
+        [2, 3, 7, 6],
+        [0, 1, 3, 2],
+        [4, 5, 7, 6],
+    ] {
+        for [i, j, k] in [[a, b, c], [a, c, d]] {
+            let [p, q, r] = [i, j, k].map(|n| positions[n as usize]);
+            let outward = (q - p).cross(r - p).dot((p + q + r) / 3.0 - center) > 0.0;
+            indices.extend(if outward { [i, j, k] } else { [i, k, j] });
+        }
+    }
+    TriangleMesh::new("box", positions, indices)
+}
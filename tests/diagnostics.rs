@@ -0,0 +1,141 @@
+mod common;
+
+use common::fixture;
+use glam::Vec3;
+use model_voxelization::import::load_model;
+use model_voxelization::mesh::{normalize_meshes, MeshDiagnostics, TriangleMesh};
+use model_voxelization::voxelization::{voxelize_meshes, VoxelCoord, VoxelizationSettings};
+
+// One good triangle followed by the same triangle with every kind of problem
+fn malformed_mesh() -> TriangleMesh {
+    let positions = vec![
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(1.0, -1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        // Duplicate of vertex 0
+        Vec3::new(-1.0, -1.0, 0.0),
+        // Collinear with vertices 0 and 1
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(f32::NAN, 0.0, 0.0),
+        Vec3::new(0.0, f32::INFINITY, 0.0),
+    ];
+    let indices = vec![
+        0, 1, 2, // good
+        0, 1, 9, // out of range
+        0, 1, 5, // NaN corner
+        6, 1, 2, // infinite corner
+        0, 0, 2, // repeated corner
+        0, 4, 1, // collinear corners
+        0, 1, // trailing
+    ];
+    TriangleMesh::new("malformed", positions, indices)
+}
+
+#[test]
+fn diagnose_counts_every_problem() {
+    let diagnostics = malformed_mesh().diagnose();
+    assert_eq!(
+        diagnostics,
+        MeshDiagnostics {
+            out_of_range_triangles: 1,
+            non_finite_triangles: 2,
+            degenerate_triangles: 2,
+            trailing_indices: 2,
+            non_finite_vertices: 2,
+            duplicate_vertices: 1,
+        }
+    );
+    assert_eq!(diagnostics.skipped_triangles(), 5);
+    assert!(diagnostics.has_errors());
+    assert_eq!(diagnostics.messages().len(), 6);
+}
+
+#[test]
+fn clean_mesh_has_no_problems() {
+    let mesh = TriangleMesh::from_triangles(
+        "triangle",
+        [[Vec3::ZERO, Vec3::X, Vec3::Y], [Vec3::Z, Vec3::X, Vec3::Y]],
+    );
+    let diagnostics = mesh.diagnose();
+    assert!(!diagnostics.has_errors());
+    // Unindexed triangles repeat their shared corners
+    assert_eq!(diagnostics.duplicate_vertices, 2);
+}
+
+#[test]
+fn malformed_triangles_are_skipped() {
+    let mesh = malformed_mesh();
+    let triangles: Vec<[Vec3; 3]> = mesh.triangles().collect();
+    assert_eq!(
+        triangles,
+        [[mesh.positions[0], mesh.positions[1], mesh.positions[2]]]
+    );
+
+    // Only the good triangle is voxelized, and the bad positions do not affect the
+    // normalization
+    let good = TriangleMesh::from_triangles("good", triangles);
+    let settings = VoxelizationSettings {
+        octree_depth: 4,
+        ..Default::default()
+    };
+    let mut malformed = [mesh];
+    let mut reference = [good];
+    let normalization = normalize_meshes(&mut malformed);
+    assert_eq!(normalization, normalize_meshes(&mut reference));
+
+    let model = voxelize_meshes(&malformed, normalization, &settings);
+    let expected = voxelize_meshes(&reference, normalization, &settings);
+    let voxels: Vec<VoxelCoord> = model.meshes[0].octree.iter_leaves(4).collect();
+    assert!(!voxels.is_empty());
+    assert_eq!(
+        voxels,
+        expected.meshes[0].octree.iter_leaves(4).collect::<Vec<_>>()
+    );
+    assert_eq!(model.diagnostics(), Some(malformed[0].diagnose()));
+}
+
+#[test]
+fn out_of_range_indices_are_loaded_and_skipped() {
+    for (name, out_of_range) in [
+        ("out_of_range.obj", 3),
+        ("out_of_range.ply", 1),
+        ("out_of_range.off", 1),
+        ("out_of_range.gltf", 1),
+    ] {
+        let meshes = load_model(&fixture(name)).unwrap();
+        assert_eq!(meshes.len(), 1, "{}", name);
+        let mesh = &meshes[0];
+        assert_eq!(
+            mesh.triangles().collect::<Vec<_>>(),
+            [[Vec3::ZERO, Vec3::X, Vec3::Y]],
+            "{}",
+            name
+        );
+        assert_eq!(
+            mesh.diagnose().out_of_range_triangles,
+            out_of_range,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn out_of_range_obj_faces_keep_the_objects_apart() {
+    let meshes = load_model(&fixture("out_of_range_objects.obj")).unwrap();
+    let names: Vec<&str> = meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+    assert_eq!(names, ["first", "second"]);
+
+    // Relative indices count back from the last vertex read before the face
+    for (mesh, corner) in meshes.iter().zip([Vec3::Y, Vec3::Z]) {
+        assert_eq!(
+            mesh.triangles().collect::<Vec<_>>(),
+            [[Vec3::ZERO, Vec3::X, corner]],
+            "{}",
+            mesh.name
+        );
+        assert_eq!(mesh.diagnose().out_of_range_triangles, 1, "{}", mesh.name);
+        // Only the vertices of the file are kept
+        assert!(mesh.positions.iter().all(|p| p.max_element() <= 1.0));
+    }
+}
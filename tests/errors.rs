@@ -0,0 +1,94 @@
+mod common;
+
+use common::fixture;
+use model_voxelization::import::ImportError;
+use model_voxelization::log;
+use model_voxelization::sdf::{sdf_file, SDF_MAX_DEPTH};
+use model_voxelization::voxelization::{voxelize_file, VoxelizationSettings};
+
+#[test]
+fn model_without_triangles_is_empty() {
+    let result = voxelize_file(&fixture("no_faces.obj"), &VoxelizationSettings::default());
+    assert!(
+        matches!(result, Err(ImportError::EmptyModel)),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn model_with_only_degenerate_triangles_is_empty() {
+    let result = voxelize_file(&fixture("degenerate.obj"), &VoxelizationSettings::default());
+    assert!(
+        matches!(result, Err(ImportError::EmptyModel)),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn tiny_triangles_are_usable_once_normalized() {
+    let model = voxelize_file(&fixture("tiny.obj"), &VoxelizationSettings::default()).unwrap();
+    assert!(model.meshes[0].octree.iter_nodes().next().is_some());
+}
+
+#[test]
+fn oversized_grids_are_rejected() {
+    let result = voxelize_file(
+        &fixture("too_large.binvox"),
+        &VoxelizationSettings::default(),
+    );
+    assert!(
+        matches!(result, Err(ImportError::GridTooLarge(2048))),
+        "{:?}",
+        result
+    );
+
+    let settings = VoxelizationSettings {
+        octree_depth: 11,
+        ..Default::default()
+    };
+    let result = voxelize_file(&fixture("tetrahedron_ascii.stl"), &settings);
+    assert!(
+        matches!(result, Err(ImportError::GridTooLarge(2048))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn sdf_depth_out_of_range_is_an_error() {
+    for depth in [0, SDF_MAX_DEPTH + 1] {
+        let result = sdf_file(&fixture("tetrahedron_ascii.stl"), depth);
+        assert!(
+            matches!(result, Err(ImportError::InvalidSdfDepth(d)) if d == depth),
+            "{:?}",
+            result
+        );
+    }
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+    let result = voxelize_file(&fixture("missing.stl"), &VoxelizationSettings::default());
+    assert!(matches!(result, Err(ImportError::Io(_))), "{:?}", result);
+}
+
+#[test]
+fn voxelization_messages_are_recorded() {
+    voxelize_file(
+        &fixture("tetrahedron_ascii.stl"),
+        &VoxelizationSettings::default(),
+    )
+    .unwrap();
+    let messages = log::take_messages();
+    assert!(messages
+        .iter()
+        .any(|message| message.starts_with("Voxelization complete")));
+
+    // Messages of other tests may arrive at any time, so only check the capacity
+    for i in 0..log::LOG_CAPACITY + 10 {
+        log::record_message(format!("message {}", i));
+    }
+    assert_eq!(log::take_messages().len(), log::LOG_CAPACITY);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::fixture;
+use glam::Vec3;
+use model_voxelization::import::load_model;
+use model_voxelization::mesh::TriangleMesh;
+
+fn assert_positions(mesh: &TriangleMesh, expected: &[[f32; 3]]) {
+    assert_eq!(mesh.positions.len(), expected.len(), "{}", mesh.name);
+    for (position, &expected) in mesh.positions.iter().zip(expected) {
+        assert!(
+            position.abs_diff_eq(Vec3::from(expected), 1e-5),
+            "{}: {} != {:?}",
+            mesh.name,
+            position,
+            expected
+        );
+    }
+}
+
+// A quad on a child model rotated 90° about z and moved up by 1, below a parent
+// moved by 10 along x and scaled by 2
+const HIERARCHY_POSITIONS: [[f32; 3]; 4] = [
+    [10.0, 2.0, 0.0],
+    [10.0, 4.0, 0.0],
+    [8.0, 2.0, 0.0],
+    [8.0, 4.0, 0.0],
+];
+
+#[test]
+fn ascii_fbx_applies_node_hierarchy() {
+    let meshes = load_model(&fixture("hierarchy.fbx")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "Child");
+    assert_positions(&meshes[0], &HIERARCHY_POSITIONS);
+    // The quad is triangulated as a fan
+    assert_eq!(meshes[0].indices, vec![0, 1, 3, 0, 3, 2]);
+}
+
+#[test]
+fn binary_fbx_matches_ascii() {
+    let meshes = load_model(&fixture("hierarchy_binary.fbx")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "Child");
+    assert_positions(&meshes[0], &HIERARCHY_POSITIONS);
+    assert_eq!(meshes[0].indices, vec![0, 1, 3, 0, 3, 2]);
+}
+
+#[test]
+fn fbx_pivots_offsets_and_rotation_order() {
+    // One triangle (0,0,0), (1,0,0), (0,1,0) instanced on four models
+    let meshes = load_model(&fixture("pivots.fbx")).unwrap();
+    let names: Vec<&str> = meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+    assert_eq!(names, ["Pivot", "Order", "PrePost", "ScalingPivot"]);
+
+    // 90° about z around the pivot (1,0,0)
+    assert_positions(
+        &meshes[0],
+        &[[1.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]],
+    );
+    // 90° about z first, then 90° about x (ZYX order)
+    assert_positions(
+        &meshes[1],
+        &[[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [-1.0, 0.0, 0.0]],
+    );
+    // Inverse post-rotation of 90° about z, then pre-rotation of 90° about x
+    assert_positions(
+        &meshes[2],
+        &[[0.0, 0.0, 0.0], [0.0, 0.0, -1.0], [1.0, 0.0, 0.0]],
+    );
+    // Scaled by 2 around (1,0,0), then moved by the scaling and rotation offsets
+    assert_positions(
+        &meshes[3],
+        &[[-1.0, 3.0, 5.0], [1.0, 3.0, 5.0], [-1.0, 5.0, 5.0]],
+    );
+}
+
+#[test]
+fn smallest_polygon_index_does_not_overflow() {
+    // Its vertex is out of range, so the triangle is skipped
+    let meshes = load_model(&fixture("min_index.fbx")).unwrap();
+    assert_eq!(meshes[0].triangles().count(), 1);
+    assert_eq!(meshes[0].diagnose().out_of_range_triangles, 1);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::fixture;
+use model_voxelization::export::{write_glb, write_gltf, ExportOptions, MeshUnits};
+use model_voxelization::mesh::Normalization;
+use model_voxelization::meshing::{build_surface_mesh, MeshingMode};
+use model_voxelization::voxelization::{
+    voxelize_file, voxelize_meshes, VoxelizationSettings, VoxelizedModel,
+};
+
+// Load a written .gltf or .glb with the gltf crate and compare every mesh with the
+// surface mesh of its submodel
+fn check_document(bytes: &[u8], model: &VoxelizedModel, options: &ExportOptions) {
+    let document = gltf::Gltf::from_slice(bytes).unwrap();
+    assert_eq!(document.meshes().count(), model.meshes.len());
+    // Decodes the embedded data URI or the BIN chunk
+    let (_, buffers, _) = gltf::import_slice(bytes).unwrap();
+    let mut colors = Vec::new();
+
+    for (mesh, voxelized) in document.meshes().zip(&model.meshes) {
+        assert_eq!(mesh.name(), Some(voxelized.name.as_str()));
+        let mut surface = build_surface_mesh(&voxelized.octree, options.meshing);
+        if options.mesh_units == MeshUnits::Original {
+            for position in &mut surface.positions {
+                *position = model.normalization.invert((*position).into()).into();
+            }
+        }
+
+        let primitive = mesh.primitives().next().unwrap();
+        let positions = primitive.get(&gltf::Semantic::Positions).unwrap();
+        assert_eq!(positions.count(), surface.vertex_count());
+        assert_eq!(
+            primitive.get(&gltf::Semantic::Normals).unwrap().count(),
+            surface.vertex_count()
+        );
+        assert_eq!(
+            primitive.get(&gltf::Semantic::Colors(0)).unwrap().count(),
+            surface.vertex_count()
+        );
+        assert_eq!(primitive.indices().unwrap().count(), surface.indices.len());
+
+        let min: Vec<f32> = (0..3)
+            .map(|axis| {
+                surface
+                    .positions
+                    .iter()
+                    .map(|p| p[axis])
+                    .fold(f32::MAX, f32::min)
+            })
+            .collect();
+        let max: Vec<f32> = (0..3)
+            .map(|axis| {
+                surface
+                    .positions
+                    .iter()
+                    .map(|p| p[axis])
+                    .fold(f32::MIN, f32::max)
+            })
+            .collect();
+        assert_eq!(bound(positions.min()), min);
+        assert_eq!(bound(positions.max()), max);
+
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let read: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+        assert_eq!(read, surface.positions);
+        let read: Vec<u32> = reader.read_indices().unwrap().into_u32().collect();
+        assert_eq!(read, surface.indices);
+        let read: Vec<[f32; 3]> = reader.read_normals().unwrap().collect();
+        assert_eq!(read, surface.normals);
+
+        // Colors are per submodel, the same for all its vertices
+        let read: Vec<[f32; 4]> = reader.read_colors(0).unwrap().into_rgba_f32().collect();
+        assert!(read.iter().all(|&color| color == read[0]));
+        assert!(!colors.contains(&read[0]));
+        colors.push(read[0]);
+    }
+}
+
+// Components of an accessor's min or max
+fn bound(value: Option<gltf::json::Value>) -> Vec<f32> {
+    value
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_f64().unwrap() as f32)
+        .collect()
+}
+
+#[test]
+fn gltf_and_glb_load_with_matching_accessors() {
+    let settings = VoxelizationSettings {
+        octree_depth: 4,
+        ..Default::default()
+    };
+    // Four submodels
+    let model = voxelize_file(&fixture("pivots.fbx"), &settings).unwrap();
+    assert_eq!(model.meshes.len(), 4);
+
+    for options in [
+        ExportOptions::default(),
+        ExportOptions {
+            mesh_units: MeshUnits::Original,
+            meshing: MeshingMode::Greedy,
+            ..Default::default()
+        },
+    ] {
+        let mut gltf = Vec::new();
+        write_gltf(&model, &options, &mut gltf).unwrap();
+        check_document(&gltf, &model, &options);
+
+        let mut glb = Vec::new();
+        write_glb(&model, &options, &mut glb).unwrap();
+        check_document(&glb, &model, &options);
+    }
+}
+
+#[test]
+fn model_without_voxels_is_written_as_just_the_asset() {
+    let model = voxelize_meshes(&[], Normalization::default(), &Default::default());
+    let options = ExportOptions::default();
+
+    let mut gltf = Vec::new();
+    write_gltf(&model, &options, &mut gltf).unwrap();
+    let mut glb = Vec::new();
+    write_glb(&model, &options, &mut glb).unwrap();
+    for bytes in [gltf, glb] {
+        let document = gltf::Gltf::from_slice(&bytes).unwrap();
+        assert!(document.blob.is_none());
+        assert_eq!(document.scenes().count(), 0);
+        assert_eq!(document.meshes().count(), 0);
+        assert_eq!(document.buffers().count(), 0);
+    }
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::fixture;
+use glam::Vec3;
+use model_voxelization::import::load_model;
+
+// nested_nodes.glb has a "base" node translated by (10, 0, 0) and turned 90° about
+// Z, an "arm" child translated by (0, 2, 0), turned 90° about X and scaled by 2,
+// and a "copy" grandchild with a matrix moving it by (0, 0, 1). Base and copy use a
+// triangle mesh, arm a mesh with a strip primitive and a fan primitive.
+#[test]
+fn nested_node_transforms_place_the_primitives_in_world_space() {
+    let meshes = load_model(&fixture("nested_nodes.glb")).unwrap();
+    let names: Vec<&str> = meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+    assert_eq!(names, ["base", "arm.0", "arm.1", "copy"]);
+
+    let expected: [&[[f32; 3]]; 4] = [
+        &[[10.0, 0.0, 0.0], [10.0, 1.0, 0.0], [9.0, 0.0, 0.0]],
+        // Strip of the unit square
+        &[
+            [8.0, 0.0, 0.0],
+            [8.0, 2.0, 0.0],
+            [8.0, 0.0, 2.0],
+            [8.0, 2.0, 2.0],
+        ],
+        // Fan around (0, 0, 1)
+        &[
+            [10.0, 0.0, 0.0],
+            [8.0, 2.0, 0.0],
+            [8.0, 0.0, 2.0],
+            [8.0, -2.0, 0.0],
+            [8.0, 0.0, -2.0],
+        ],
+        &[[10.0, 0.0, 0.0], [10.0, 2.0, 0.0], [10.0, 0.0, 2.0]],
+    ];
+    for (mesh, expected) in meshes.iter().zip(expected) {
+        assert_eq!(mesh.positions.len(), expected.len(), "{}", mesh.name);
+        for (&position, &expected) in mesh.positions.iter().zip(expected) {
+            assert!(
+                position.abs_diff_eq(Vec3::from(expected), 1e-5),
+                "{}: {} instead of {:?}",
+                mesh.name,
+                position,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn strips_and_fans_become_triangle_lists() {
+    let meshes = load_model(&fixture("nested_nodes.glb")).unwrap();
+
+    // Every other strip triangle is flipped to keep the winding of the first
+    assert_eq!(meshes[1].indices, [0, 1, 2, 2, 1, 3]);
+    assert_eq!(meshes[2].indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+
+    // Both strip triangles face the same way
+    let normals: Vec<Vec3> = meshes[1]
+        .triangles()
+        .map(|[a, b, c]| (b - a).cross(c - a).normalize())
+        .collect();
+    assert_eq!(normals.len(), 2);
+    assert!(normals[0].abs_diff_eq(normals[1], 1e-5));
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::box_mesh;
+use glam::Vec3;
+use model_voxelization::mesh::Normalization;
+use model_voxelization::meshing::{build_surface_mesh, MeshingMode};
+use model_voxelization::sdf::compute_sdf;
+use model_voxelization::voxelization::{
+    voxelize_mesh, FillMode, VoxelCoord, VoxelGrid, VoxelizationSettings,
+};
+use std::collections::HashSet;
+
+fn settings(octree_depth: usize, fill_mode: FillMode) -> VoxelizationSettings {
+    VoxelizationSettings {
+        octree_depth,
+        fill_mode,
+        ..Default::default()
+    }
+}
+
+fn cells(range: std::ops::RangeInclusive<i32>) -> HashSet<VoxelCoord> {
+    let mut cells = HashSet::new();
+    for x in range.clone() {
+        for y in range.clone() {
+            for z in range.clone() {
+                cells.insert(VoxelCoord(x, y, z));
+            }
+        }
+    }
+    cells
+}
+
+fn bounds(positions: &[[f32; 3]]) -> (Vec3, Vec3) {
+    positions.iter().map(|&p| Vec3::from(p)).fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), p| (min.min(p), max.max(p)),
+    )
+}
+
+#[test]
+fn grid_covers_the_coordinate_range() {
+    let grid = VoxelGrid::new(3);
+    assert_eq!(grid.cell_size, 0.25);
+    assert_eq!(grid.cell_min(VoxelCoord(-4, -4, -4)), Vec3::splat(-1.0));
+    assert_eq!(grid.cell_min(VoxelCoord(3, 3, 3)), Vec3::splat(0.75));
+    assert_eq!(
+        grid.cell_center(VoxelCoord(0, -1, 3)),
+        Vec3::new(0.125, -0.125, 0.875)
+    );
+    assert_eq!(settings(3, FillMode::Surface).grid(), grid);
+}
+
+#[test]
+fn grid_conversions_round_trip() {
+    let grid = VoxelGrid::new(5);
+    for coord in [
+        VoxelCoord(0, 0, 0),
+        VoxelCoord(-16, 7, 15),
+        VoxelCoord(3, -9, -1),
+    ] {
+        assert_eq!(grid.cell_at(grid.cell_center(coord)), coord);
+        assert_eq!(grid.cell_at(grid.cell_min(coord)), coord);
+        let point = Vec3::new(coord.0 as f32, coord.1 as f32, coord.2 as f32) + 0.25;
+        assert!(grid.to_grid(grid.to_world(point)).distance(point) < 1e-5);
+    }
+}
+
+#[test]
+fn axis_aligned_cube_lands_on_expected_cells() {
+    // Slightly inside cells 1..=2 and -3..=-2 of every axis at depth 3
+    for (min, max, expected) in [(0.26, 0.74, 1..=2), (-0.74, -0.26, -3..=-2)] {
+        let mesh = box_mesh(Vec3::splat(min), Vec3::splat(max));
+        for fill_mode in FillMode::ALL {
+            let octree = voxelize_mesh(&mesh, &settings(3, fill_mode));
+            let voxels: HashSet<VoxelCoord> = octree.iter_leaves(3).collect();
+            assert_eq!(voxels, cells(expected.clone()), "{:?}", fill_mode);
+        }
+    }
+}
+
+#[test]
+fn block_meshes_cover_the_voxelized_cells() {
+    let mesh = box_mesh(Vec3::splat(0.26), Vec3::splat(0.74));
+    let octree = voxelize_mesh(&mesh, &settings(3, FillMode::Solid));
+
+    for mode in [MeshingMode::Cubes, MeshingMode::Greedy] {
+        let surface = build_surface_mesh(&octree, mode);
+        assert_eq!(
+            bounds(&surface.positions),
+            (Vec3::splat(0.25), Vec3::splat(0.75)),
+            "{:?}",
+            mode
+        );
+        // Every vertex lies on the cell boundaries
+        for position in &surface.positions {
+            assert!(
+                position.iter().all(|&c| (c / 0.25).fract() == 0.0),
+                "{:?}",
+                mode
+            );
+        }
+    }
+}
+
+#[test]
+fn smooth_mesh_stays_within_the_voxelized_cells() {
+    let mesh = box_mesh(Vec3::splat(0.26), Vec3::splat(0.74));
+    let octree = voxelize_mesh(&mesh, &settings(3, FillMode::Solid));
+
+    let surface = build_surface_mesh(&octree, MeshingMode::SurfaceNets);
+    let (min, max) = bounds(&surface.positions);
+    assert!(min.cmpge(Vec3::splat(0.25)).all() && max.cmple(Vec3::splat(0.75)).all());
+    assert!(((min + max) / 2.0).distance(Vec3::splat(0.5)) < 1e-5);
+}
+
+#[test]
+fn sdf_is_sampled_at_the_voxel_centers() {
+    let (min, max) = (Vec3::splat(-0.74), Vec3::splat(0.49));
+    let mesh = box_mesh(min, max);
+    let normalization = Normalization {
+        center: Vec3::ZERO,
+        scale: 1.0,
+        size: Vec3::splat(2.0),
+    };
+    let settings = settings(4, FillMode::Solid);
+    let sdf = compute_sdf(std::slice::from_ref(&mesh), normalization, 4);
+    let octree = voxelize_mesh(&mesh, &settings);
+    let grid = settings.grid();
+
+    for (coord, distance) in sdf.iter() {
+        let position = grid.cell_center(coord);
+        assert_eq!(sdf.sample_position(coord), position);
+
+        // Exact distance to the box
+        let center = (min + max) / 2.0;
+        let q = (position - center).abs() - (max - min) / 2.0;
+        let expected = q.max(Vec3::ZERO).length() + q.max_element().min(0.0);
+        assert!((distance - expected).abs() < 0.02, "{:?}", coord);
+
+        // Solid voxels are exactly the samples inside the box
+        assert_eq!(octree.is_occupied(coord), distance < 0.0, "{:?}", coord);
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{box_mesh, temp_path};
+use glam::Vec3;
+use model_voxelization::export::{write_obj, write_ply, ExportOptions, MeshUnits};
+use model_voxelization::import::load_model;
+use model_voxelization::mesh::{Normalization, TriangleMesh};
+use model_voxelization::meshing::{build_surface_mesh, MeshingMode, SurfaceMesh};
+use model_voxelization::voxelization::{
+    voxelize_meshes, FillMode, VoxelizationSettings, VoxelizedModel,
+};
+
+// Two submodels placed off the origin, so original units differ from normalized ones
+fn model() -> VoxelizedModel {
+    let meshes = [
+        box_mesh(Vec3::new(-0.8, -0.6, -0.4), Vec3::new(0.1, 0.5, 0.3)),
+        box_mesh(Vec3::new(0.2, -0.7, 0.1), Vec3::new(0.7, 0.4, 0.8)),
+    ];
+    let normalization = Normalization {
+        center: Vec3::new(3.0, -1.0, 0.5),
+        scale: 0.25,
+        size: Vec3::splat(8.0),
+    };
+    let settings = VoxelizationSettings {
+        octree_depth: 5,
+        fill_mode: FillMode::Solid,
+        ..Default::default()
+    };
+    voxelize_meshes(&meshes, normalization, &settings)
+}
+
+// The surface mesh of every submodel, as the exporters build it
+fn surfaces(model: &VoxelizedModel, options: &ExportOptions) -> Vec<SurfaceMesh> {
+    model
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let mut surface = build_surface_mesh(&mesh.octree, options.meshing);
+            if options.mesh_units == MeshUnits::Original {
+                for position in &mut surface.positions {
+                    *position = model.normalization.invert((*position).into()).into();
+                }
+            }
+            surface
+        })
+        .collect()
+}
+
+// The corners of every triangle in order
+fn corners(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    indices
+        .iter()
+        .map(|&index| positions[index as usize])
+        .collect()
+}
+
+fn loaded_corners(mesh: &TriangleMesh) -> Vec<[f32; 3]> {
+    corners(&positions(mesh), &mesh.indices)
+}
+
+fn positions(mesh: &TriangleMesh) -> Vec<[f32; 3]> {
+    mesh.positions.iter().map(|p| p.to_array()).collect()
+}
+
+// Write the model with `write` to a file named `name` and load it back
+fn round_trip(
+    name: &str,
+    model: &VoxelizedModel,
+    options: &ExportOptions,
+    write: fn(&VoxelizedModel, &ExportOptions, &mut Vec<u8>) -> std::io::Result<()>,
+) -> Vec<TriangleMesh> {
+    let mut bytes = Vec::new();
+    write(model, options, &mut bytes).unwrap();
+    let path = temp_path(name);
+    std::fs::write(&path, bytes).unwrap();
+    let loaded = load_model(&path);
+    std::fs::remove_file(&path).unwrap();
+    loaded.unwrap()
+}
+
+#[test]
+fn obj_export_loads_back_as_the_surface_meshes() {
+    let model = model();
+    for meshing in MeshingMode::ALL {
+        for mesh_units in MeshUnits::ALL {
+            let options = ExportOptions {
+                meshing,
+                mesh_units,
+                ..Default::default()
+            };
+            let loaded = round_trip("round_trip.obj", &model, &options, write_obj);
+
+            // One object per submodel. tobj numbers the vertices of an object in
+            // the order its faces use them, so the triangles are compared by their
+            // corners.
+            let surfaces = surfaces(&model, &options);
+            assert_eq!(loaded.len(), surfaces.len());
+            for ((loaded, surface), mesh) in loaded.iter().zip(&surfaces).zip(&model.meshes) {
+                assert_eq!(loaded.name, mesh.name);
+                assert_eq!(loaded.positions.len(), surface.vertex_count());
+                assert_eq!(
+                    loaded_corners(loaded),
+                    corners(&surface.positions, &surface.indices)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn ply_export_loads_back_as_the_surface_meshes() {
+    let model = model();
+    for meshing in MeshingMode::ALL {
+        for mesh_units in MeshUnits::ALL {
+            let options = ExportOptions {
+                meshing,
+                mesh_units,
+                ..Default::default()
+            };
+            let loaded = round_trip("round_trip.ply", &model, &options, write_ply);
+
+            // All submodels in a single mesh, one after another
+            let mut merged = SurfaceMesh::default();
+            for surface in surfaces(&model, &options) {
+                let offset = merged.positions.len() as u32;
+                merged.positions.extend(surface.positions);
+                merged
+                    .indices
+                    .extend(surface.indices.iter().map(|index| index + offset));
+            }
+            assert_eq!(loaded.len(), 1);
+            assert_eq!(positions(&loaded[0]), merged.positions);
+            assert_eq!(loaded[0].indices, merged.indices);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use glam::Vec3;
+use model_voxelization::meshing::{
+    create_greedy_mesh, create_surface_nets_mesh, create_voxelized_mesh, SurfaceMesh,
+};
+use model_voxelization::octree::SparseVoxelOctree;
+use model_voxelization::voxelization::{VoxelCoord, VoxelGrid};
+use std::collections::{HashMap, HashSet};
+
+const DEPTH: usize = 5;
+
+fn octree(voxels: impl IntoIterator<Item = VoxelCoord>) -> SparseVoxelOctree {
+    let mut octree = SparseVoxelOctree::new(DEPTH);
+    for voxel in voxels {
+        octree.insert(voxel);
+    }
+    octree
+}
+
+// Every voxel of the grid
+fn all_voxels() -> impl Iterator<Item = VoxelCoord> {
+    let half = 1 << (DEPTH - 1);
+    (-half..half).flat_map(move |z| {
+        (-half..half).flat_map(move |y| (-half..half).map(move |x| VoxelCoord(x, y, z)))
+    })
+}
+
+// The voxel faces covered by a block mesh, as the outward axis and sign and the
+// voxel the face belongs to. Block meshes are quads of four consecutive vertices
+// spanning whole cells; a face covered twice is an overlap.
+fn unit_faces(mesh: &SurfaceMesh) -> HashSet<([i32; 3], [i32; 3])> {
+    let grid = VoxelGrid::new(DEPTH);
+    let mut faces = HashSet::new();
+    for (quad, normal) in mesh
+        .positions
+        .chunks_exact(4)
+        .zip(mesh.normals.iter().step_by(4))
+    {
+        let corners: Vec<Vec3> = quad
+            .iter()
+            .map(|&p| grid.to_grid(Vec3::from(p)).round())
+            .collect();
+        let min = corners.iter().fold(Vec3::MAX, |a, &b| a.min(b)).as_ivec3();
+        let max = corners.iter().fold(Vec3::MIN, |a, &b| a.max(b)).as_ivec3();
+        let normal = normal.map(|n| n as i32);
+        let axis = normal.iter().position(|&n| n != 0).unwrap();
+        assert_eq!(
+            min[axis], max[axis],
+            "quad is not perpendicular to its normal"
+        );
+
+        // The voxel lies below the face on positive sides and above it on negative ones
+        let layer = if normal[axis] > 0 {
+            min[axis] - 1
+        } else {
+            min[axis]
+        };
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for cu in min[u]..max[u] {
+            for cv in min[v]..max[v] {
+                let mut voxel = [0; 3];
+                (voxel[axis], voxel[u], voxel[v]) = (layer, cu, cv);
+                assert!(faces.insert((normal, voxel)), "{:?} covered twice", voxel);
+            }
+        }
+    }
+    faces
+}
+
+#[test]
+fn greedy_mesh_merges_the_faces_of_a_box() {
+    let octree =
+        octree(all_voxels().filter(|v| {
+            (-6..6).contains(&v.0) && (-3..3).contains(&v.1) && (-4..5).contains(&v.2)
+        }));
+
+    let cubes = create_voxelized_mesh(&octree);
+    let greedy = create_greedy_mesh(&octree);
+    assert_eq!(cubes.triangle_count(), 2 * 2 * (12 * 6 + 6 * 9 + 12 * 9));
+    // One rectangle per side
+    assert_eq!(greedy.triangle_count(), 2 * 6);
+    assert_eq!(greedy.vertex_count(), 4 * 6);
+    assert_eq!(unit_faces(&greedy), unit_faces(&cubes));
+}
+
+#[test]
+fn greedy_mesh_covers_the_cube_faces_without_holes_or_overlaps() {
+    // A ball with a hollow core and a tunnel through it, so there are inner
+    // faces, concave edges and layers with several separate regions
+    let octree = octree(all_voxels().filter(|v| {
+        let distance = Vec3::new(v.0 as f32, v.1 as f32, v.2 as f32) + 0.5;
+        let radius = distance.length();
+        let tunnel = v.0.abs() <= 1 && v.1.abs() <= 1;
+        radius < 12.0 && radius > 5.0 && !tunnel
+    }));
+
+    let cubes = create_voxelized_mesh(&octree);
+    let greedy = create_greedy_mesh(&octree);
+    let faces = unit_faces(&cubes);
+    assert_eq!(faces.len(), cubes.triangle_count() / 2);
+    assert_eq!(unit_faces(&greedy), faces);
+    assert!(
+        greedy.triangle_count() * 2 < cubes.triangle_count(),
+        "{} greedy and {} cube triangles",
+        greedy.triangle_count(),
+        cubes.triangle_count()
+    );
+
+    // Both triangles of every quad face along its normal
+    for (triangle, normal) in greedy
+        .indices
+        .chunks_exact(3)
+        .zip(greedy.normals.iter().step_by(4).flat_map(|&n| [n, n]))
+    {
+        let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(greedy.positions[triangle[k] as usize]));
+        assert!((b - a).cross(c - a).dot(Vec3::from(normal)) > 0.0);
+    }
+}
+
+#[test]
+fn smooth_mesh_of_a_box_is_closed() {
+    let octree =
+        octree(all_voxels().filter(|v| {
+            (-6..6).contains(&v.0) && (-3..3).contains(&v.1) && (-4..5).contains(&v.2)
+        }));
+    let mesh = create_surface_nets_mesh(&octree);
+    assert!(mesh.triangle_count() > 0);
+
+    // Every edge is used once in each direction by two triangles wound the same way
+    let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        for i in 0..3 {
+            *edges
+                .entry((triangle[i], triangle[(i + 1) % 3]))
+                .or_default() += 1;
+        }
+    }
+    for (&(a, b), &count) in &edges {
+        assert_eq!(count, 1, "edge {} {} used {} times", a, b, count);
+        assert!(edges.contains_key(&(b, a)), "edge {} {} is open", a, b);
+    }
+}
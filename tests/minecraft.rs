@@ -0,0 +1,299 @@
+mod common;
+
+use common::box_mesh;
+use flate2::read::GzDecoder;
+use glam::Vec3;
+use model_voxelization::export::{write_schem, write_structure, BlockPalette, ExportOptions};
+use model_voxelization::mesh::Normalization;
+use model_voxelization::voxelization::{
+    voxelize_meshes, FillMode, VoxelCoord, VoxelizationSettings, VoxelizedModel,
+};
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read};
+
+// A decoded NBT tag
+#[derive(Debug, PartialEq)]
+enum Tag {
+    Short(i16),
+    Int(i32),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Vec<(String, Tag)>),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+}
+
+impl Tag {
+    // The entry of a compound with the given name
+    fn get(&self, name: &str) -> &Tag {
+        let Tag::Compound(entries) = self else {
+            panic!("{:?} is not a compound", self);
+        };
+        entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, tag)| tag)
+            .unwrap_or_else(|| panic!("no {} tag", name))
+    }
+
+    fn names(&self) -> Vec<&str> {
+        let Tag::Compound(entries) = self else {
+            panic!("{:?} is not a compound", self);
+        };
+        entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn int(&self) -> i32 {
+        match self {
+            Tag::Int(value) => *value,
+            tag => panic!("{:?} is not an int", tag),
+        }
+    }
+
+    fn list(&self) -> &[Tag] {
+        match self {
+            Tag::List(items) => items,
+            tag => panic!("{:?} is not a list", tag),
+        }
+    }
+}
+
+// Big endian reader over the decompressed file
+struct Reader {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    fn take(&mut self, count: usize) -> &[u8] {
+        let bytes = &self.bytes[self.pos..self.pos + count];
+        self.pos += count;
+        bytes
+    }
+
+    fn i32(&mut self) -> i32 {
+        i32::from_be_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn string(&mut self) -> String {
+        let length = u16::from_be_bytes(self.take(2).try_into().unwrap());
+        String::from_utf8(self.take(length as usize).to_vec()).unwrap()
+    }
+
+    fn payload(&mut self, id: u8) -> Tag {
+        match id {
+            2 => Tag::Short(i16::from_be_bytes(self.take(2).try_into().unwrap())),
+            3 => Tag::Int(self.i32()),
+            7 => {
+                let length = self.i32() as usize;
+                Tag::ByteArray(self.take(length).to_vec())
+            }
+            8 => Tag::String(self.string()),
+            9 => {
+                let item = self.take(1)[0];
+                let length = self.i32();
+                Tag::List((0..length).map(|_| self.payload(item)).collect())
+            }
+            10 => {
+                let mut entries = Vec::new();
+                loop {
+                    let id = self.take(1)[0];
+                    if id == 0 {
+                        break;
+                    }
+                    let name = self.string();
+                    entries.push((name, self.payload(id)));
+                }
+                Tag::Compound(entries)
+            }
+            11 => {
+                let length = self.i32();
+                Tag::IntArray((0..length).map(|_| self.i32()).collect())
+            }
+            id => panic!("unexpected tag type {}", id),
+        }
+    }
+}
+
+// The root tag of a gzip compressed NBT file and its name
+fn decode(file: &[u8]) -> (String, Tag) {
+    let mut bytes = Vec::new();
+    GzDecoder::new(file).read_to_end(&mut bytes).unwrap();
+    let mut reader = Reader { bytes, pos: 0 };
+    assert_eq!(reader.take(1), [10]);
+    let name = reader.string();
+    let root = reader.payload(10);
+    assert_eq!(reader.pos, reader.bytes.len());
+    (name, root)
+}
+
+// Two boxes in separate submodels, in a grid of 2^depth voxels per axis
+fn model(depth: usize) -> VoxelizedModel {
+    let meshes = [
+        box_mesh(Vec3::new(-0.8, -0.6, -0.4), Vec3::new(0.1, 0.5, 0.3)),
+        box_mesh(Vec3::splat(0.3), Vec3::splat(0.7)),
+    ];
+    let settings = VoxelizationSettings {
+        octree_depth: depth,
+        fill_mode: FillMode::Solid,
+        ..Default::default()
+    };
+    voxelize_meshes(&meshes, Normalization::default(), &settings)
+}
+
+// Block positions of each submodel, counted from the grid's minimum corner. The
+// first submodel wins where they overlap.
+fn positions(model: &VoxelizedModel) -> Vec<HashSet<[i32; 3]>> {
+    let depth = model.settings.octree_depth;
+    let half = 1 << (depth - 1);
+    let mut seen = HashSet::new();
+    model
+        .meshes
+        .iter()
+        .map(|mesh| {
+            mesh.octree
+                .iter_leaves(depth)
+                .map(|VoxelCoord(x, y, z)| [x + half, y + half, z + half])
+                .filter(|&position| seen.insert(position))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn schematic_stores_every_block_as_a_palette_varint() {
+    let model = model(5);
+    let options = ExportOptions::default();
+    let mut file = Vec::new();
+    write_schem(&model, &options, &mut file).unwrap();
+    let (name, root) = decode(&file);
+
+    assert_eq!(name, "Schematic");
+    assert_eq!(
+        root.names(),
+        [
+            "Version",
+            "DataVersion",
+            "Width",
+            "Height",
+            "Length",
+            "Offset",
+            "PaletteMax",
+            "Palette",
+            "BlockData",
+            "BlockEntities"
+        ]
+    );
+    assert_eq!(root.get("Version").int(), 2);
+    for axis in ["Width", "Height", "Length"] {
+        assert_eq!(*root.get(axis), Tag::Short(32));
+    }
+    assert_eq!(*root.get("Offset"), Tag::IntArray(vec![0, 0, 0]));
+    assert_eq!(
+        *root.get("Palette"),
+        Tag::Compound(vec![
+            ("minecraft:air".to_string(), Tag::Int(0)),
+            ("minecraft:stone".to_string(), Tag::Int(1)),
+        ])
+    );
+    assert_eq!(root.get("PaletteMax").int(), 2);
+    assert!(root.get("BlockEntities").list().is_empty());
+
+    // Palette indices ordered by y, then z, then x
+    let Tag::ByteArray(data) = root.get("BlockData") else {
+        panic!("BlockData is not a byte array");
+    };
+    let mut indices = Vec::new();
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        let (mut index, mut shift, mut byte) = (0, 0, byte);
+        while byte & 0x80 != 0 {
+            index |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            byte = *bytes.next().unwrap();
+        }
+        indices.push(index | (byte as usize) << shift);
+    }
+    assert_eq!(indices.len(), 32 * 32 * 32);
+
+    let stone: HashSet<[i32; 3]> = indices
+        .iter()
+        .enumerate()
+        .filter(|&(_, &index)| index != 0)
+        .map(|(i, &index)| {
+            assert_eq!(index, 1);
+            let i = i as i32;
+            [i % 32, i / (32 * 32), i / 32 % 32]
+        })
+        .collect();
+    let expected: HashSet<[i32; 3]> = positions(&model).into_iter().flatten().collect();
+    assert_eq!(stone.len(), model.voxel_count());
+    assert_eq!(stone, expected);
+}
+
+#[test]
+fn structure_lists_each_block_with_its_state() {
+    let model = model(5);
+    let options = ExportOptions {
+        block_palette: BlockPalette::concrete(),
+        ..Default::default()
+    };
+    let mut file = Vec::new();
+    write_structure(&model, &options, &mut file).unwrap();
+    let (name, root) = decode(&file);
+
+    assert_eq!(name, "");
+    assert_eq!(
+        root.names(),
+        ["DataVersion", "size", "palette", "blocks", "entities"]
+    );
+    assert_eq!(
+        *root.get("size"),
+        Tag::List(vec![Tag::Int(32), Tag::Int(32), Tag::Int(32)])
+    );
+    assert!(root.get("entities").list().is_empty());
+
+    let palette: Vec<&Tag> = root
+        .get("palette")
+        .list()
+        .iter()
+        .map(|state| state.get("Name"))
+        .collect();
+    assert_eq!(*palette[0], Tag::String("minecraft:air".to_string()));
+    for block in &palette[1..] {
+        assert!(matches!(block, Tag::String(name) if name.ends_with("_concrete")));
+    }
+
+    // Only occupied blocks are stored, each submodel as a single concrete block
+    let mut states: HashMap<i32, HashSet<[i32; 3]>> = HashMap::new();
+    for block in root.get("blocks").list() {
+        assert_eq!(block.names(), ["pos", "state"]);
+        let position: Vec<i32> = block.get("pos").list().iter().map(Tag::int).collect();
+        states
+            .entry(block.get("state").int())
+            .or_default()
+            .insert(position.try_into().unwrap());
+    }
+    assert!(!states.contains_key(&0));
+    let mut blocks: Vec<HashSet<[i32; 3]>> = states.into_values().collect();
+    let mut expected = positions(&model);
+    blocks.sort_by_key(HashSet::len);
+    expected.sort_by_key(HashSet::len);
+    assert_eq!(blocks, expected);
+}
+
+#[test]
+fn grids_past_the_size_limits_are_rejected() {
+    let options = ExportOptions::default();
+
+    let error = write_schem(&model(9), &options, &mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("512³"), "{}", error);
+    assert!(error.to_string().contains("384 blocks"), "{}", error);
+    write_schem(&model(8), &options, &mut Vec::new()).unwrap();
+
+    let error = write_structure(&model(6), &options, &mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("64³"), "{}", error);
+    assert!(error.to_string().contains("48 blocks"), "{}", error);
+}
@@ -0,0 +1,69 @@
+use glam::Vec3;
+use model_voxelization::mesh::{normalize_meshes, TriangleMesh};
+use model_voxelization::voxelization::{
+    voxelize_mesh, voxelize_mesh_with_threads, FillMode, IntersectionTest, VoxelizationSettings,
+};
+use std::f32::consts::PI;
+
+// Closed sphere with 4 * (segments - 1) * segments triangles, normalized
+fn sphere(segments: u32) -> TriangleMesh {
+    let sectors = 2 * segments;
+    let mut triangles = Vec::new();
+    let point = |ring: u32, sector: u32| {
+        let theta = PI * ring as f32 / segments as f32;
+        let phi = 2.0 * PI * sector as f32 / sectors as f32;
+        Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    };
+    for ring in 0..segments {
+        for sector in 0..sectors {
+            let (a, b) = (point(ring, sector), point(ring, sector + 1));
+            let (c, d) = (point(ring + 1, sector), point(ring + 1, sector + 1));
+            if ring > 0 {
+                triangles.push([a, b, d]);
+            }
+            if ring < segments - 1 {
+                triangles.push([a, d, c]);
+            }
+        }
+    }
+
+    let mut meshes = [TriangleMesh::from_triangles("sphere", triangles)];
+    normalize_meshes(&mut meshes);
+    let [mesh] = meshes;
+    mesh
+}
+
+#[test]
+fn parallel_voxelization_matches_single_thread() {
+    // Enough triangles for every thread to get a share
+    let mesh = sphere(90);
+    assert!(mesh.triangle_count() > 7 * 4096);
+
+    for fill_mode in FillMode::ALL {
+        for intersection_test in IntersectionTest::ALL {
+            let settings = VoxelizationSettings {
+                octree_depth: 7,
+                fill_mode,
+                intersection_test,
+            };
+            let expected = voxelize_mesh_with_threads(&mesh, &settings, 1);
+            assert!(!expected.is_empty());
+            for threads in [2, 3, 7] {
+                let octree = voxelize_mesh_with_threads(&mesh, &settings, threads);
+                assert!(
+                    octree == expected,
+                    "{:?} {:?} on {} threads",
+                    fill_mode,
+                    intersection_test,
+                    threads
+                );
+            }
+            // As many threads as the machine has
+            assert!(voxelize_mesh(&mesh, &settings) == expected);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::fixture;
+use model_voxelization::import::{load_model, ImportError};
+
+// A square pyramid whose base is a quad, with extra vertex and face properties
+// that the importer skips
+#[test]
+fn ascii_ply_loads_and_triangulates_quads() {
+    let meshes = load_model(&fixture("pyramid_ascii.ply")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "pyramid_ascii");
+    assert_eq!(meshes[0].positions.len(), 5);
+    assert_eq!(
+        meshes[0].indices,
+        [0, 3, 2, 0, 2, 1, 0, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4]
+    );
+}
+
+#[test]
+fn binary_ply_matches_ascii_in_both_byte_orders() {
+    let ascii = load_model(&fixture("pyramid_ascii.ply")).unwrap();
+    for name in ["pyramid_le.ply", "pyramid_be.ply"] {
+        let binary = load_model(&fixture(name)).unwrap();
+        assert_eq!(binary[0].positions, ascii[0].positions, "{}", name);
+        assert_eq!(binary[0].indices, ascii[0].indices, "{}", name);
+    }
+}
+
+#[test]
+fn negative_or_fractional_ply_index_is_a_parse_error() {
+    for name in ["negative_index.ply", "fractional_index.ply"] {
+        let result = load_model(&fixture(name));
+        assert!(matches!(result, Err(ImportError::Parse(_))), "{}", name);
+    }
+}
+
+#[test]
+fn off_polygons_are_triangulated_as_fans() {
+    let meshes = load_model(&fixture("pentagon.off")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].positions.len(), 6);
+    assert_eq!(meshes[0].indices, [0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 1, 5]);
+}
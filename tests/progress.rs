@@ -0,0 +1,118 @@
+mod common;
+
+use common::{fixture, temp_path};
+use model_voxelization::export::{
+    export_model_with_progress, ExportFormat, ExportOptions, ExportProgress,
+};
+use model_voxelization::import::ImportError;
+use model_voxelization::sdf::{sdf_file, sdf_file_with_progress};
+use model_voxelization::voxelization::{
+    voxelize_file, voxelize_file_with_progress, VoxelizationProgress, VoxelizationSettings,
+};
+
+#[test]
+fn progress_counts_every_triangle() {
+    let progress = VoxelizationProgress::new();
+    assert_eq!(progress.triangles(), (0, 0));
+
+    let settings = VoxelizationSettings::default();
+    let path = fixture("tetrahedron_ascii.stl");
+    let model = voxelize_file_with_progress(&path, &settings, &progress).unwrap();
+    assert_eq!(progress.triangles(), (4, 4));
+
+    let expected = voxelize_file(&path, &settings).unwrap();
+    assert!(model.meshes[0].octree == expected.meshes[0].octree);
+}
+
+#[test]
+fn cancelled_voxelization_returns_no_voxels() {
+    let progress = VoxelizationProgress::new();
+    progress.cancel();
+    assert!(progress.is_cancelled());
+
+    let result = voxelize_file_with_progress(
+        &fixture("tetrahedron_ascii.stl"),
+        &VoxelizationSettings::default(),
+        &progress,
+    );
+    assert!(matches!(result, Err(ImportError::Cancelled)));
+    assert_eq!(progress.triangles(), (0, 4));
+}
+
+#[test]
+fn sdf_progress_counts_every_triangle() {
+    let progress = VoxelizationProgress::new();
+    let path = fixture("tetrahedron_ascii.stl");
+    let sdf = sdf_file_with_progress(&path, 4, &progress).unwrap();
+    assert_eq!(progress.triangles(), (4, 4));
+    assert_eq!(sdf.values(), sdf_file(&path, 4).unwrap().values());
+}
+
+#[test]
+fn cancelled_sdf_returns_no_field() {
+    let progress = VoxelizationProgress::new();
+    progress.cancel();
+    let result = sdf_file_with_progress(&fixture("tetrahedron_ascii.stl"), 4, &progress);
+    assert!(matches!(result, Err(ImportError::Cancelled)));
+    assert_eq!(progress.triangles(), (0, 4));
+}
+
+#[test]
+fn export_progress_counts_the_written_bytes() {
+    let model = voxelize_file(
+        &fixture("tetrahedron_ascii.stl"),
+        &VoxelizationSettings::default(),
+    )
+    .unwrap();
+    let options = ExportOptions::default();
+
+    for format in [ExportFormat::Vox, ExportFormat::PngSlices] {
+        let path = temp_path(&format!("progress.{}", format.extension()));
+        let progress = ExportProgress::new();
+        export_model_with_progress(&model, &path, format, &options, &progress).unwrap();
+
+        // One file, or one slice per layer next to it
+        let files: Vec<_> = if format == ExportFormat::PngSlices {
+            let stem = path.file_stem().unwrap().to_str().unwrap().to_string();
+            (0..1 << model.settings.octree_depth)
+                .map(|layer| path.with_file_name(format!("{}_{:04}.png", stem, layer)))
+                .collect()
+        } else {
+            vec![path.clone()]
+        };
+        let mut size = 0;
+        for file in &files {
+            size += std::fs::metadata(file).unwrap().len();
+            std::fs::remove_file(file).unwrap();
+        }
+        assert_eq!(progress.bytes_written(), size);
+    }
+}
+
+#[test]
+fn cancelled_export_leaves_no_files() {
+    let model = voxelize_file(
+        &fixture("tetrahedron_ascii.stl"),
+        &VoxelizationSettings::default(),
+    )
+    .unwrap();
+    let options = ExportOptions::default();
+
+    for format in [ExportFormat::Raw, ExportFormat::PngSlices] {
+        let path = temp_path(&format!("cancelled.{}", format.extension()));
+        let progress = ExportProgress::new();
+        progress.cancel();
+        assert!(export_model_with_progress(&model, &path, format, &options, &progress).is_err());
+        assert_eq!(progress.bytes_written(), 0);
+
+        let stem = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let leftovers = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&stem)
+            })
+            .count();
+        assert_eq!(leftovers, 0, "{:?}", format);
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{fixture, temp_path};
+use glam::{Quat, Vec3};
+use model_voxelization::project::{hash_file, load_project, save_project, CameraState, Project};
+use model_voxelization::voxelization::{voxelize_file, FillMode, VoxelizationSettings};
+
+#[test]
+fn project_round_trip() {
+    let source = fixture("tetrahedron_ascii.stl");
+    let settings = VoxelizationSettings {
+        octree_depth: 5,
+        fill_mode: FillMode::Solid,
+        ..Default::default()
+    };
+    let model = voxelize_file(&source, &settings).unwrap();
+    let camera = CameraState {
+        position: Vec3::new(1.0, 2.0, 3.0),
+        rotation: Quat::from_rotation_y(0.5),
+        target: Vec3::new(0.0, 0.5, 0.0),
+        distance: 3.5,
+    };
+
+    for (source_path, source_hash, camera) in [
+        (
+            Some(source.clone()),
+            Some(hash_file(&source).unwrap()),
+            Some(camera),
+        ),
+        // Projects of projects without a source model keep having none
+        (None, None, None),
+    ] {
+        let project = Project {
+            model: model.clone(),
+            source_path,
+            source_hash,
+            camera,
+        };
+        let path = temp_path("round_trip.mvproj");
+        save_project(&project, &path).unwrap();
+        let loaded = load_project(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.source_path, project.source_path);
+        assert_eq!(loaded.source_hash, project.source_hash);
+        assert_eq!(loaded.camera, project.camera);
+        assert_eq!(loaded.model.settings, model.settings);
+        assert_eq!(loaded.model.normalization, model.normalization);
+        assert_eq!(loaded.model.meshes.len(), model.meshes.len());
+        for (loaded, mesh) in loaded.model.meshes.iter().zip(&model.meshes) {
+            assert_eq!(loaded.name, mesh.name);
+            assert_eq!(loaded.octree, mesh.octree);
+        }
+    }
+}
+
+#[test]
+fn changed_source_no_longer_matches_the_saved_hash() {
+    let source = temp_path("source.stl");
+    std::fs::copy(fixture("tetrahedron_ascii.stl"), &source).unwrap();
+    let model = voxelize_file(&source, &VoxelizationSettings::default()).unwrap();
+    let project = Project {
+        model,
+        source_path: Some(source.clone()),
+        source_hash: Some(hash_file(&source).unwrap()),
+        camera: None,
+    };
+    let path = temp_path("changed.mvproj");
+    save_project(&project, &path).unwrap();
+
+    let loaded = load_project(&path).unwrap();
+    assert_eq!(loaded.source_hash, Some(hash_file(&source).unwrap()));
+
+    // Any change to the source model's bytes changes its hash
+    let mut bytes = std::fs::read(&source).unwrap();
+    bytes.extend_from_slice(b"\n");
+    std::fs::write(&source, bytes).unwrap();
+    let loaded = load_project(&path).unwrap();
+    assert_ne!(loaded.source_hash, Some(hash_file(&source).unwrap()));
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&source).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_source_path_is_refused() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let model = voxelize_file(
+        &fixture("tetrahedron_ascii.stl"),
+        &VoxelizationSettings::default(),
+    )
+    .unwrap();
+    let project = Project {
+        model,
+        source_path: Some(OsStr::from_bytes(b"model\xff.stl").into()),
+        source_hash: None,
+        camera: None,
+    };
+    let path = temp_path("non_utf8.mvproj");
+    let error = save_project(&project, &path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("UTF-8"), "{}", error);
+    assert!(!path.exists());
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::box_mesh;
+use glam::Vec3;
+use model_voxelization::mesh::Normalization;
+use model_voxelization::sdf::compute_sdf;
+use model_voxelization::voxelization::VoxelCoord;
+
+// Exact signed distance to the cube from -half to half
+fn cube_distance(position: Vec3, half: f32) -> f32 {
+    let offset = position.abs() - Vec3::splat(half);
+    offset.max(Vec3::ZERO).length() + offset.max_element().min(0.0)
+}
+
+#[test]
+fn unit_cube_distances_have_the_right_sign_and_magnitude() {
+    let sdf = compute_sdf(
+        &[box_mesh(Vec3::splat(-0.5), Vec3::splat(0.5))],
+        Normalization::default(),
+        5,
+    );
+
+    let mut inside = 0;
+    for (coord, value) in sdf.iter() {
+        let position = sdf.sample_position(coord);
+        let expected = cube_distance(position, 0.5);
+        assert_eq!(value < 0.0, expected < 0.0, "sign at {:?}", coord);
+        assert!(
+            (value - expected).abs() < 1e-3,
+            "{:?}: {} != {}",
+            coord,
+            value,
+            expected
+        );
+        inside += (value < 0.0) as usize;
+    }
+    // 16 of the 32 samples per axis lie within -0.5~0.5
+    assert_eq!(inside, 16 * 16 * 16);
+    assert_eq!(sdf.get(VoxelCoord(0, 0, 0)), Some(-0.46875));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::fixture;
+use glam::Vec3;
+use model_voxelization::export::{write_stl, ExportOptions};
+use model_voxelization::import::{load_model, ImportError};
+use model_voxelization::meshing::MeshingMode;
+use model_voxelization::voxelization::{voxelize_file, VoxelizationSettings};
+
+#[test]
+fn ascii_stl_loads_named_solid() {
+    let meshes = load_model(&fixture("tetrahedron_ascii.stl")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "tetrahedron");
+    assert_eq!(meshes[0].triangle_count(), 4);
+}
+
+#[test]
+fn binary_stl_with_solid_header_loads_as_binary() {
+    let meshes = load_model(&fixture("tetrahedron_binary.stl")).unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].triangle_count(), 4);
+}
+
+#[test]
+fn ascii_and_binary_stl_have_identical_triangles() {
+    let ascii = load_model(&fixture("tetrahedron_ascii.stl")).unwrap();
+    let binary = load_model(&fixture("tetrahedron_binary.stl")).unwrap();
+    let ascii_triangles: Vec<_> = ascii[0].triangles().collect();
+    let binary_triangles: Vec<_> = binary[0].triangles().collect();
+    assert_eq!(ascii_triangles, binary_triangles);
+}
+
+#[test]
+fn truncated_binary_stl_is_a_parse_error() {
+    let result = load_model(&fixture("truncated_binary.stl"));
+    assert!(matches!(result, Err(ImportError::Parse(_))));
+}
+
+#[test]
+fn stl_voxelizes_like_any_other_model() {
+    let settings = VoxelizationSettings {
+        octree_depth: 4,
+        ..Default::default()
+    };
+    let ascii = voxelize_file(&fixture("tetrahedron_ascii.stl"), &settings).unwrap();
+    let binary = voxelize_file(&fixture("tetrahedron_binary.stl"), &settings).unwrap();
+
+    assert!(ascii.voxel_count() > 0);
+    assert_eq!(ascii.voxel_count(), binary.voxel_count());
+    assert_eq!(ascii.normalization, binary.normalization);
+}
+
+#[test]
+fn stl_export_writes_facet_normals() {
+    let settings = VoxelizationSettings {
+        octree_depth: 4,
+        ..Default::default()
+    };
+    let model = voxelize_file(&fixture("tetrahedron_ascii.stl"), &settings).unwrap();
+    // Smooth meshes have vertex normals that differ from the facet normals
+    let options = ExportOptions {
+        meshing: MeshingMode::SurfaceNets,
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    write_stl(&model, &options, &mut bytes).unwrap();
+
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    assert!(count > 0);
+    assert_eq!(bytes.len(), 84 + 50 * count);
+    for facet in bytes[84..].chunks_exact(50) {
+        let [normal, a, b, c] = [0, 1, 2, 3].map(|v| {
+            let component = |i: usize| {
+                let offset = 12 * v + 4 * i;
+                f32::from_le_bytes(facet[offset..offset + 4].try_into().unwrap())
+            };
+            Vec3::new(component(0), component(1), component(2))
+        });
+        let expected = (b - a).cross(c - a).normalize();
+        assert!(
+            normal.abs_diff_eq(expected, 1e-5),
+            "{} != {}",
+            normal,
+            expected
+        );
+    }
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::fixture;
+use model_voxelization::export::{write_sdf_vdb, write_vdb, ExportOptions, VdbGrid};
+use model_voxelization::sdf::sdf_file;
+use model_voxelization::voxelization::{voxelize_file, VoxelizationSettings};
+
+// Little endian reader over the bytes of a written file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> &[u8] {
+        let bytes = &self.bytes[self.pos..self.pos + count];
+        self.pos += count;
+        bytes
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+
+    fn string(&mut self) -> String {
+        let length = self.u32() as usize;
+        String::from_utf8(self.take(length).to_vec()).unwrap()
+    }
+
+    fn metadata(&mut self) -> Vec<(String, String)> {
+        (0..self.u32())
+            .map(|_| {
+                let name = self.string();
+                assert_eq!(self.string(), "string");
+                (name, self.string())
+            })
+            .collect()
+    }
+}
+
+// Check the header and grid descriptor of a file with one float grid, and that the
+// descriptor's stream offsets point at the grid, its leaf buffers and the file end
+fn check_structure(bytes: &[u8], name: &str, class: &str) {
+    let mut reader = Reader { bytes, pos: 0 };
+    assert_eq!(reader.i64(), 0x5644_4220, "magic");
+    assert_eq!(reader.u32(), 224, "file version");
+    assert_eq!([reader.u32(), reader.u32()], [10, 0], "library version");
+    assert_eq!(reader.take(1), [1], "grid offsets flag");
+    let uuid = String::from_utf8(reader.take(36).to_vec()).unwrap();
+    assert_eq!(uuid.matches('-').count(), 4, "{}", uuid);
+    assert_eq!(
+        reader.metadata(),
+        [("creator".to_string(), "model_voxelization".to_string())]
+    );
+
+    assert_eq!(reader.u32(), 1, "grid count");
+    assert_eq!(reader.string(), name);
+    assert_eq!(reader.string(), "Tree_float_5_4_3");
+    assert_eq!(reader.string(), "", "instance parent");
+    let [grid, block, end] = [reader.i64(), reader.i64(), reader.i64()].map(|o| o as usize);
+
+    assert_eq!(grid, reader.pos, "grid offset");
+    assert_eq!(end, bytes.len(), "end offset");
+    assert!(grid < block && block < end);
+
+    // The grid starts with its compression flags and metadata
+    assert_eq!(reader.u32(), 1, "zip compression");
+    assert_eq!(
+        reader.metadata(),
+        [
+            ("class".to_string(), class.to_string()),
+            ("name".to_string(), name.to_string())
+        ]
+    );
+    assert_eq!(reader.string(), "UniformScaleTranslateMap");
+
+    // The buffers start with the first leaf's 512 bit value mask and value array
+    let mut reader = Reader { bytes, pos: block };
+    assert!(reader.take(64).iter().any(|&byte| byte != 0), "leaf mask");
+    assert_eq!(reader.take(1), [6], "value array flags");
+}
+
+#[test]
+fn vdb_files_have_consistent_headers_and_offsets() {
+    let settings = VoxelizationSettings {
+        octree_depth: 5,
+        ..Default::default()
+    };
+    let model = voxelize_file(&fixture("tetrahedron_ascii.stl"), &settings).unwrap();
+
+    for (grid, name, class) in [
+        (VdbGrid::Occupancy, "density", "fog volume"),
+        (VdbGrid::LevelSet, "surface", "level set"),
+    ] {
+        let options = ExportOptions {
+            vdb_grid: grid,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_vdb(&model, &options, &mut bytes).unwrap();
+        check_structure(&bytes, name, class);
+
+        // Only the file UUID after the 21 byte version header differs between writes
+        let mut again = Vec::new();
+        write_vdb(&model, &options, &mut again).unwrap();
+        assert_eq!(bytes[..21], again[..21]);
+        assert_eq!(bytes[57..], again[57..]);
+    }
+
+    let sdf = sdf_file(&fixture("tetrahedron_ascii.stl"), 5).unwrap();
+    let mut bytes = Vec::new();
+    write_sdf_vdb(&sdf, &mut bytes).unwrap();
+    check_structure(&bytes, "surface", "level set");
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{box_mesh, temp_path};
+use flate2::read::GzDecoder;
+use glam::Vec3;
+use model_voxelization::export::{
+    write_nrrd, write_png_slices, write_raw, ExportOptions, MeshUnits, SliceAxis,
+};
+use model_voxelization::mesh::Normalization;
+use model_voxelization::voxelization::{
+    voxelize_meshes, FillMode, VoxelCoord, VoxelizationSettings, VoxelizedModel,
+};
+use std::collections::HashSet;
+use std::io::Read;
+
+const DEPTH: usize = 5;
+const SIZE: usize = 1 << DEPTH;
+
+// An L shaped solid of two submodels, so a flipped or swapped axis shows
+fn model(normalization: Normalization) -> VoxelizedModel {
+    let meshes = [
+        box_mesh(Vec3::new(-0.8, -0.9, -0.5), Vec3::new(0.6, -0.2, 0.4)),
+        box_mesh(Vec3::new(-0.8, -0.5, -0.5), Vec3::new(-0.3, 0.85, 0.1)),
+    ];
+    let settings = VoxelizationSettings {
+        octree_depth: DEPTH,
+        fill_mode: FillMode::Solid,
+        ..Default::default()
+    };
+    voxelize_meshes(&meshes, normalization, &settings)
+}
+
+// Occupied voxels of all submodels, counted from the grid's minimum corner
+fn occupied(model: &VoxelizedModel) -> HashSet<[usize; 3]> {
+    let half = SIZE as i32 / 2;
+    model
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.octree.iter_leaves(DEPTH))
+        .map(|VoxelCoord(x, y, z)| [x, y, z].map(|c| (c + half) as usize))
+        .collect()
+}
+
+#[test]
+fn raw_volume_holds_every_voxel() {
+    let model = model(Normalization::default());
+    let mut raw = Vec::new();
+    write_raw(&model, &mut raw).unwrap();
+    assert_eq!(raw.len(), SIZE * SIZE * SIZE);
+
+    // x runs fastest, then y, then z
+    let mut voxels = HashSet::new();
+    for (i, &value) in raw.iter().enumerate() {
+        match value {
+            0 => {}
+            255 => {
+                voxels.insert([i % SIZE, i / SIZE % SIZE, i / (SIZE * SIZE)]);
+            }
+            value => panic!("value {} at {}", value, i),
+        }
+    }
+    assert_eq!(voxels, occupied(&model));
+}
+
+#[test]
+fn nrrd_header_places_the_raw_volume() {
+    let model = model(Normalization {
+        center: Vec3::new(1.0, 2.0, 3.0),
+        scale: 0.5,
+        size: Vec3::splat(4.0),
+    });
+    let mut raw = Vec::new();
+    write_raw(&model, &mut raw).unwrap();
+
+    for (mesh_units, spacing, origin) in [
+        // Cells of 2 / 32 from the center of the cell at -1
+        (
+            MeshUnits::Normalized,
+            "0.0625",
+            "(-0.96875,-0.96875,-0.96875)",
+        ),
+        // Scaled by 2 around the center
+        (MeshUnits::Original, "0.125", "(-0.9375,0.0625,1.0625)"),
+    ] {
+        let options = ExportOptions {
+            mesh_units,
+            ..Default::default()
+        };
+        let mut file = Vec::new();
+        write_nrrd(&model, &options, &mut file).unwrap();
+
+        let end = file.windows(2).position(|w| w == b"\n\n").unwrap();
+        let header = std::str::from_utf8(&file[..end]).unwrap();
+        let fields: Vec<&str> = header.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            fields,
+            [
+                "NRRD0004".to_string(),
+                "type: uint8".to_string(),
+                "dimension: 3".to_string(),
+                "space dimension: 3".to_string(),
+                "sizes: 32 32 32".to_string(),
+                format!("space directions: ({spacing},0,0) (0,{spacing},0) (0,0,{spacing})"),
+                format!("space origin: {origin}"),
+                "endian: little".to_string(),
+                "encoding: gzip".to_string(),
+            ]
+        );
+
+        let mut data = Vec::new();
+        GzDecoder::new(&file[end + 2..])
+            .read_to_end(&mut data)
+            .unwrap();
+        assert!(data == raw);
+    }
+}
+
+#[test]
+fn png_slices_show_the_layers_along_the_axis() {
+    let model = model(Normalization::default());
+    let voxels = occupied(&model);
+    let flip = |c: u32| SIZE - 1 - c as usize;
+
+    for axis in SliceAxis::ALL {
+        let path = temp_path(&format!("slices_{}.png", axis.label()));
+        let options = ExportOptions {
+            slice_axis: axis,
+            ..Default::default()
+        };
+        write_png_slices(&model, &options, &path).unwrap();
+
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        let slice_path = |layer| path.with_file_name(format!("{}_{:04}.png", stem, layer));
+        let mut slices = Vec::new();
+        for layer in 0..SIZE {
+            slices.push(image::open(slice_path(layer)).unwrap().into_luma8());
+            std::fs::remove_file(slice_path(layer)).unwrap();
+        }
+        assert!(!slice_path(SIZE).exists());
+
+        // Seen from the positive end of the axis, +Y up for X and Z slices
+        for (layer, slice) in slices.iter().enumerate() {
+            assert_eq!(slice.dimensions(), (SIZE as u32, SIZE as u32));
+            for (column, row, pixel) in slice.enumerate_pixels() {
+                let voxel = match axis {
+                    SliceAxis::X => [layer, flip(row), flip(column)],
+                    SliceAxis::Y => [column as usize, layer, row as usize],
+                    SliceAxis::Z => [column as usize, flip(row), layer],
+                };
+                let expected = if voxels.contains(&voxel) { 255 } else { 0 };
+                assert_eq!(pixel.0, [expected], "{:?} slice {}", axis, layer);
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::box_mesh;
+use glam::Vec3;
+use model_voxelization::export::write_vox;
+use model_voxelization::mesh::Normalization;
+use model_voxelization::voxelization::{
+    voxelize_meshes, FillMode, VoxelCoord, VoxelizationSettings,
+};
+use std::collections::{HashMap, HashSet};
+
+// Little endian reader over the content of a chunk
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..self.pos + count];
+        self.pos += count;
+        bytes
+    }
+
+    fn i32(&mut self) -> i32 {
+        i32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn string(&mut self) -> String {
+        let length = self.i32() as usize;
+        String::from_utf8(self.take(length).to_vec()).unwrap()
+    }
+
+    fn dict(&mut self) -> HashMap<String, String> {
+        (0..self.i32())
+            .map(|_| (self.string(), self.string()))
+            .collect()
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+// The children of the MAIN chunk as (id, content)
+fn chunks(bytes: &[u8]) -> Vec<(String, &[u8])> {
+    let mut reader = Reader { bytes, pos: 0 };
+    assert_eq!(reader.take(4), b"VOX ");
+    assert_eq!(reader.i32(), 200);
+    assert_eq!(reader.take(4), b"MAIN");
+    assert_eq!(reader.i32(), 0);
+    assert_eq!(reader.i32() as usize, bytes.len() - 20);
+
+    let mut chunks = Vec::new();
+    while !reader.done() {
+        let id = String::from_utf8(reader.take(4).to_vec()).unwrap();
+        let length = reader.i32() as usize;
+        assert_eq!(reader.i32(), 0, "{} has children", id);
+        chunks.push((id, reader.take(length)));
+    }
+    chunks
+}
+
+#[test]
+fn large_models_are_split_into_placed_chunks() {
+    // A flat slab spanning most of the depth 9 grid of 512 voxels per axis, and a
+    // small box of a second submodel
+    let meshes = [
+        box_mesh(Vec3::new(-0.95, -0.2, -0.9), Vec3::new(0.9, 0.1, 0.95)),
+        box_mesh(Vec3::splat(0.3), Vec3::splat(0.6)),
+    ];
+    let settings = VoxelizationSettings {
+        octree_depth: 9,
+        fill_mode: FillMode::Surface,
+        ..Default::default()
+    };
+    let model = voxelize_meshes(&meshes, Normalization::default(), &settings);
+
+    let mut bytes = Vec::new();
+    write_vox(&model, &mut bytes).unwrap();
+    let chunks = chunks(&bytes);
+
+    // SIZE and XYZI pairs come first, one pair per model
+    let mut models = Vec::new();
+    let mut rest = chunks.as_slice();
+    while let [(size_id, size), (xyzi_id, xyzi), tail @ ..] = rest {
+        if size_id != "SIZE" {
+            break;
+        }
+        assert_eq!(xyzi_id, "XYZI");
+        let mut reader = Reader {
+            bytes: size,
+            pos: 0,
+        };
+        let size = [reader.i32(), reader.i32(), reader.i32()];
+        assert!(size.iter().all(|s| (1..=256).contains(s)), "{:?}", size);
+
+        let mut reader = Reader {
+            bytes: xyzi,
+            pos: 0,
+        };
+        let count = reader.i32() as usize;
+        let voxels: Vec<[u8; 4]> = (0..count)
+            .map(|_| reader.take(4).try_into().unwrap())
+            .collect();
+        assert!(reader.done());
+        for voxel in &voxels {
+            assert!((0..3).all(|axis| (voxel[axis] as i32) < size[axis]));
+        }
+        models.push((size, voxels));
+        rest = tail;
+    }
+    // The slab needs two models along x and z
+    assert!(models.len() >= 5, "{} models", models.len());
+
+    // Root transform -> group -> a transform and shape per model
+    let mut nodes = rest.iter();
+    let (id, root) = nodes.next().unwrap();
+    assert_eq!(id, "nTRN");
+    let mut reader = Reader {
+        bytes: root,
+        pos: 0,
+    };
+    assert_eq!(reader.i32(), 0);
+    reader.dict();
+    assert_eq!(reader.i32(), 1);
+
+    let (id, group) = nodes.next().unwrap();
+    assert_eq!(id, "nGRP");
+    let mut reader = Reader {
+        bytes: group,
+        pos: 0,
+    };
+    assert_eq!(reader.i32(), 1);
+    reader.dict();
+    let children: Vec<i32> = (0..reader.i32()).map(|_| reader.i32()).collect();
+    assert_eq!(children.len(), models.len());
+
+    // Voxels of every submodel, placed back in the signed, Y-up voxel grid
+    let mut placed: Vec<HashSet<VoxelCoord>> = vec![HashSet::new(); model.meshes.len()];
+    let mut placed_count = 0;
+    for &child in &children {
+        let (id, transform) = nodes.next().unwrap();
+        assert_eq!(id, "nTRN");
+        let mut reader = Reader {
+            bytes: transform,
+            pos: 0,
+        };
+        assert_eq!(reader.i32(), child);
+        reader.dict();
+        let shape_id = reader.i32();
+        assert_eq!(reader.i32(), -1);
+        reader.i32();
+        assert_eq!(reader.i32(), 1);
+        let frame = reader.dict();
+        let translation: Vec<i32> = frame["_t"].split(' ').map(|t| t.parse().unwrap()).collect();
+
+        let (id, shape) = nodes.next().unwrap();
+        assert_eq!(id, "nSHP");
+        let mut reader = Reader {
+            bytes: shape,
+            pos: 0,
+        };
+        assert_eq!(reader.i32(), shape_id);
+        reader.dict();
+        assert_eq!(reader.i32(), 1);
+        let (size, voxels) = &models[reader.i32() as usize];
+
+        // MagicaVoxel puts voxel (0, 0, 0) of a model at its translation minus
+        // half its size, Z-up
+        for voxel in voxels {
+            let [x, y, z] =
+                [0, 1, 2].map(|axis| translation[axis] - size[axis] / 2 + voxel[axis] as i32);
+            let mesh = voxel[3] as usize - 1;
+            assert!(placed[mesh].insert(VoxelCoord(x, z, -1 - y)));
+            placed_count += 1;
+        }
+    }
+
+    let (id, palette) = nodes.next().unwrap();
+    assert_eq!(id, "RGBA");
+    assert_eq!(palette.len(), 256 * 4);
+    assert!(nodes.next().is_none());
+
+    assert_eq!(placed_count, model.voxel_count());
+    for (placed, mesh) in placed.iter().zip(&model.meshes) {
+        let voxels: HashSet<VoxelCoord> = mesh.octree.iter_leaves(9).collect();
+        assert_eq!(*placed, voxels, "{}", mesh.name);
+    }
+}